@@ -0,0 +1,481 @@
+use crate::{add_to_library, unexpected_exit};
+use anyhow::Result;
+use clipforge_core::capture::x11::create_capture_source;
+use clipforge_core::config::Config;
+use clipforge_core::encode::ffmpeg::{build_clip_encoding, build_replay_command};
+use clipforge_core::encode::hw_probe::{probe_encoders, select_encoder};
+use clipforge_core::library::SourceType;
+use clipforge_core::process::{FfmpegProcess, ProcessExit};
+use clipforge_core::replay::budget::mib;
+use clipforge_core::replay::daemon::{
+    ControlSocket, DaemonPaths, DaemonRequest, DaemonResponse, DaemonStatus, PendingRequest,
+    ReplayLock,
+};
+use clipforge_core::replay::lookahead::{PendingSave, PendingSaves};
+use clipforge_core::replay::pause::PAUSE_POLL_INTERVAL;
+use clipforge_core::replay::record::COLLECT_INTERVAL;
+use clipforge_core::replay::save::save_replay;
+use clipforge_core::replay::{
+    AutoPause, BufferRecording, Migration, PauseChange, ReplayRing, RingFit, RingPlan,
+    SystemSignals,
+};
+use clipforge_core::runner::SystemRunner;
+use std::path::PathBuf;
+
+/// A running capture into the replay ring
+struct Buffer {
+    config: Config,
+    runner: SystemRunner,
+    ring: ReplayRing,
+    /// Moves segments to the disk tier, if the ring has one
+    migration: Option<Migration>,
+    /// The capture, unless auto-paused
+    process: Option<FfmpegProcess>,
+    started_at: chrono::DateTime<chrono::Local>,
+    /// Full recording started from the buffered footage
+    recording: Option<BufferRecording>,
+    /// Look-ahead saves waiting for footage
+    pending: PendingSaves,
+    auto_pause: AutoPause,
+}
+
+impl Buffer {
+    async fn start(config: Config) -> Result<Self> {
+        let runner = SystemRunner::from_config(&config);
+        let mut ring = ReplayRing::for_config(&config);
+        ring.cleanup()?;
+
+        let plan = RingPlan::for_config(&config);
+        if let RingFit::Tight(message) = plan.check()? {
+            println!("Warning: {message}");
+        }
+
+        let process = spawn_capture(&config, &runner, &mut ring, 0).await?;
+        println!(
+            "Replay buffer active ({} seconds, ~{} MiB)",
+            config.replay.duration_secs,
+            mib(plan.estimated_bytes)
+        );
+        if let (Some(disk), Some(tier)) = (&plan.disk, &ring.disk) {
+            println!(
+                "Footage older than {}s moves to {} (~{} MiB)",
+                config.replay.ram_secs,
+                tier.dir.display(),
+                mib(disk.estimated_bytes)
+            );
+        }
+
+        Ok(Self {
+            auto_pause: AutoPause::new(config.replay.auto_pause.clone()),
+            config,
+            runner,
+            migration: Migration::spawn(&ring),
+            ring,
+            process: Some(process),
+            started_at: chrono::Local::now(),
+            recording: None,
+            pending: PendingSaves::default(),
+        })
+    }
+
+    /// Sample the desktop and pause or resume the capture to match
+    async fn check_auto_pause(&mut self) -> Result<()> {
+        if !self.auto_pause.config().is_enabled() && self.auto_pause.paused().is_none() {
+            return Ok(());
+        }
+        // Pausing would cut a recording from the buffer short
+        if self.recording.is_some() {
+            return Ok(());
+        }
+        match self.auto_pause.poll(&SystemSignals).await {
+            Some(PauseChange::Paused(reason)) => {
+                self.flush_pending().await;
+                if let Some(mut process) = self.process.take() {
+                    process.stop_graceful().await?;
+                }
+                println!("Replay buffer paused ({})", reason.label());
+            }
+            Some(PauseChange::Resumed) => {
+                // Footage from before the pause stays saveable
+                let first_segment = self.ring.resume()?;
+                self.process = Some(
+                    spawn_capture(&self.config, &self.runner, &mut self.ring, first_segment)
+                        .await?,
+                );
+                self.started_at = chrono::Local::now();
+                println!("Replay buffer resumed");
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    async fn stop(mut self) -> Result<()> {
+        // Save a running recording while its in-progress segment still exists
+        if self.recording.is_some() {
+            self.stop_recording().await?;
+        }
+        self.flush_pending().await;
+        if let Some(mut process) = self.process.take() {
+            process.stop_graceful().await?;
+        }
+        self.migration = None;
+        self.ring.cleanup()?;
+        Ok(())
+    }
+
+    /// Save what can still be saved and clear the ring once the capture
+    /// is gone for good: it exited on its own or could not be restarted
+    async fn abandon(&mut self) -> Result<()> {
+        if self.recording.is_some() {
+            self.stop_recording().await?;
+        }
+        self.flush_pending().await;
+        self.migration = None;
+        self.ring.cleanup()?;
+        Ok(())
+    }
+
+    /// Neither capturing nor auto-paused, so no capture will come back
+    fn capture_lost(&self) -> bool {
+        self.process.is_none() && self.auto_pause.paused().is_none()
+    }
+
+    fn status(&self) -> DaemonStatus {
+        DaemonStatus {
+            pid: std::process::id(),
+            duration_secs: self.config.replay.duration_secs,
+            buffered_secs: self.ring.buffered_secs().unwrap_or(0.0),
+            segments: self.ring.live_segments().map(|s| s.len()).unwrap_or(0),
+            started_at: self.started_at.to_rfc3339(),
+            cache_dir: self.ring.segment_dir.clone(),
+            recording: self.recording.as_ref().map(|r| r.output().to_path_buf()),
+            pending_saves: self.pending.status(&self.ring),
+            paused: self.auto_pause.paused(),
+        }
+    }
+
+    async fn save(
+        &mut self,
+        seconds: u32,
+        after_secs: u32,
+        output: Option<PathBuf>,
+    ) -> Result<DaemonResponse> {
+        let replays_dir = &self.config.paths.replays_dir;
+        let output = output.unwrap_or_else(|| {
+            let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+            replays_dir.join(format!("replay_{timestamp}.mkv"))
+        });
+        std::fs::create_dir_all(replays_dir)?;
+
+        if after_secs > 0 {
+            if let Some(reason) = self.auto_pause.paused() {
+                anyhow::bail!("replay buffer is paused ({})", reason.label());
+            }
+            let (id, path) = self
+                .pending
+                .request(&self.ring, seconds, after_secs, output)?;
+            let remaining_secs = self
+                .pending
+                .status(&self.ring)
+                .iter()
+                .find(|p| p.id == id)
+                .map_or(0.0, |p| p.remaining_secs);
+            println!("Pending: {} ({remaining_secs:.0}s to go)", path.display());
+            return Ok(DaemonResponse::SavePending {
+                id,
+                path,
+                remaining_secs,
+            });
+        }
+
+        let path = save_replay(&self.runner, &self.ring, seconds, 0, &output).await?;
+        println!("Saved: {}", path.display());
+        add_to_library(&self.config, &self.runner, &path, SourceType::Replay, None).await;
+        Ok(DaemonResponse::Saved { path })
+    }
+
+    /// Write look-ahead saves whose footage has been recorded
+    async fn save_due(&mut self) {
+        match self.pending.take_due(&self.ring) {
+            Ok(due) => self.write_pending(due).await,
+            Err(e) => println!("Warning: could not check pending saves: {e}"),
+        }
+    }
+
+    /// Write every waiting look-ahead save with the footage so far
+    async fn flush_pending(&mut self) {
+        let saves = self.pending.take_all();
+        self.write_pending(saves).await;
+    }
+
+    async fn write_pending(&self, saves: Vec<PendingSave>) {
+        for save in saves {
+            match save.save(&self.runner, &self.ring).await {
+                Ok(path) => {
+                    println!("Saved: {}", path.display());
+                    add_to_library(&self.config, &self.runner, &path, SourceType::Replay, None)
+                        .await;
+                }
+                Err(e) => println!("Failed to save {}: {e}", save.output.display()),
+            }
+        }
+    }
+
+    async fn start_recording(&mut self, output: Option<PathBuf>) -> Result<(PathBuf, f64)> {
+        if let Some(ref recording) = self.recording {
+            anyhow::bail!("already recording to {}", recording.output().display());
+        }
+        if let Some(reason) = self.auto_pause.paused() {
+            anyhow::bail!("replay buffer is paused ({})", reason.label());
+        }
+        let recordings_dir = &self.config.paths.recordings_dir;
+        let output = output.unwrap_or_else(|| {
+            let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+            recordings_dir.join(format!("recording_{timestamp}.mkv"))
+        });
+        std::fs::create_dir_all(recordings_dir)?;
+
+        let recording = BufferRecording::start(&self.ring, &output).await?;
+        let buffered = recording.duration();
+        println!(
+            "Recording to {} ({buffered:.1}s from the buffer)",
+            output.display()
+        );
+        self.recording = Some(recording);
+        Ok((output, buffered))
+    }
+
+    async fn collect(&mut self) {
+        if let Some(ref mut recording) = self.recording {
+            match recording.collect(&self.ring).await {
+                Ok(collected) if collected.missed > 0 => println!(
+                    "Warning: {} segments were overwritten before they could be recorded",
+                    collected.missed
+                ),
+                Ok(_) => {}
+                Err(e) => println!("Warning: could not collect replay segments: {e}"),
+            }
+        }
+    }
+
+    async fn stop_recording(&mut self) -> Result<PathBuf> {
+        let Some(recording) = self.recording.take() else {
+            anyhow::bail!("not recording");
+        };
+        if recording.missed() > 0 {
+            println!(
+                "Warning: the recording skips {} overwritten segments",
+                recording.missed()
+            );
+        }
+        let path = recording.finish(&self.runner, &self.ring).await?;
+        println!("Saved: {}", path.display());
+        add_to_library(
+            &self.config,
+            &self.runner,
+            &path,
+            SourceType::Recording,
+            None,
+        )
+        .await;
+        Ok(path)
+    }
+}
+
+/// Start capturing into `ring` from segment `first_segment` with a freshly
+/// probed encoder and source; the ring re-encodes clips with the same one
+async fn spawn_capture(
+    config: &Config,
+    runner: &SystemRunner,
+    ring: &mut ReplayRing,
+    first_segment: u64,
+) -> Result<FfmpegProcess> {
+    let encoders = probe_encoders(runner).await;
+    let enc = select_encoder(&encoders, &config.recording.encoder);
+    ring.clip_encoding = Some(build_clip_encoding(config, enc));
+    let source = create_capture_source(config).await?;
+    let args = build_replay_command(config, enc, &source, first_segment).await?;
+    Ok(FfmpegProcess::spawn(runner, args).await?)
+}
+
+/// Wait for the capture to exit; never resolves while paused
+async fn wait_for_exit(process: Option<&FfmpegProcess>) -> ProcessExit {
+    match process {
+        Some(process) => process.wait().await,
+        None => std::future::pending().await,
+    }
+}
+
+enum Event {
+    Interrupted,
+    Collect,
+    CheckPause,
+    Exited(ProcessExit),
+    Request(PendingRequest),
+}
+
+/// Run the replay buffer until Ctrl+C or a `stop` request, serving control
+/// requests on the socket next to the segment directory
+pub async fn run(mut config: Config, duration_override: Option<u32>) -> Result<()> {
+    if let Some(seconds) = duration_override {
+        config.replay.duration_secs = seconds;
+    }
+    let paths = DaemonPaths::for_cache_dir(&config.paths.replay_cache_dir);
+    let _lock = ReplayLock::acquire(&paths.lock)?;
+    let mut socket = ControlSocket::bind(&paths.socket)?;
+
+    let mut buffer = Buffer::start(config).await?;
+    println!("Control socket: {}", paths.socket.display());
+    println!("Press Ctrl+C to stop");
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut collect = tokio::time::interval(COLLECT_INTERVAL);
+    let mut check_pause = tokio::time::interval(PAUSE_POLL_INTERVAL);
+    loop {
+        let event = tokio::select! {
+            res = &mut ctrl_c => {
+                res?;
+                Event::Interrupted
+            }
+            exit = wait_for_exit(buffer.process.as_ref()) => Event::Exited(exit),
+            _ = collect.tick() => Event::Collect,
+            _ = check_pause.tick() => Event::CheckPause,
+            pending = socket.next() => Event::Request(pending?),
+        };
+
+        let pending = match event {
+            Event::Interrupted => break,
+            Event::Collect => {
+                buffer.collect().await;
+                buffer.save_due().await;
+                continue;
+            }
+            Event::CheckPause => {
+                if let Err(e) = buffer.check_auto_pause().await {
+                    buffer.abandon().await?;
+                    return Err(e);
+                }
+                continue;
+            }
+            Event::Exited(exit) => {
+                buffer.abandon().await?;
+                return Err(unexpected_exit(exit).into());
+            }
+            Event::Request(pending) => pending,
+        };
+
+        let response = match pending.request {
+            DaemonRequest::Status => DaemonResponse::Status(buffer.status()),
+            DaemonRequest::Save {
+                seconds,
+                after_secs,
+                ref output,
+            } => match buffer.save(seconds, after_secs, output.clone()).await {
+                Ok(response) => response,
+                Err(e) => DaemonResponse::Error {
+                    message: e.to_string(),
+                },
+            },
+            DaemonRequest::Stop => {
+                let _ = pending.respond(&DaemonResponse::Stopping).await;
+                break;
+            }
+            DaemonRequest::ReloadConfig => match reload(&mut buffer, duration_override).await {
+                Ok(restarted) => DaemonResponse::Reloaded { restarted },
+                // The old capture was stopped and the new one did not start
+                Err(e) if buffer.capture_lost() => {
+                    let message = e.to_string();
+                    let _ = pending.respond(&DaemonResponse::Error { message }).await;
+                    buffer.abandon().await?;
+                    return Err(e.context("could not restart the replay buffer"));
+                }
+                Err(e) => DaemonResponse::Error {
+                    message: e.to_string(),
+                },
+            },
+            DaemonRequest::StartRecording { ref output } => {
+                match buffer.start_recording(output.clone()).await {
+                    Ok((path, buffered_secs)) => DaemonResponse::RecordingStarted {
+                        path,
+                        buffered_secs,
+                    },
+                    Err(e) => DaemonResponse::Error {
+                        message: e.to_string(),
+                    },
+                }
+            }
+            DaemonRequest::StopRecording => match buffer.stop_recording().await {
+                Ok(path) => DaemonResponse::Saved { path },
+                Err(e) => DaemonResponse::Error {
+                    message: e.to_string(),
+                },
+            },
+        };
+        let _ = pending.respond(&response).await;
+    }
+
+    println!("\nStopping replay buffer...");
+    buffer.stop().await
+}
+
+/// Apply the config file, restarting the capture only if settings it was
+/// started with changed. Returns whether it restarted.
+async fn reload(buffer: &mut Buffer, duration_override: Option<u32>) -> Result<bool> {
+    let mut config = Config::load()?;
+    config.validate()?;
+    if let Some(seconds) = duration_override {
+        config.replay.duration_secs = seconds;
+    }
+
+    // Auto-pause conditions apply from the next check without a restart
+    buffer
+        .auto_pause
+        .set_config(config.replay.auto_pause.clone());
+    let capture_settings = |c: &Config| {
+        let mut replay = c.replay.clone();
+        replay.auto_pause = Default::default();
+        serde_json::json!([c.recording, replay, c.paths, c.encoder_process])
+    };
+    if capture_settings(&config) == capture_settings(&buffer.config) {
+        buffer.config = config;
+        return Ok(false);
+    }
+    if buffer.recording.is_some() {
+        anyhow::bail!("a recording from the buffer is running; stop it before reloading");
+    }
+    if !buffer.pending.is_empty() {
+        anyhow::bail!("replay saves are still pending; reload once they are written");
+    }
+    if config.paths.replay_cache_dir != buffer.config.paths.replay_cache_dir {
+        anyhow::bail!("replay_cache_dir cannot change while the buffer runs; restart it instead");
+    }
+    if config.paths.replay_disk_dir != buffer.config.paths.replay_disk_dir {
+        anyhow::bail!("replay_disk_dir cannot change while the buffer runs; restart it instead");
+    }
+
+    // Refuse settings the new capture cannot run with while the old one
+    // still runs
+    RingPlan::for_config(&config).check()?;
+    create_capture_source(&config).await?;
+
+    // The next resume starts the capture with the new settings, which
+    // cannot be joined with the footage so far
+    if buffer.process.is_none() {
+        buffer.migration = None;
+        buffer.ring = ReplayRing::for_config(&config);
+        buffer.ring.cleanup()?;
+        buffer.migration = Migration::spawn(&buffer.ring);
+        buffer.config = config;
+        return Ok(false);
+    }
+
+    println!("Config changed, restarting capture...");
+    if let Some(mut process) = buffer.process.take() {
+        process.stop_graceful().await?;
+    }
+    let restarted = Buffer::start(config).await?;
+    *buffer = restarted;
+    Ok(true)
+}
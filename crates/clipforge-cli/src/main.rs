@@ -4,14 +4,24 @@ use clipforge_core::audio::list_audio_sources;
 use clipforge_core::capture::x11::create_capture_source;
 use clipforge_core::config::Config;
 use clipforge_core::doctor::run_diagnostics;
-use clipforge_core::encode::ffmpeg::{build_recording_command, build_replay_command};
-use clipforge_core::encode::hw_probe::{probe_encoders, select_best_encoder};
+use clipforge_core::encode::ffmpeg::{build_clip_encoding, build_recording_command};
+use clipforge_core::encode::hw_probe::{probe_encoders, select_best_encoder, select_encoder};
+use clipforge_core::error::Error;
 use clipforge_core::export::pipeline::{ExportJob, ExportPipeline};
 use clipforge_core::export::presets::ExportPreset;
-use clipforge_core::process::FfmpegProcess;
+use clipforge_core::library::{index_file, index_parts, index_recovered, Library, SourceType};
+use clipforge_core::process::ProcessExit;
+use clipforge_core::recording::{
+    recover_orphans, PerformanceSummary, RecordingSession, SessionOptions,
+};
+use clipforge_core::replay::daemon::{send_request, DaemonPaths, DaemonRequest, DaemonResponse};
 use clipforge_core::replay::ring::ReplayRing;
 use clipforge_core::replay::save::save_replay;
-use std::path::PathBuf;
+use clipforge_core::runner::SystemRunner;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+mod daemon;
 
 #[derive(Parser)]
 #[command(
@@ -32,9 +42,13 @@ enum Commands {
         #[arg(long, default_value = "screen")]
         mode: String,
 
-        /// Framerate
-        #[arg(long, default_value = "60")]
-        fps: u32,
+        /// Recording profile to apply (e.g. competitive, cinematic, low-end)
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Framerate (overrides the profile/config value)
+        #[arg(long)]
+        fps: Option<u32>,
 
         /// Encoder: auto, h264_vaapi, h264_nvenc, libx264
         #[arg(long, default_value = "auto")]
@@ -45,18 +59,25 @@ enum Commands {
         out: Option<PathBuf>,
     },
 
-    /// Start replay buffer service
+    /// Start replay buffer service, or control a running one
     Replay {
-        /// Replay buffer duration in seconds
-        #[arg(long, default_value = "30")]
-        seconds: u32,
+        #[command(subcommand)]
+        command: Option<ReplayCommand>,
+
+        /// Replay buffer duration in seconds (defaults to the configured duration)
+        #[arg(long)]
+        seconds: Option<u32>,
     },
 
     /// Save last N seconds from running replay buffer
     SaveReplay {
-        /// Seconds to save
-        #[arg(long, default_value = "30")]
-        seconds: u32,
+        /// Seconds to save (defaults to the configured save length)
+        #[arg(long)]
+        seconds: Option<u32>,
+
+        /// Seconds to keep recording after now before saving
+        #[arg(long, default_value = "0")]
+        after: u32,
 
         /// Output file
         #[arg(short, long)]
@@ -84,6 +105,10 @@ enum Commands {
         /// Trim end (seconds)
         #[arg(long)]
         trim_end: Option<f64>,
+
+        /// Tonemap HDR input to SDR (BT.709) for uploading
+        #[arg(long)]
+        tonemap_sdr: bool,
     },
 
     /// List available devices (encoders, audio sources)
@@ -91,6 +116,58 @@ enum Commands {
 
     /// Run system diagnostics
     Doctor,
+
+    /// Inspect the recording library
+    Library {
+        #[command(subcommand)]
+        command: LibraryCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReplayCommand {
+    /// Show the running buffer's state
+    Status,
+
+    /// Save the last N seconds from the running buffer
+    Save {
+        /// Seconds to save (defaults to the configured save length)
+        #[arg(long)]
+        seconds: Option<u32>,
+
+        /// Seconds to keep recording after now before saving
+        #[arg(long, default_value = "0")]
+        after: u32,
+
+        /// Output file
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Stop the running buffer
+    Stop,
+
+    /// Re-read the config file, restarting capture if its settings changed
+    ReloadConfig,
+
+    /// Start a full recording that begins with the buffered footage
+    RecordStart {
+        /// Output file
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Stop the recording started from the buffer and save it
+    RecordStop,
+}
+
+#[derive(Subcommand)]
+enum LibraryCommand {
+    /// Show capture performance for a recording
+    Stats {
+        /// Recording id
+        id: String,
+    },
 }
 
 #[tokio::main]
@@ -104,20 +181,29 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
     let mut config = Config::load().unwrap_or_default();
+    let runner = Arc::new(SystemRunner::from_config(&config));
     config.ensure_dirs()?;
 
     match cli.command {
         Commands::Record {
             mode: _,
+            profile,
             fps,
             encoder,
             out,
         } => {
-            config.recording.fps = fps;
+            if let Some(ref name) = profile {
+                config.apply_profile(name)?;
+            }
+            if let Some(fps) = fps {
+                config.recording.fps = fps;
+            }
+            let fps = config.recording.fps;
+            recover_unfinished(&config, runner.as_ref()).await;
 
-            let encoders = probe_encoders().await;
+            let encoders = probe_encoders(runner.as_ref()).await;
             let enc = if encoder == "auto" {
-                select_best_encoder(&encoders)
+                select_encoder(&encoders, &config.recording.encoder)
             } else {
                 encoders
                     .iter()
@@ -129,55 +215,147 @@ async fn main() -> Result<()> {
             let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
             let output = out.unwrap_or_else(|| PathBuf::from(format!("recording_{timestamp}.mkv")));
 
-            let args = build_recording_command(&config, enc, &source, &output).await;
+            let args = build_recording_command(&config, enc, &source, &output).await?;
             println!("Recording to: {}", output.display());
+            if let Some(ref name) = profile {
+                println!("Profile: {name}");
+            }
             println!(
                 "Encoder: {} | FPS: {} | Press Ctrl+C to stop",
                 enc.name, fps
             );
+            let split = &config.recording.split;
+            if split.is_enabled() {
+                let limits: Vec<String> = [
+                    split
+                        .max_duration()
+                        .map(|d| format!("{} min", d.as_secs() / 60)),
+                    (split.max_size_mb > 0).then(|| format!("{} MiB", split.max_size_mb)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                println!("Splitting into parts of at most {}", limits.join(" or "));
+            }
 
-            let mut process = FfmpegProcess::spawn(args).await?;
-
-            // Wait for Ctrl+C
-            tokio::signal::ctrl_c().await?;
+            let mut session = RecordingSession::start(
+                runner.clone(),
+                args,
+                SessionOptions::from_config(&config.recording)
+                    .with_journal(&config.paths.recording_journal_dir()),
+            )
+            .await?;
+            let mut warnings = session.subscribe_warnings();
+
+            // Wait for Ctrl+C, unless FFmpeg dies first; restart stalled captures.
+            // Whatever ends capture, the parts written so far are finalized.
+            let ctrl_c = tokio::signal::ctrl_c();
+            tokio::pin!(ctrl_c);
+            let failure: Option<Error> = loop {
+                let mut stalled = session.process().subscribe_stalled();
+                tokio::select! {
+                    res = &mut ctrl_c => {
+                        res?;
+                        break None;
+                    }
+                    exit = session.process().wait() => {
+                        // A split part reached its limit: carry on in the next
+                        if session.part_complete() {
+                            match session.next_part().await {
+                                Ok(part) => {
+                                    println!("Continuing in {}", part.display());
+                                    continue;
+                                }
+                                Err(e) => break Some(e),
+                            }
+                        }
+                        break Some(unexpected_exit(exit));
+                    }
+                    Ok(warning) = warnings.recv() => {
+                        println!(
+                            "Warning: dropped {} of {} frames ({:.1}%)",
+                            warning.dropped, warning.frames, warning.percent
+                        );
+                        continue;
+                    }
+                    _ = stalled.wait_for(|s| *s) => {}
+                }
+                match session.restart().await {
+                    Ok(part) => println!("Capture stalled, continuing in {}", part.display()),
+                    Err(e) => break Some(e),
+                }
+            };
 
-            println!("\nStopping recording...");
-            process.stop_graceful().await?;
-            println!("Saved: {}", output.display());
+            match &failure {
+                None => println!("\nStopping recording..."),
+                Some(_) => eprintln!("\nRecording failed, keeping what was recorded..."),
+            }
+            let finished = session.finish().await?;
+            for part in &finished.parts {
+                println!("Saved: {}", part.display());
+            }
+            print_performance(&finished.performance);
+            let parts = finished
+                .parts
+                .into_iter()
+                .map(absolute)
+                .collect::<Result<Vec<_>>>()?;
+            add_parts_to_library(
+                &config,
+                runner.as_ref(),
+                &parts,
+                Some(&finished.performance),
+            )
+            .await;
+            if let Some(e) = failure {
+                return Err(e.into());
+            }
         }
 
-        Commands::Replay { seconds } => {
-            config.replay.duration_secs = seconds;
-
-            let encoders = probe_encoders().await;
-            let enc = select_best_encoder(&encoders);
-            let source = create_capture_source(&config).await?;
-
-            let ring = ReplayRing::new(
-                &config.paths.replay_cache_dir,
-                config.replay.segment_secs,
-                config.replay.max_segments,
-            );
-            ring.cleanup()?;
-
-            let args = build_replay_command(&config, enc, &source).await;
-            println!("Replay buffer active ({seconds} seconds)");
-            println!("Press Ctrl+C to stop");
-
-            let mut process = FfmpegProcess::spawn(args).await?;
-            tokio::signal::ctrl_c().await?;
+        Commands::Replay {
+            command: None,
+            seconds,
+        } => {
+            daemon::run(config, seconds).await?;
+        }
 
-            println!("\nStopping replay buffer...");
-            process.stop_graceful().await?;
-            ring.cleanup()?;
+        Commands::Replay {
+            command: Some(command),
+            ..
+        } => {
+            let request = match command {
+                ReplayCommand::Status => DaemonRequest::Status,
+                ReplayCommand::Save {
+                    seconds,
+                    after,
+                    out,
+                } => DaemonRequest::Save {
+                    seconds: seconds.unwrap_or(config.replay.save_secs),
+                    after_secs: after,
+                    output: out.map(absolute).transpose()?,
+                },
+                ReplayCommand::Stop => DaemonRequest::Stop,
+                ReplayCommand::ReloadConfig => DaemonRequest::ReloadConfig,
+                ReplayCommand::RecordStart { out } => DaemonRequest::StartRecording {
+                    output: out.map(absolute).transpose()?,
+                },
+                ReplayCommand::RecordStop => DaemonRequest::StopRecording,
+            };
+            let paths = DaemonPaths::for_cache_dir(&config.paths.replay_cache_dir);
+            let response = send_request(&paths.socket, &request).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+            if let DaemonResponse::Error { message } = response {
+                anyhow::bail!(message);
+            }
         }
 
-        Commands::SaveReplay { seconds, out } => {
-            let ring = ReplayRing::new(
-                &config.paths.replay_cache_dir,
-                config.replay.segment_secs,
-                config.replay.max_segments,
-            );
+        Commands::SaveReplay {
+            seconds,
+            after,
+            out,
+        } => {
+            let seconds = seconds.unwrap_or(config.replay.save_secs);
+            let ring = ReplayRing::for_config(&config);
 
             let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
             let output = out.unwrap_or_else(|| {
@@ -187,8 +365,45 @@ async fn main() -> Result<()> {
                     .join(format!("replay_{timestamp}.mkv"))
             });
 
-            println!("Saving last {seconds} seconds...");
-            let path = save_replay(&ring, seconds, &output).await?;
+            if after > 0 {
+                println!("Saving {seconds} seconds before and {after} seconds after now...");
+            } else {
+                println!("Saving last {seconds} seconds...");
+            }
+            // Prefer the running daemon, which knows the live ring
+            let paths = DaemonPaths::for_cache_dir(&config.paths.replay_cache_dir);
+            let request = DaemonRequest::Save {
+                seconds,
+                after_secs: after,
+                output: Some(absolute(output.clone())?),
+            };
+            let path = match send_request(&paths.socket, &request).await {
+                Ok(DaemonResponse::Saved { path }) => path,
+                Ok(DaemonResponse::SavePending {
+                    path,
+                    remaining_secs,
+                    ..
+                }) => {
+                    println!(
+                        "The buffer will save {} in about {remaining_secs:.0} seconds",
+                        path.display()
+                    );
+                    return Ok(());
+                }
+                Ok(DaemonResponse::Error { message }) => anyhow::bail!(message),
+                Ok(other) => anyhow::bail!("unexpected daemon response: {other:?}"),
+                Err(Error::ReplayNotActive) => {
+                    // Cuts off a keyframe re-encode like the capture did
+                    let encoders = probe_encoders(runner.as_ref()).await;
+                    let encoder = select_encoder(&encoders, &config.recording.encoder);
+                    let ring = ring.with_clip_encoding(build_clip_encoding(&config, encoder));
+                    let path = save_replay(runner.as_ref(), &ring, seconds, after, &output).await?;
+                    let path = absolute(path)?;
+                    add_to_library(&config, runner.as_ref(), &path, SourceType::Replay, None).await;
+                    path
+                }
+                Err(e) => return Err(e.into()),
+            };
             println!("Saved: {}", path.display());
         }
 
@@ -198,6 +413,7 @@ async fn main() -> Result<()> {
             out,
             trim_start,
             trim_end,
+            tonemap_sdr,
         } => {
             let preset_obj = match preset.as_str() {
                 "shorts" => ExportPreset::shorts(),
@@ -220,20 +436,25 @@ async fn main() -> Result<()> {
                 preset: preset_obj,
                 trim_start,
                 trim_end,
+                tonemap_sdr,
             };
 
             println!("Exporting with '{preset}' preset...");
-            ExportPipeline::run(&job).await?;
+            ExportPipeline::run(runner.as_ref(), &job).await?;
             println!("Exported: {}", output.display());
         }
 
         Commands::Devices => {
             println!("=== Hardware Encoders ===");
-            let encoders = probe_encoders().await;
+            let encoders = probe_encoders(runner.as_ref()).await;
             for enc in &encoders {
                 let hw = if enc.is_hardware() { "HW" } else { "SW" };
                 let device = enc.device.as_deref().unwrap_or("-");
                 println!("  [{}] {} (device: {})", hw, enc.name, device);
+                if !enc.ten_bit_codecs.is_empty() {
+                    let hdr = if enc.hdr_conversion { " (HDR)" } else { "" };
+                    println!("       10-bit: {:?}{hdr}", enc.ten_bit_codecs);
+                }
             }
 
             println!("\n=== Audio Sources ===");
@@ -252,7 +473,7 @@ async fn main() -> Result<()> {
         }
 
         Commands::Doctor => {
-            let report = run_diagnostics().await;
+            let report = run_diagnostics(&config, runner.as_ref()).await;
             println!("=== ClipForge System Diagnostics ===\n");
             for check in &report.checks {
                 let icon = match check.status {
@@ -266,7 +487,119 @@ async fn main() -> Result<()> {
                 }
             }
         }
+
+        Commands::Library {
+            command: LibraryCommand::Stats { id },
+        } => {
+            let library = Library::open(&config.paths.library_db())?;
+            let Some(recording) = library.get(&id)? else {
+                anyhow::bail!("No recording with id {id}");
+            };
+            println!("{} ({})", recording.title, recording.file_path);
+            match library.get_stats(&id)? {
+                Some(stats) => print_performance(&stats),
+                None => println!("No performance data recorded"),
+            }
+        }
     }
 
     Ok(())
 }
+
+/// Resolve a path against the current directory, for sending to the daemon
+fn absolute(path: PathBuf) -> Result<PathBuf> {
+    Ok(std::env::current_dir()?.join(path))
+}
+
+/// Index a saved file; failing to do so leaves the file and only warns
+async fn add_to_library(
+    config: &Config,
+    runner: &SystemRunner,
+    path: &Path,
+    source: SourceType,
+    performance: Option<&PerformanceSummary>,
+) {
+    let thumbnails_dir = &config.paths.thumbnails_dir;
+    let result = match Library::open(&config.paths.library_db()) {
+        Ok(library) => index_file(runner, &library, path, thumbnails_dir, source, performance)
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        println!(
+            "Warning: could not add {} to the library: {e}",
+            path.display()
+        );
+    }
+}
+
+/// Index the parts of a recording as one session, warning on failure
+async fn add_parts_to_library(
+    config: &Config,
+    runner: &SystemRunner,
+    parts: &[PathBuf],
+    performance: Option<&PerformanceSummary>,
+) {
+    let thumbnails_dir = &config.paths.thumbnails_dir;
+    let result = match Library::open(&config.paths.library_db()) {
+        Ok(library) => index_parts(
+            runner,
+            &library,
+            parts,
+            thumbnails_dir,
+            SourceType::Recording,
+            performance,
+        )
+        .await
+        .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        println!("Warning: could not add the recording to the library: {e}");
+    }
+}
+
+/// Repair recordings an earlier run left unfinished and add them to the
+/// library; failures only warn
+async fn recover_unfinished(config: &Config, runner: &SystemRunner) {
+    let recovered = match recover_orphans(runner, &config.paths.recording_journal_dir()).await {
+        Ok(recovered) => recovered,
+        Err(e) => {
+            println!("Warning: could not check for unfinished recordings: {e}");
+            return;
+        }
+    };
+
+    for recording in &recovered {
+        for part in &recording.parts {
+            println!("Recovered unfinished recording: {}", part.display());
+        }
+        let result = match Library::open(&config.paths.library_db()) {
+            Ok(library) => {
+                index_recovered(runner, &library, recording, &config.paths.thumbnails_dir)
+                    .await
+                    .map(|_| ())
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            println!("Warning: could not add the recovered recording to the library: {e}");
+        }
+    }
+}
+
+fn print_performance(stats: &PerformanceSummary) {
+    println!(
+        "Performance: {:.1} fps avg | {:.1} fps 1% low | {} dropped | {} duplicated | {:.2}x speed",
+        stats.avg_fps, stats.low_1pct_fps, stats.total_drops, stats.total_dups, stats.avg_speed
+    );
+}
+
+/// Error for an FFmpeg process that exited before Ctrl+C
+fn unexpected_exit(exit: ProcessExit) -> Error {
+    exit.error().unwrap_or(match exit.code {
+        Some(code) => Error::FfmpegExitCode(code),
+        None => Error::FfmpegKilled,
+    })
+}
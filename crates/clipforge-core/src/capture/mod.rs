@@ -27,6 +27,15 @@ pub enum CaptureSource {
 }
 
 impl CaptureSource {
+    /// Captured frame size, if known up front (window size isn't)
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        match self {
+            CaptureSource::X11Fullscreen { width, height, .. }
+            | CaptureSource::X11Region { width, height, .. } => Some((*width, *height)),
+            CaptureSource::X11Window { .. } => None,
+        }
+    }
+
     /// Convert to FFmpeg input arguments
     pub fn to_ffmpeg_args(&self) -> Vec<String> {
         match self {
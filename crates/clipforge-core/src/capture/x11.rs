@@ -126,3 +126,91 @@ async fn select_window() -> Result<String> {
         Err(Error::Other("failed to select window via xdotool".into()))
     }
 }
+
+/// Stdout of a desktop query tool, `None` if it is missing or fails
+async fn query(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Whether the focused window is fullscreen, via `xprop`
+pub async fn active_window_fullscreen() -> Option<bool> {
+    let root = query("xprop", &["-root", "_NET_ACTIVE_WINDOW"]).await?;
+    let Some(window) = parse_active_window(&root) else {
+        // Nothing focused, e.g. on an empty desktop
+        return Some(false);
+    };
+    let state = query("xprop", &["-id", &window, "_NET_WM_STATE"]).await?;
+    Some(state.contains("_NET_WM_STATE_FULLSCREEN"))
+}
+
+/// Time since the last keyboard or mouse input, from the XScreenSaver
+/// extension via `xprintidle`
+pub async fn idle_time() -> Option<std::time::Duration> {
+    let ms = query("xprintidle", &[]).await?.trim().parse().ok()?;
+    Some(std::time::Duration::from_millis(ms))
+}
+
+/// Whether the screen is blanked by the screensaver or DPMS, via `xset q`
+pub async fn screen_blanked() -> Option<bool> {
+    parse_monitor_blanked(&query("xset", &["q"]).await?)
+}
+
+/// "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007" -> "0x3a00007"
+fn parse_active_window(xprop: &str) -> Option<String> {
+    let id = xprop.rsplit('#').next()?.trim();
+    let id = id.split([',', ' ']).next()?;
+    (id.starts_with("0x") && id != "0x0").then(|| id.to_string())
+}
+
+/// "Monitor is Off" (or Standby/Suspend) means blanked; `None` without DPMS
+fn parse_monitor_blanked(xset: &str) -> Option<bool> {
+    let state = xset
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Monitor is "))?;
+    Some(state.trim() != "On")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_window_id_is_parsed() {
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007\n").as_deref(),
+            Some("0x3a00007")
+        );
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0\n"),
+            None
+        );
+        assert_eq!(
+            parse_active_window("_NET_ACTIVE_WINDOW:  not found.\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn monitor_state_comes_from_dpms_section() {
+        let on = "DPMS (Energy Star):\n  Standby: 600\n  DPMS is Enabled\n  Monitor is On\n";
+        assert_eq!(parse_monitor_blanked(on), Some(false));
+        assert_eq!(
+            parse_monitor_blanked(&on.replace("is On", "is Off")),
+            Some(true)
+        );
+        assert_eq!(
+            parse_monitor_blanked("Server does not have the DPMS Extension\n"),
+            None
+        );
+    }
+}
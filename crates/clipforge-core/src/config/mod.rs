@@ -1,6 +1,9 @@
 use crate::error::{Error, Result};
+use crate::hotkeys::HotkeyAction;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use tracing::warn;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -10,6 +13,15 @@ pub struct Config {
     pub hotkeys: HotkeyConfig,
     pub paths: PathConfig,
     pub ui: UiConfig,
+    /// Scheduling limits applied to long-running FFmpeg processes
+    #[serde(default)]
+    pub encoder_process: EncoderProcessConfig,
+    /// Named bundles of recording settings, switchable at runtime
+    #[serde(default = "RecordingProfile::defaults")]
+    pub profiles: Vec<RecordingProfile>,
+    /// Name of the profile last applied to `recording`, if any
+    #[serde(default)]
+    pub active_profile: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +33,188 @@ pub struct RecordingConfig {
     pub audio_source: String,
     pub container: String,
     pub capture_mode: CaptureMode,
+    /// Scale the captured frames to this size before encoding
+    #[serde(default)]
+    pub output_resolution: Option<(u32, u32)>,
+    /// Crop the captured frames (before scaling)
+    #[serde(default)]
+    pub crop: Option<CropRect>,
+    #[serde(default)]
+    pub bit_depth: BitDepth,
+    /// Extra FFmpeg arg templates keyed by encoder name (e.g. "h264_nvenc")
+    #[serde(default)]
+    pub extra_args: BTreeMap<String, ExtraArgs>,
+    /// Restart capture into a new part if no frames arrive for this many
+    /// seconds (0 disables the watchdog)
+    #[serde(default = "default_stall_timeout_secs")]
+    pub stall_timeout_secs: u32,
+    /// Warn live when more than this percentage of frames are dropped
+    #[serde(default = "default_drop_warning_percent")]
+    pub drop_warning_percent: f64,
+    /// Split long recordings into separate numbered files
+    #[serde(default)]
+    pub split: SplitConfig,
+    /// Mux so a file cut short by a crash stays playable (fragmented MP4,
+    /// short Matroska clusters)
+    #[serde(default = "default_crash_safe")]
+    pub crash_safe: bool,
+}
+
+/// Limits that end a recording part and continue capture in the next one.
+///
+/// Each part is a separate FFmpeg run, so there is a short gap in capture
+/// while the next one starts. Pausing and resuming also starts a new part,
+/// and that part gets the full `max_minutes` again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitConfig {
+    /// Start a new part before a file grows past this many MiB (0 = no limit)
+    #[serde(default)]
+    pub max_size_mb: u64,
+    /// Start a new part after this many minutes (0 = no limit)
+    #[serde(default)]
+    pub max_minutes: u32,
+    /// File name of each part, without extension. `{name}` is replaced by
+    /// the recording's name and `{part}` by its number (001, 002, ...).
+    #[serde(default = "default_part_template")]
+    pub part_template: String,
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        Self {
+            max_size_mb: 0,
+            max_minutes: 0,
+            part_template: default_part_template(),
+        }
+    }
+}
+
+impl SplitConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.max_size_mb > 0 || self.max_minutes > 0
+    }
+
+    /// Size limit in bytes
+    pub fn max_bytes(&self) -> Option<u64> {
+        (self.max_size_mb > 0).then(|| self.max_size_mb * 1024 * 1024)
+    }
+
+    pub fn max_duration(&self) -> Option<std::time::Duration> {
+        (self.max_minutes > 0).then(|| std::time::Duration::from_secs(self.max_minutes as u64 * 60))
+    }
+
+    /// Name of part `n` (1-based) of the recording `name`, without extension
+    pub fn part_name(&self, name: &str, n: usize) -> String {
+        self.part_template
+            .replace("{name}", name)
+            .replace("{part}", &format!("{n:03}"))
+    }
+}
+
+/// User-supplied FFmpeg arg templates. Tokens may contain `{fps}`,
+/// `{width}`, `{height}` and `{qp}` placeholders.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExtraArgs {
+    /// Inserted before the capture input (`-i`)
+    #[serde(default)]
+    pub input: Vec<String>,
+    /// Merged into the video encoder options
+    #[serde(default)]
+    pub output: Vec<String>,
+}
+
+/// A named set of recording settings that can be applied over `RecordingConfig`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingProfile {
+    pub name: String,
+    pub capture_mode: CaptureMode,
+    pub fps: u32,
+    pub encoder: EncoderPreference,
+    pub quality: Quality,
+    pub audio_enabled: bool,
+    pub audio_source: String,
+}
+
+impl PathConfig {
+    /// Library database, stored next to the recordings directory
+    pub fn library_db(&self) -> PathBuf {
+        self.recordings_dir
+            .parent()
+            .unwrap_or(&self.recordings_dir)
+            .join("library.db")
+    }
+
+    /// Journal of recordings in progress, used to recover them after a crash
+    pub fn recording_journal_dir(&self) -> PathBuf {
+        self.recordings_dir.join(".journal")
+    }
+}
+
+impl RecordingConfig {
+    /// Stall watchdog timeout, `None` when disabled
+    pub fn stall_timeout(&self) -> Option<std::time::Duration> {
+        match self.stall_timeout_secs {
+            0 => None,
+            secs => Some(std::time::Duration::from_secs(secs.into())),
+        }
+    }
+}
+
+impl RecordingProfile {
+    /// Snapshot the current recording settings as a profile
+    pub fn from_recording(name: &str, recording: &RecordingConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            capture_mode: recording.capture_mode.clone(),
+            fps: recording.fps,
+            encoder: recording.encoder.clone(),
+            quality: recording.quality.clone(),
+            audio_enabled: recording.audio_enabled,
+            audio_source: recording.audio_source.clone(),
+        }
+    }
+
+    fn apply_to(&self, recording: &mut RecordingConfig) {
+        recording.capture_mode = self.capture_mode.clone();
+        recording.fps = self.fps;
+        recording.encoder = self.encoder.clone();
+        recording.quality = self.quality.clone();
+        recording.audio_enabled = self.audio_enabled;
+        recording.audio_source = self.audio_source.clone();
+    }
+
+    /// Built-in profiles shipped with the default config
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self {
+                name: "competitive".to_string(),
+                capture_mode: CaptureMode::Fullscreen,
+                fps: 144,
+                encoder: EncoderPreference::Auto,
+                quality: Quality::Medium,
+                audio_enabled: true,
+                audio_source: "default".to_string(),
+            },
+            Self {
+                name: "cinematic".to_string(),
+                capture_mode: CaptureMode::Fullscreen,
+                fps: 60,
+                encoder: EncoderPreference::Auto,
+                quality: Quality::High,
+                audio_enabled: true,
+                audio_source: "default".to_string(),
+            },
+            Self {
+                name: "low-end".to_string(),
+                capture_mode: CaptureMode::Fullscreen,
+                fps: 30,
+                encoder: EncoderPreference::Auto,
+                quality: Quality::Low,
+                audio_enabled: true,
+                audio_source: "default".to_string(),
+            },
+        ]
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,12 +239,167 @@ pub enum CaptureMode {
     Region { x: u32, y: u32, w: u32, h: u32 },
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitDepth {
+    /// 8-bit 4:2:0 (nv12), works with every encoder
+    #[default]
+    Eight,
+    /// 10-bit 4:2:0 (p010). `hdr` converts the capture to BT.2020/PQ and
+    /// tags it so; without zscale in FFmpeg it stays BT.709.
+    Ten { codec: TenBitCodec, hdr: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TenBitCodec {
+    Hevc,
+    Av1,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReplayConfig {
     pub enabled: bool,
     pub duration_secs: u32,
     pub segment_secs: u32,
-    pub max_segments: u32,
+    /// Cap on the ring's size in MiB (0 = limited by free space only)
+    #[serde(default)]
+    pub memory_budget_mb: u64,
+    /// Length saved by `save_replay` when no length is given
+    #[serde(default = "default_save_secs")]
+    pub save_secs: u32,
+    /// Seconds kept in `replay_cache_dir`; older footage moves to
+    /// `paths.replay_disk_dir` (0 = the whole buffer stays in the cache dir)
+    #[serde(default)]
+    pub ram_secs: u32,
+    /// Cap on the disk tier's size in MiB (0 = limited by free space only)
+    #[serde(default)]
+    pub disk_budget_mb: u64,
+    #[serde(default)]
+    pub segment_container: SegmentContainer,
+    /// When to stop capturing until the desktop is in use again
+    #[serde(default)]
+    pub auto_pause: AutoPauseConfig,
+}
+
+/// Conditions that pause the replay buffer; it resumes once none hold
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AutoPauseConfig {
+    /// Pause while the session is locked or the screen is blanked
+    #[serde(default)]
+    pub when_locked: bool,
+    /// Pause while the focused window is not fullscreen
+    #[serde(default)]
+    pub require_fullscreen: bool,
+    /// Pause while none of these processes run (matched by process name)
+    #[serde(default)]
+    pub games: Vec<String>,
+    /// Pause after this many minutes without input (0 = never)
+    #[serde(default)]
+    pub idle_minutes: u32,
+}
+
+impl AutoPauseConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.when_locked
+            || self.require_fullscreen
+            || !self.games.is_empty()
+            || self.idle_minutes > 0
+    }
+}
+
+/// Container the replay ring's segments are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentContainer {
+    /// Segments with their own timestamps, joined by the concat demuxer
+    #[default]
+    Matroska,
+    /// Segments with continuous timestamps, joined byte for byte and
+    /// remuxed. Readable while still being written.
+    Mpegts,
+}
+
+impl SegmentContainer {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Matroska => "mkv",
+            Self::Mpegts => "ts",
+        }
+    }
+
+    /// FFmpeg muxer name
+    pub fn format(self) -> &'static str {
+        match self {
+            Self::Matroska => "matroska",
+            Self::Mpegts => "mpegts",
+        }
+    }
+}
+
+/// Ring slots beyond `duration_secs`: the segment being written and one
+/// partially outside the requested window
+pub const RING_HEADROOM_SEGMENTS: u32 = 2;
+
+impl ReplayConfig {
+    /// Whether older footage is moved out of the cache dir to disk
+    pub fn is_tiered(&self) -> bool {
+        self.ram_secs > 0 && self.ram_secs < self.duration_secs
+    }
+
+    /// Number of slots FFmpeg cycles through in the cache dir: enough for
+    /// `ram_secs` when tiered, `duration_secs` otherwise
+    pub fn segment_count(&self) -> u32 {
+        let secs = if self.is_tiered() {
+            self.ram_secs
+        } else {
+            self.duration_secs
+        };
+        secs.div_ceil(self.segment_secs.max(1)) + RING_HEADROOM_SEGMENTS
+    }
+}
+
+/// Keeps the encoder from competing with the game for CPU and disk.
+///
+/// Each setting maps to a wrapper (`nice`, `ionice`, `taskset`,
+/// `systemd-run --scope`) that is skipped if unavailable on the host.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EncoderProcessConfig {
+    /// Niceness from -20 to 19; 0 leaves the priority unchanged
+    #[serde(default)]
+    pub nice: i32,
+    #[serde(default)]
+    pub io_class: IoClass,
+    /// CPUs the encoder may run on, as a list like "4-7" or "0,2,4"
+    #[serde(default)]
+    pub cpu_affinity: Option<String>,
+    /// Cap CPU time in a transient systemd scope (100 = one full core)
+    #[serde(default)]
+    pub cpu_quota_percent: Option<u32>,
+}
+
+impl EncoderProcessConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// I/O scheduling class for the encoder
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IoClass {
+    #[default]
+    Unchanged,
+    /// Lowest best-effort priority
+    BestEffort,
+    /// Only gets disk time when nothing else needs it
+    Idle,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,9 +411,120 @@ pub struct ExportConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HotkeyConfig {
     pub toggle_recording: String,
+    #[serde(default = "default_pause_recording_hotkey")]
+    pub pause_recording: String,
     pub save_replay: String,
     pub toggle_replay_buffer: String,
     pub mark_highlight: String,
+    #[serde(default = "default_cycle_profile_hotkey")]
+    pub cycle_profile: String,
+    /// Extra replay saves, each with its own length
+    #[serde(default = "default_replay_saves")]
+    pub replay_saves: Vec<ReplaySaveBinding>,
+}
+
+/// A hotkey that saves a fixed span of the replay buffer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplaySaveBinding {
+    pub binding: String,
+    /// Seconds before the key press
+    pub seconds: u32,
+    /// Seconds after the key press to wait for and include
+    #[serde(default)]
+    pub after_secs: u32,
+}
+
+impl ReplaySaveBinding {
+    /// Menu label, e.g. "Last 15s" or "Last 1m + 10s after"
+    pub fn label(&self) -> String {
+        let span = |secs: u32| match secs {
+            s if s >= 60 && s % 60 == 0 => format!("{}m", s / 60),
+            s => format!("{s}s"),
+        };
+        match self.after_secs {
+            0 => format!("Last {}", span(self.seconds)),
+            after => format!("Last {} + {} after", span(self.seconds), span(after)),
+        }
+    }
+}
+
+fn default_cache_dir() -> PathBuf {
+    directories::ProjectDirs::from("com", "clipforge", "ClipForge")
+        .map(|d| d.cache_dir().to_path_buf())
+        .unwrap_or_else(|| {
+            directories::UserDirs::new()
+                .map(|u| u.home_dir().join(".cache").join("clipforge"))
+                .unwrap_or_else(|| PathBuf::from("/tmp/clipforge/cache"))
+        })
+}
+
+fn default_replay_disk_dir() -> PathBuf {
+    default_cache_dir().join("replay-disk")
+}
+
+fn default_ffmpeg_path() -> PathBuf {
+    PathBuf::from("ffmpeg")
+}
+
+fn default_ffprobe_path() -> PathBuf {
+    PathBuf::from("ffprobe")
+}
+
+fn default_drop_warning_percent() -> f64 {
+    5.0
+}
+
+fn default_stall_timeout_secs() -> u32 {
+    10
+}
+
+fn default_pause_recording_hotkey() -> String {
+    "Ctrl+Alt+Space".to_string()
+}
+
+fn default_crash_safe() -> bool {
+    true
+}
+
+fn default_part_template() -> String {
+    "{name}_{part}".to_string()
+}
+
+fn default_cycle_profile_hotkey() -> String {
+    "Ctrl+Alt+P".to_string()
+}
+
+fn default_save_secs() -> u32 {
+    30
+}
+
+fn default_replay_saves() -> Vec<ReplaySaveBinding> {
+    vec![
+        ReplaySaveBinding {
+            binding: "Ctrl+Alt+1".to_string(),
+            seconds: 15,
+            after_secs: 0,
+        },
+        ReplaySaveBinding {
+            binding: "Ctrl+Alt+2".to_string(),
+            seconds: 60,
+            after_secs: 0,
+        },
+    ]
+}
+
+impl HotkeyConfig {
+    /// Key binding configured for an action (empty if unbound)
+    pub fn binding(&self, action: HotkeyAction) -> &str {
+        match action {
+            HotkeyAction::ToggleRecording => &self.toggle_recording,
+            HotkeyAction::PauseRecording => &self.pause_recording,
+            HotkeyAction::SaveReplay => &self.save_replay,
+            HotkeyAction::ToggleReplayBuffer => &self.toggle_replay_buffer,
+            HotkeyAction::MarkHighlight => &self.mark_highlight,
+            HotkeyAction::CycleProfile => &self.cycle_profile,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,7 +532,15 @@ pub struct PathConfig {
     pub recordings_dir: PathBuf,
     pub replays_dir: PathBuf,
     pub replay_cache_dir: PathBuf,
+    /// Disk tier of the replay buffer, used when `replay.ram_secs` is set
+    #[serde(default = "default_replay_disk_dir")]
+    pub replay_disk_dir: PathBuf,
     pub thumbnails_dir: PathBuf,
+    /// FFmpeg binary; a bare name is looked up in PATH
+    #[serde(default = "default_ffmpeg_path")]
+    pub ffmpeg_path: PathBuf,
+    #[serde(default = "default_ffprobe_path")]
+    pub ffprobe_path: PathBuf,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,13 +564,7 @@ impl Default for Config {
             .join("ClipForge");
 
         // Thumbnails go in ~/.cache/clipforge (not user-facing)
-        let cache_dir = directories::ProjectDirs::from("com", "clipforge", "ClipForge")
-            .map(|d| d.cache_dir().to_path_buf())
-            .unwrap_or_else(|| {
-                directories::UserDirs::new()
-                    .map(|u| u.home_dir().join(".cache").join("clipforge"))
-                    .unwrap_or_else(|| PathBuf::from("/tmp/clipforge/cache"))
-            });
+        let cache_dir = default_cache_dir();
 
         // Prefer /dev/shm for replay cache if available and has space
         let replay_cache_dir = if Path::new("/dev/shm").exists() {
@@ -125,12 +587,25 @@ impl Default for Config {
                 audio_source: "default".to_string(),
                 container: "mkv".to_string(),
                 capture_mode: CaptureMode::Fullscreen,
+                output_resolution: None,
+                crop: None,
+                bit_depth: BitDepth::Eight,
+                extra_args: BTreeMap::new(),
+                stall_timeout_secs: default_stall_timeout_secs(),
+                drop_warning_percent: default_drop_warning_percent(),
+                split: SplitConfig::default(),
+                crash_safe: default_crash_safe(),
             },
             replay: ReplayConfig {
                 enabled: false,
                 duration_secs: 120,
                 segment_secs: 3,
-                max_segments: 40,
+                memory_budget_mb: 0,
+                save_secs: default_save_secs(),
+                ram_secs: 0,
+                disk_budget_mb: 0,
+                segment_container: SegmentContainer::default(),
+                auto_pause: AutoPauseConfig::default(),
             },
             export: ExportConfig {
                 default_preset: "high_quality".to_string(),
@@ -138,15 +613,21 @@ impl Default for Config {
             },
             hotkeys: HotkeyConfig {
                 toggle_recording: "Ctrl+Alt+R".to_string(),
+                pause_recording: default_pause_recording_hotkey(),
                 save_replay: "Ctrl+Alt+S".to_string(),
                 toggle_replay_buffer: "Ctrl+Alt+B".to_string(),
                 mark_highlight: "Ctrl+Alt+H".to_string(),
+                cycle_profile: default_cycle_profile_hotkey(),
+                replay_saves: default_replay_saves(),
             },
             paths: PathConfig {
                 recordings_dir,
                 replays_dir,
                 replay_cache_dir,
+                replay_disk_dir: cache_dir.join("replay-disk"),
                 thumbnails_dir,
+                ffmpeg_path: default_ffmpeg_path(),
+                ffprobe_path: default_ffprobe_path(),
             },
             ui: UiConfig {
                 theme: "dark".to_string(),
@@ -154,6 +635,9 @@ impl Default for Config {
                 start_minimized: false,
                 show_notifications: true,
             },
+            encoder_process: EncoderProcessConfig::default(),
+            profiles: RecordingProfile::defaults(),
+            active_profile: None,
         }
     }
 }
@@ -167,6 +651,7 @@ impl Config {
         config.paths.recordings_dir = base_dir.join("recordings");
         config.paths.replays_dir = base_dir.join("replays");
         config.paths.replay_cache_dir = replay_cache_dir;
+        config.paths.replay_disk_dir = cache_dir.join("replay-disk");
         config.paths.thumbnails_dir = cache_dir.join("thumbnails");
         config.export.output_dir = base_dir.join("exports");
         config
@@ -201,6 +686,163 @@ impl Config {
         Ok(())
     }
 
+    /// Apply a named profile to the recording settings and mark it active
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self
+            .profiles
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = self.profiles.iter().map(|p| p.name.as_str()).collect();
+                Error::Config(format!(
+                    "unknown profile '{name}' (available: {})",
+                    names.join(", ")
+                ))
+            })?;
+        profile.apply_to(&mut self.recording);
+        self.active_profile = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Apply the profile after the active one (wrapping around), returning its name
+    pub fn cycle_profile(&mut self) -> Result<String> {
+        if self.profiles.is_empty() {
+            return Err(Error::Config("no recording profiles configured".into()));
+        }
+        let current = self
+            .active_profile
+            .as_ref()
+            .and_then(|name| self.profiles.iter().position(|p| &p.name == name));
+        let next = match current {
+            Some(idx) => (idx + 1) % self.profiles.len(),
+            None => 0,
+        };
+        let name = self.profiles[next].name.clone();
+        self.apply_profile(&name)?;
+        Ok(name)
+    }
+
+    /// Check user-supplied settings that would otherwise fail at record time
+    pub fn validate(&self) -> Result<()> {
+        for (encoder, extra) in &self.recording.extra_args {
+            crate::encode::template::lint_args(&extra.input)
+                .and_then(|_| crate::encode::template::lint_args(&extra.output))
+                .map_err(|e| Error::Config(format!("extra_args for {encoder}: {e}")))?;
+        }
+
+        let process = &self.encoder_process;
+        if !(-20..=19).contains(&process.nice) {
+            return Err(Error::Config(format!(
+                "encoder_process.nice must be between -20 and 19, got {}",
+                process.nice
+            )));
+        }
+        if let Some(ref cpus) = process.cpu_affinity {
+            crate::runner::isolation::parse_cpu_list(cpus)
+                .map_err(|e| Error::Config(format!("encoder_process.cpu_affinity: {e}")))?;
+        }
+        if process.cpu_quota_percent == Some(0) {
+            return Err(Error::Config(
+                "encoder_process.cpu_quota_percent must be greater than 0".into(),
+            ));
+        }
+
+        let split = &self.recording.split;
+        if split.is_enabled() {
+            let template = &split.part_template;
+            if !template.contains("{part}") {
+                return Err(Error::Config(
+                    "recording.split.part_template must contain {part}".into(),
+                ));
+            }
+            if template.contains('/') || template.contains('\\') {
+                return Err(Error::Config(
+                    "recording.split.part_template must be a file name, not a path".into(),
+                ));
+            }
+        }
+
+        let replay = &self.replay;
+        if replay.is_tiered() {
+            if replay.ram_secs < replay.segment_secs {
+                return Err(Error::Config(format!(
+                    "replay.ram_secs must hold at least one {}s segment",
+                    replay.segment_secs
+                )));
+            }
+            if self.paths.replay_disk_dir == self.paths.replay_cache_dir {
+                return Err(Error::Config(
+                    "paths.replay_disk_dir must differ from paths.replay_cache_dir".into(),
+                ));
+            }
+        }
+
+        self.validate_replay_saves()
+    }
+
+    /// Every save must fit in the ring: by the time a look-ahead save is
+    /// written the ring has to hold both sides of the key press. Lengths
+    /// left at their defaults are only warned about, since a shorter buffer
+    /// just saves less; ones the user set must fit.
+    fn validate_replay_saves(&self) -> Result<()> {
+        let capacity = self.replay.duration_secs;
+        let check = |name: &str, seconds: u32, after_secs: u32, default: bool| {
+            if seconds == 0 {
+                return Err(Error::Config(format!("{name} must save at least 1 second")));
+            }
+            if seconds + after_secs <= capacity {
+                return Ok(());
+            }
+            if default && after_secs == 0 {
+                warn!(
+                    save = name,
+                    seconds,
+                    capacity,
+                    "default replay save is longer than the buffer; it saves all of it"
+                );
+                return Ok(());
+            }
+            Err(Error::Config(format!(
+                "{name} spans {}s but the replay buffer holds {capacity}s; \
+                 raise replay.duration_secs or shorten the save",
+                seconds + after_secs
+            )))
+        };
+        check(
+            "replay.save_secs",
+            self.replay.save_secs,
+            0,
+            self.replay.save_secs == default_save_secs(),
+        )?;
+
+        // Shortcuts are matched regardless of case
+        let mut bound: Vec<String> = HotkeyAction::all()
+            .iter()
+            .map(|&a| self.hotkeys.binding(a))
+            .filter(|b| !b.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        let defaults = default_replay_saves();
+        for save in &self.hotkeys.replay_saves {
+            let name = format!("replay save hotkey {}", save.binding);
+            if save.binding.is_empty() {
+                return Err(Error::Config("replay save hotkey has no binding".into()));
+            }
+            let binding = save.binding.to_lowercase();
+            if bound.contains(&binding) {
+                return Err(Error::Config(format!("{name} is bound twice")));
+            }
+            bound.push(binding);
+            check(
+                &name,
+                save.seconds,
+                save.after_secs,
+                defaults.contains(save),
+            )?;
+        }
+        Ok(())
+    }
+
     /// Ensure all configured directories exist
     pub fn ensure_dirs(&self) -> Result<()> {
         std::fs::create_dir_all(&self.paths.recordings_dir).map_err(Error::Io)?;
@@ -283,7 +925,52 @@ mod tests {
         assert!(!config.replay.enabled);
         assert_eq!(config.replay.duration_secs, 120);
         assert_eq!(config.replay.segment_secs, 3);
-        assert_eq!(config.replay.max_segments, 40);
+        assert_eq!(config.replay.memory_budget_mb, 0);
+        // 120s of 3s segments plus headroom
+        assert_eq!(config.replay.segment_count(), 42);
+    }
+
+    #[test]
+    fn segment_count_rounds_up_partial_segments() {
+        let mut replay = Config::default().replay;
+        replay.duration_secs = 31;
+        replay.segment_secs = 3;
+        assert_eq!(replay.segment_count(), 11 + RING_HEADROOM_SEGMENTS);
+        replay.segment_secs = 0;
+        assert_eq!(replay.segment_count(), 31 + RING_HEADROOM_SEGMENTS);
+    }
+
+    #[test]
+    fn tiered_ring_sizes_cache_dir_for_ram_secs() {
+        let mut config = Config::default();
+        config.replay.duration_secs = 1800;
+        config.replay.ram_secs = 30;
+        assert!(config.replay.is_tiered());
+        assert_eq!(config.replay.segment_count(), 10 + RING_HEADROOM_SEGMENTS);
+        assert!(config.validate().is_ok());
+
+        // Holding the whole buffer in RAM is not tiered
+        config.replay.ram_secs = 1800;
+        assert!(!config.replay.is_tiered());
+        assert_eq!(config.replay.segment_count(), 600 + RING_HEADROOM_SEGMENTS);
+
+        config.replay.ram_secs = 2;
+        assert!(config.validate().is_err());
+        config.replay.ram_secs = 30;
+        config.paths.replay_disk_dir = config.paths.replay_cache_dir.clone();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn legacy_max_segments_field_is_ignored() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        let replay = value["replay"].as_object_mut().unwrap();
+        replay.remove("memory_budget_mb");
+        replay.insert("max_segments".to_string(), 40.into());
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.replay.memory_budget_mb, 0);
+        assert_eq!(config.replay.segment_count(), 42);
     }
 
     #[test]
@@ -334,6 +1021,260 @@ mod tests {
         assert!(config.paths.recordings_dir.exists());
     }
 
+    #[test]
+    fn default_has_no_scaling_or_crop() {
+        let config = Config::default();
+        assert!(config.recording.output_resolution.is_none());
+        assert!(config.recording.crop.is_none());
+    }
+
+    #[test]
+    fn recording_config_without_scaling_fields_still_loads() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        let recording = value["recording"].as_object_mut().unwrap();
+        recording.remove("output_resolution");
+        recording.remove("crop");
+        recording.remove("bit_depth");
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert!(config.recording.output_resolution.is_none());
+        assert!(config.recording.crop.is_none());
+        assert_eq!(config.recording.bit_depth, BitDepth::Eight);
+    }
+
+    #[test]
+    fn watchdog_settings_default_when_missing() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        let recording = value["recording"].as_object_mut().unwrap();
+        recording.remove("stall_timeout_secs");
+        recording.remove("drop_warning_percent");
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.recording.stall_timeout_secs, 10);
+        assert_eq!(config.recording.drop_warning_percent, 5.0);
+    }
+
+    #[test]
+    fn binary_paths_default_to_path_lookup() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        let paths = value["paths"].as_object_mut().unwrap();
+        paths.remove("ffmpeg_path");
+        paths.remove("ffprobe_path");
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.paths.ffmpeg_path, PathBuf::from("ffmpeg"));
+        assert_eq!(config.paths.ffprobe_path, PathBuf::from("ffprobe"));
+    }
+
+    #[test]
+    fn ten_bit_depth_serde_roundtrip() {
+        let depth = BitDepth::Ten {
+            codec: TenBitCodec::Av1,
+            hdr: true,
+        };
+        let json = serde_json::to_string(&depth).unwrap();
+        let back: BitDepth = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, depth);
+    }
+
+    #[test]
+    fn apply_profile_updates_recording_settings() {
+        let mut config = Config::default();
+        config.apply_profile("competitive").unwrap();
+        assert_eq!(config.recording.fps, 144);
+        assert!(matches!(config.recording.quality, Quality::Medium));
+        assert_eq!(config.active_profile.as_deref(), Some("competitive"));
+    }
+
+    #[test]
+    fn apply_unknown_profile_is_config_error() {
+        let mut config = Config::default();
+        let err = config.apply_profile("nope").unwrap_err();
+        assert!(err.to_string().contains("cinematic"), "got: {err}");
+        assert!(config.active_profile.is_none());
+    }
+
+    #[test]
+    fn cycle_profile_wraps_around() {
+        let mut config = Config::default();
+        assert_eq!(config.cycle_profile().unwrap(), "competitive");
+        assert_eq!(config.cycle_profile().unwrap(), "cinematic");
+        assert_eq!(config.cycle_profile().unwrap(), "low-end");
+        assert_eq!(config.cycle_profile().unwrap(), "competitive");
+        assert_eq!(config.recording.fps, 144);
+    }
+
+    #[test]
+    fn cycle_profile_without_profiles_errors() {
+        let mut config = Config::default();
+        config.profiles.clear();
+        assert!(config.cycle_profile().is_err());
+    }
+
+    #[test]
+    fn config_without_profiles_field_still_loads() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        let root = value.as_object_mut().unwrap();
+        root.remove("profiles");
+        root.remove("active_profile");
+        root["hotkeys"]
+            .as_object_mut()
+            .unwrap()
+            .remove("cycle_profile");
+        root["hotkeys"]
+            .as_object_mut()
+            .unwrap()
+            .remove("pause_recording");
+
+        let config: Config = serde_json::from_value(value).unwrap();
+        // Configs from before profiles get the built-in ones
+        let names: Vec<&str> = config.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["competitive", "cinematic", "low-end"]);
+        assert_eq!(config.hotkeys.cycle_profile, "Ctrl+Alt+P");
+        assert_eq!(config.hotkeys.pause_recording, "Ctrl+Alt+Space");
+    }
+
+    #[test]
+    fn split_parts_are_named_from_template() {
+        let mut config = Config::default();
+        assert!(!config.recording.split.is_enabled());
+        config.recording.split.max_minutes = 30;
+        assert_eq!(
+            config.recording.split.part_name("recording_x", 2),
+            "recording_x_002"
+        );
+        assert!(config.validate().is_ok());
+
+        config.recording.split.part_template = "{name}".to_string();
+        assert!(config.validate().is_err());
+        config.recording.split.part_template = "parts/{name}_{part}".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_denied_extra_args() {
+        let mut config = Config::default();
+        config.recording.extra_args.insert(
+            "h264_nvenc".to_string(),
+            ExtraArgs {
+                input: Vec::new(),
+                output: vec!["-tune".to_string(), "ll".to_string()],
+            },
+        );
+        assert!(config.validate().is_ok());
+
+        config
+            .recording
+            .extra_args
+            .get_mut("h264_nvenc")
+            .unwrap()
+            .output
+            .push("-y".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("h264_nvenc"), "got: {err}");
+    }
+
+    #[test]
+    fn encoder_process_defaults_when_missing_and_validates() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        value.as_object_mut().unwrap().remove("encoder_process");
+        let mut config: Config = serde_json::from_value(value).unwrap();
+        assert!(config.encoder_process.is_default());
+
+        config.encoder_process = EncoderProcessConfig {
+            nice: 10,
+            io_class: IoClass::Idle,
+            cpu_affinity: Some("0-3,6".to_string()),
+            cpu_quota_percent: Some(200),
+        };
+        assert!(config.validate().is_ok());
+
+        config.encoder_process.cpu_affinity = Some("0-x".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("cpu_affinity"), "got: {err}");
+
+        config.encoder_process.cpu_affinity = None;
+        config.encoder_process.nice = 25;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn replay_saves_default_when_missing() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        value["hotkeys"]
+            .as_object_mut()
+            .unwrap()
+            .remove("replay_saves");
+        value["replay"].as_object_mut().unwrap().remove("save_secs");
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.replay.save_secs, 30);
+        let lengths: Vec<u32> = config
+            .hotkeys
+            .replay_saves
+            .iter()
+            .map(|s| s.seconds)
+            .collect();
+        assert_eq!(lengths, vec![15, 60]);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn replay_saves_must_fit_in_the_ring() {
+        let mut config = Config::default();
+        config.replay.duration_secs = 300;
+        config.hotkeys.replay_saves.push(ReplaySaveBinding {
+            binding: "Ctrl+Alt+3".to_string(),
+            seconds: 300,
+            after_secs: 0,
+        });
+        assert!(config.validate().is_ok());
+
+        // Look-ahead has to be buffered too
+        config.hotkeys.replay_saves[2].after_secs = 10;
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("Ctrl+Alt+3"), "got: {err}");
+
+        config.hotkeys.replay_saves[2].after_secs = 0;
+        config.replay.save_secs = 301;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn replay_save_bindings_must_be_unique() {
+        let mut config = Config::default();
+        config.hotkeys.replay_saves[1].binding = config.hotkeys.save_replay.clone();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("bound twice"), "got: {err}");
+
+        config.hotkeys.replay_saves[1].binding = config.hotkeys.save_replay.to_uppercase();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("bound twice"), "got: {err}");
+    }
+
+    #[test]
+    fn default_replay_saves_do_not_fail_a_short_buffer() {
+        let mut config = Config::default();
+        config.replay.duration_secs = 20;
+        assert!(config.validate().is_ok());
+
+        // Lengths the user chose still have to fit
+        config.hotkeys.replay_saves[1].seconds = 45;
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("Ctrl+Alt+2"), "got: {err}");
+    }
+
+    #[test]
+    fn replay_save_labels() {
+        let save = |seconds, after_secs| ReplaySaveBinding {
+            binding: String::new(),
+            seconds,
+            after_secs,
+        };
+        assert_eq!(save(15, 0).label(), "Last 15s");
+        assert_eq!(save(300, 0).label(), "Last 5m");
+        assert_eq!(save(90, 10).label(), "Last 90s + 10s after");
+    }
+
     #[test]
     fn default_quality_is_high() {
         let config = Config::default();
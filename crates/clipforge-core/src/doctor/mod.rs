@@ -1,4 +1,9 @@
+use crate::config::Config;
+use crate::replay::budget::{mib, RingFit, RingPlan};
+use crate::runner::isolation::Isolation;
+use crate::runner::{FfmpegRunner, Tool};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::process::Stdio;
 use tokio::process::Command;
 
@@ -32,14 +37,14 @@ impl std::fmt::Display for CheckStatus {
     }
 }
 
-pub async fn run_diagnostics() -> DiagnosticReport {
+pub async fn run_diagnostics(config: &Config, runner: &dyn FfmpegRunner) -> DiagnosticReport {
     let mut checks = Vec::new();
 
     // Run all checks concurrently
     let (os, display, ffmpeg, vaapi, pipewire, audio, disk) = tokio::join!(
         check_os(),
         check_display_server(),
-        check_ffmpeg(),
+        check_ffmpeg(runner, &config.paths.ffmpeg_path),
         check_vaapi(),
         check_pipewire(),
         check_audio_sources(),
@@ -53,6 +58,11 @@ pub async fn run_diagnostics() -> DiagnosticReport {
     checks.push(pipewire);
     checks.push(audio);
     checks.push(disk);
+    checks.push(check_encoder_isolation(config));
+    // A ring that is never started needs no room
+    if config.replay.enabled {
+        checks.push(check_replay_memory(config));
+    }
 
     DiagnosticReport { checks }
 }
@@ -104,9 +114,12 @@ async fn check_display_server() -> DiagnosticCheck {
     }
 }
 
-async fn check_ffmpeg() -> DiagnosticCheck {
-    match run_command("ffmpeg", &["-version"]).await {
-        Ok(output) => {
+/// Run the FFmpeg the runner was configured with, `ffmpeg_path`
+async fn check_ffmpeg(runner: &dyn FfmpegRunner, ffmpeg_path: &Path) -> DiagnosticCheck {
+    let output = runner.run(Tool::Ffmpeg, vec!["-version".to_string()]).await;
+    match output {
+        Ok(output) if output.success() => {
+            let output = String::from_utf8_lossy(&output.stdout);
             let version_line = output.lines().next().unwrap_or("unknown");
             DiagnosticCheck {
                 name: "FFmpeg".to_string(),
@@ -115,12 +128,26 @@ async fn check_ffmpeg() -> DiagnosticCheck {
                 recommendation: None,
             }
         }
-        Err(_) => DiagnosticCheck {
-            name: "FFmpeg".to_string(),
-            status: CheckStatus::Fail,
-            detail: "FFmpeg not found in PATH".to_string(),
-            recommendation: Some("Install FFmpeg: sudo apt install ffmpeg".to_string()),
-        },
+        _ => {
+            // A bare name is looked up in PATH, anything else is used as is
+            let detail = if ffmpeg_path.parent() == Some(Path::new("")) {
+                format!("{} not found in PATH", ffmpeg_path.display())
+            } else {
+                format!("FFmpeg not found at {}", ffmpeg_path.display())
+            };
+            let recommendation = if ffmpeg_path == Path::new("ffmpeg") {
+                "Install FFmpeg: sudo apt install ffmpeg".to_string()
+            } else {
+                "Check paths.ffmpeg_path in the config, or remove it to use FFmpeg from PATH"
+                    .to_string()
+            };
+            DiagnosticCheck {
+                name: "FFmpeg".to_string(),
+                status: CheckStatus::Fail,
+                detail,
+                recommendation: Some(recommendation),
+            }
+        }
     }
 }
 
@@ -267,6 +294,79 @@ async fn check_disk_space() -> DiagnosticCheck {
     }
 }
 
+fn check_replay_memory(config: &Config) -> DiagnosticCheck {
+    let plan = RingPlan::for_config(config);
+    let mut detail = format!(
+        "~{} MiB for {}s ({} segments at ~{:.1} Mbps) in {}",
+        mib(plan.estimated_bytes),
+        config.replay.duration_secs,
+        plan.segments,
+        plan.estimated_kbps as f64 / 1000.0,
+        config.paths.replay_cache_dir.display()
+    );
+    if let Some(limit) = plan.limit() {
+        detail.push_str(&format!(", {} MiB available", mib(limit)));
+    }
+
+    let (status, recommendation) = match plan.check() {
+        Ok(RingFit::Fits) => (CheckStatus::Pass, None),
+        Ok(RingFit::Tight(_)) => (
+            CheckStatus::Warn,
+            Some("Little headroom left; lower replay.duration_secs or quality".to_string()),
+        ),
+        Err(_) => (
+            CheckStatus::Fail,
+            Some("Replay buffer won't fit; lower replay.duration_secs or quality".to_string()),
+        ),
+    };
+    DiagnosticCheck {
+        name: "Replay Buffer Memory".to_string(),
+        status,
+        detail,
+        recommendation,
+    }
+}
+
+fn check_encoder_isolation(config: &Config) -> DiagnosticCheck {
+    let name = "Encoder Priority".to_string();
+    if config.encoder_process.is_default() {
+        return DiagnosticCheck {
+            name,
+            status: CheckStatus::Pass,
+            detail: "Default scheduling (no limits configured)".to_string(),
+            recommendation: None,
+        };
+    }
+
+    let isolation = Isolation::resolve(&config.encoder_process);
+    let applied = if isolation.applied.is_empty() {
+        "none".to_string()
+    } else {
+        isolation.applied.join(", ")
+    };
+    if isolation.unavailable.is_empty() {
+        DiagnosticCheck {
+            name,
+            status: CheckStatus::Pass,
+            detail: format!("Effective: {applied}"),
+            recommendation: None,
+        }
+    } else {
+        DiagnosticCheck {
+            name,
+            status: CheckStatus::Warn,
+            detail: format!(
+                "Effective: {applied}; skipped: {}",
+                isolation.unavailable.join(", ")
+            ),
+            recommendation: Some(
+                "Install util-linux (nice, ionice, taskset) and run under a systemd user session for CPU quotas"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
 async fn run_command(cmd: &str, args: &[&str]) -> std::result::Result<String, ()> {
     let output = Command::new(cmd)
         .args(args)
@@ -282,3 +382,33 @@ async fn run_command(cmd: &str, args: &[&str]) -> std::result::Result<String, ()
         Err(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::{FakeResponse, FakeRunner};
+
+    fn missing_ffmpeg() -> FakeRunner {
+        FakeRunner::new().on(
+            Tool::Ffmpeg,
+            "-version",
+            FakeResponse::failing(127, "not found"),
+        )
+    }
+
+    #[tokio::test]
+    async fn missing_ffmpeg_in_path_recommends_installing_it() {
+        let check = check_ffmpeg(&missing_ffmpeg(), Path::new("ffmpeg")).await;
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert_eq!(check.detail, "ffmpeg not found in PATH");
+        assert!(check.recommendation.unwrap().contains("apt install"));
+    }
+
+    #[tokio::test]
+    async fn missing_configured_ffmpeg_names_the_path() {
+        let path = Path::new("/opt/ffmpeg/bin/ffmpeg");
+        let check = check_ffmpeg(&missing_ffmpeg(), path).await;
+        assert_eq!(check.detail, "FFmpeg not found at /opt/ffmpeg/bin/ffmpeg");
+        assert!(check.recommendation.unwrap().contains("paths.ffmpeg_path"));
+    }
+}
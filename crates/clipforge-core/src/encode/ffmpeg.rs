@@ -1,12 +1,22 @@
 use crate::audio::resolve_audio_source;
 use crate::capture::CaptureSource;
-use crate::config::{Config, Quality};
-use crate::encode::hw_probe::{EncoderInfo, HwAccelType};
+use crate::config::{BitDepth, Config, Quality, SegmentContainer, TenBitCodec};
+use crate::encode::filter::{FilterGraph, VideoTransform};
+use crate::encode::hw_probe::{ten_bit_encoder_name, EncoderInfo, HwAccelType};
+use crate::encode::template::{self, merge_args, TemplateContext};
+use crate::error::Result;
 use std::path::Path;
+use tracing::warn;
 
 /// Builds FFmpeg argument vectors for recording commands
 pub struct FfmpegCommandBuilder {
     args: Vec<String>,
+    transform: VideoTransform,
+    bit_depth: BitDepth,
+    /// The input is already BT.2020/PQ, so HDR output needs no conversion
+    pq_input: bool,
+    /// Index where the video encoder options begin, for merging user args
+    encoder_start: Option<usize>,
 }
 
 impl Default for FfmpegCommandBuilder {
@@ -19,6 +29,10 @@ impl FfmpegCommandBuilder {
     pub fn new() -> Self {
         Self {
             args: vec!["-y".to_string()],
+            transform: VideoTransform::default(),
+            bit_depth: BitDepth::Eight,
+            pq_input: false,
+            encoder_start: None,
         }
     }
 
@@ -39,6 +53,19 @@ impl FfmpegCommandBuilder {
         self
     }
 
+    /// Add user input options; they apply to the next input added
+    pub fn with_extra_input_args(mut self, args: Vec<String>) -> Self {
+        self.args.extend(args);
+        self
+    }
+
+    /// Merge user options into the video encoder args added by `with_encoder`
+    pub fn with_extra_output_args(mut self, args: &[String]) -> Result<Self> {
+        let from = self.encoder_start.unwrap_or(self.args.len());
+        merge_args(&mut self.args, from, args)?;
+        Ok(self)
+    }
+
     /// Add PulseAudio/PipeWire audio input
     pub fn with_audio(mut self, source: &str) -> Self {
         self.args.extend([
@@ -50,32 +77,104 @@ impl FfmpegCommandBuilder {
         self
     }
 
+    /// Crop and/or scale captured frames before they reach the encoder
+    pub fn with_transform(mut self, transform: VideoTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Record in 10-bit when the encoder supports it (falls back to 8-bit otherwise)
+    pub fn with_bit_depth(mut self, bit_depth: BitDepth) -> Self {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    /// Re-encode footage that was converted to HDR when it was captured
+    pub fn with_pq_input(mut self) -> Self {
+        self.pq_input = true;
+        self
+    }
+
     /// Add video encoding with hardware acceleration
     pub fn with_encoder(mut self, encoder: &EncoderInfo, quality: &Quality) -> Self {
+        self.encoder_start = Some(self.args.len());
+
+        let ten_bit = match self.bit_depth {
+            BitDepth::Ten { codec, hdr } if encoder.supports_ten_bit(codec) => {
+                match ten_bit_encoder_name(encoder.hw_accel, codec) {
+                    Some(name) => Some((codec, hdr, name)),
+                    None => {
+                        warn!(encoder = %encoder.name, codec = ?codec, "no 10-bit encoder for this backend, recording 8-bit");
+                        None
+                    }
+                }
+            }
+            BitDepth::Ten { codec, .. } => {
+                warn!(encoder = %encoder.name, codec = ?codec, "10-bit not supported, recording 8-bit");
+                None
+            }
+            BitDepth::Eight => None,
+        };
+
+        // The capture is SDR; only tag HDR when the graph converts it to PQ
+        let hdr = match ten_bit {
+            Some((_, true, _)) if self.pq_input || encoder.hdr_conversion => true,
+            Some((_, true, _)) => {
+                warn!("FFmpeg lacks zscale to convert to HDR, recording 10-bit SDR");
+                false
+            }
+            _ => false,
+        };
+
+        let graph = video_filter_graph(
+            encoder.hw_accel,
+            &self.transform,
+            ten_bit.is_some(),
+            hdr && !self.pq_input,
+        );
+
+        // vpp_qsv needs frames uploaded into an explicit QSV device; only a
+        // scale uploads, a crop alone stays in software
+        if encoder.hw_accel == HwAccelType::Qsv && self.transform.scale.is_some() {
+            self.args.extend([
+                "-init_hw_device".to_string(),
+                "qsv=qsv".to_string(),
+                "-filter_hw_device".to_string(),
+                "qsv".to_string(),
+            ]);
+        }
+
+        match graph.render() {
+            Some(filter) => self.args.extend([
+                "-filter_complex".to_string(),
+                filter,
+                "-map".to_string(),
+                graph.output_label(),
+            ]),
+            None => self.args.extend(["-map".to_string(), "0:v".to_string()]),
+        }
+
+        let codec_name = match ten_bit {
+            Some((_, _, name)) => name,
+            None => match encoder.hw_accel {
+                HwAccelType::Vaapi => "h264_vaapi",
+                HwAccelType::Nvenc => "h264_nvenc",
+                HwAccelType::Qsv => "h264_qsv",
+                HwAccelType::Software => "libx264",
+            },
+        };
+        self.args
+            .extend(["-c:v".to_string(), codec_name.to_string()]);
+
         match encoder.hw_accel {
             HwAccelType::Vaapi => {
-                self.args.extend([
-                    "-filter_complex".to_string(),
-                    "[0:v]hwupload,scale_vaapi=format=nv12[vout]".to_string(),
-                    "-map".to_string(),
-                    "[vout]".to_string(),
-                ]);
-                self.args.extend([
-                    "-c:v".to_string(),
-                    "h264_vaapi".to_string(),
-                    "-rc_mode".to_string(),
-                    "CQP".to_string(),
-                ]);
+                self.args
+                    .extend(["-rc_mode".to_string(), "CQP".to_string()]);
                 let qp = quality_to_qp(quality);
                 self.args.extend(["-qp".to_string(), qp.to_string()]);
-                self.args.extend(["-g".to_string(), "120".to_string()]);
             }
             HwAccelType::Nvenc => {
                 self.args.extend([
-                    "-map".to_string(),
-                    "0:v".to_string(),
-                    "-c:v".to_string(),
-                    "h264_nvenc".to_string(),
                     "-preset".to_string(),
                     "p4".to_string(),
                     "-rc".to_string(),
@@ -83,36 +182,49 @@ impl FfmpegCommandBuilder {
                 ]);
                 let qp = quality_to_qp(quality);
                 self.args.extend(["-qp".to_string(), qp.to_string()]);
-                self.args.extend(["-g".to_string(), "120".to_string()]);
             }
             HwAccelType::Qsv => {
-                self.args.extend([
-                    "-map".to_string(),
-                    "0:v".to_string(),
-                    "-c:v".to_string(),
-                    "h264_qsv".to_string(),
-                    "-preset".to_string(),
-                    "medium".to_string(),
-                ]);
+                self.args
+                    .extend(["-preset".to_string(), "medium".to_string()]);
                 let qp = quality_to_qp(quality);
                 self.args
                     .extend(["-global_quality".to_string(), qp.to_string()]);
-                self.args.extend(["-g".to_string(), "120".to_string()]);
             }
             HwAccelType::Software => {
-                self.args.extend([
-                    "-map".to_string(),
-                    "0:v".to_string(),
-                    "-c:v".to_string(),
-                    "libx264".to_string(),
-                    "-preset".to_string(),
-                    "fast".to_string(),
-                ]);
+                // SVT-AV1 presets are numeric; x264/x265 share named presets
+                let preset = if codec_name == "libsvtav1" {
+                    "8"
+                } else {
+                    "fast"
+                };
+                self.args
+                    .extend(["-preset".to_string(), preset.to_string()]);
                 let crf = quality_to_crf(quality);
                 self.args.extend(["-crf".to_string(), crf.to_string()]);
-                self.args.extend(["-g".to_string(), "120".to_string()]);
             }
         }
+
+        if let Some((codec, _, _)) = ten_bit {
+            if codec == TenBitCodec::Hevc && encoder.is_hardware() {
+                self.args
+                    .extend(["-profile:v".to_string(), "main10".to_string()]);
+            }
+            let (primaries, transfer, matrix) = if hdr {
+                ("bt2020", "smpte2084", "bt2020nc")
+            } else {
+                ("bt709", "bt709", "bt709")
+            };
+            self.args.extend([
+                "-color_primaries".to_string(),
+                primaries.to_string(),
+                "-color_trc".to_string(),
+                transfer.to_string(),
+                "-colorspace".to_string(),
+                matrix.to_string(),
+            ]);
+        }
+
+        self.args.extend(["-g".to_string(), "120".to_string()]);
         self
     }
 
@@ -142,14 +254,36 @@ impl FfmpegCommandBuilder {
         self
     }
 
-    /// Set output to segmented files for replay buffer
+    /// Mux so a file cut short by a crash stays readable: fragmented MP4
+    /// needs no index at the end, and Matroska closes a cluster every second
+    pub fn with_crash_safe_muxing(mut self, container: &str) -> Self {
+        match container_to_ffmpeg_format(container) {
+            "mp4" | "mov" => self.args.extend([
+                "-movflags".to_string(),
+                "+frag_keyframe+empty_moov+default_base_moof".to_string(),
+            ]),
+            "matroska" | "webm" => self
+                .args
+                .extend(["-cluster_time_limit".to_string(), "1000".to_string()]),
+            _ => {}
+        }
+        self
+    }
+
+    /// Set output to segmented files for replay buffer, numbered from
+    /// `first_segment` (non-zero when resuming into a ring).
+    ///
+    /// Matroska segments restart at zero; MPEG-TS segments keep the
+    /// stream's timestamps so they can be joined byte for byte.
     pub fn with_segment_output(
         mut self,
         segment_dir: &Path,
         segment_time: u32,
         segment_wrap: u32,
+        first_segment: u64,
+        container: SegmentContainer,
     ) -> Self {
-        let segment_pattern = segment_dir.join("seg_%03d.mkv");
+        let segment_pattern = segment_dir.join(format!("seg_%03d.{}", container.extension()));
         let segment_list = segment_dir.join("segments.csv");
 
         self.args.extend([
@@ -158,17 +292,26 @@ impl FfmpegCommandBuilder {
             "-segment_time".to_string(),
             segment_time.to_string(),
             "-segment_format".to_string(),
-            "matroska".to_string(),
+            container.format().to_string(),
             "-segment_wrap".to_string(),
             segment_wrap.to_string(),
             "-segment_list".to_string(),
             segment_list.to_string_lossy().to_string(),
             "-segment_list_type".to_string(),
             "csv".to_string(),
-            "-reset_timestamps".to_string(),
-            "1".to_string(),
-            segment_pattern.to_string_lossy().to_string(),
         ]);
+        if first_segment > 0 {
+            self.args.extend([
+                "-segment_start_number".to_string(),
+                first_segment.to_string(),
+            ]);
+        }
+        if container == SegmentContainer::Matroska {
+            self.args
+                .extend(["-reset_timestamps".to_string(), "1".to_string()]);
+        }
+        self.args
+            .push(segment_pattern.to_string_lossy().to_string());
         self
     }
 
@@ -183,35 +326,74 @@ pub async fn build_recording_command(
     encoder: &EncoderInfo,
     source: &CaptureSource,
     output: &Path,
-) -> Vec<String> {
+) -> Result<Vec<String>> {
+    let mut builder = capture_and_encode(config, encoder, source).await?;
+    if config.recording.crash_safe {
+        builder = builder.with_crash_safe_muxing(&config.recording.container);
+    }
+
+    Ok(builder
+        .with_output(output, &config.recording.container)
+        .build())
+}
+
+/// Build a segmented recording command for replay buffer, starting at
+/// segment `first_segment` (see `ReplayRing::resume`)
+pub async fn build_replay_command(
+    config: &Config,
+    encoder: &EncoderInfo,
+    source: &CaptureSource,
+    first_segment: u64,
+) -> Result<Vec<String>> {
+    let builder = capture_and_encode(config, encoder, source)
+        .await?
+        .with_segment_output(
+            &config.paths.replay_cache_dir,
+            config.replay.segment_secs,
+            config.replay.segment_count(),
+            first_segment,
+            config.replay.segment_container,
+        );
+
+    Ok(builder.build())
+}
+
+/// Encoder args for re-encoding a clip cut from the replay ring, matching
+/// the capture: same encoder, quality and bit depth. The footage is already
+/// cropped, scaled and, for HDR, converted. Audio is re-encoded if present.
+pub fn build_clip_encoding(config: &Config, encoder: &EncoderInfo) -> Vec<String> {
     let mut builder = FfmpegCommandBuilder::new()
         .with_hw_device(encoder)
-        .with_capture_source(source);
-
-    if config.recording.audio_enabled {
-        let audio_source = resolve_audio_source(&config.recording.audio_source)
-            .await
-            .unwrap_or_else(|_| config.recording.audio_source.clone());
-        builder = builder.with_audio(&audio_source);
+        .with_bit_depth(config.recording.bit_depth);
+    if matches!(config.recording.bit_depth, BitDepth::Ten { hdr: true, .. }) {
+        builder = builder.with_pq_input();
     }
-
-    builder = builder
+    let mut args = builder
         .with_encoder(encoder, &config.recording.quality)
-        .with_audio_encode(config.recording.audio_enabled)
-        .with_output(output, &config.recording.container);
-
-    builder.build()
+        .build();
+    args.extend(
+        ["-map", "0:a?", "-c:a", "aac", "-b:a", "192k"]
+            .iter()
+            .map(|a| a.to_string()),
+    );
+    // The clip command brings its own -y
+    args.split_off(1)
 }
 
-/// Build a segmented recording command for replay buffer
-pub async fn build_replay_command(
+/// Shared input, filter and encoder setup for recording and replay commands
+async fn capture_and_encode(
     config: &Config,
     encoder: &EncoderInfo,
     source: &CaptureSource,
-) -> Vec<String> {
+) -> Result<FfmpegCommandBuilder> {
+    let (extra_input, extra_output) = render_extra_args(config, encoder, source)?;
+
     let mut builder = FfmpegCommandBuilder::new()
         .with_hw_device(encoder)
-        .with_capture_source(source);
+        .with_extra_input_args(extra_input)
+        .with_capture_source(source)
+        .with_transform(VideoTransform::from_config(&config.recording))
+        .with_bit_depth(config.recording.bit_depth);
 
     if config.recording.audio_enabled {
         let audio_source = resolve_audio_source(&config.recording.audio_source)
@@ -220,19 +402,113 @@ pub async fn build_replay_command(
         builder = builder.with_audio(&audio_source);
     }
 
-    builder = builder
+    Ok(builder
         .with_encoder(encoder, &config.recording.quality)
-        .with_audio_encode(config.recording.audio_enabled)
-        .with_segment_output(
-            &config.paths.replay_cache_dir,
-            config.replay.segment_secs,
-            config.replay.max_segments,
-        );
+        .with_extra_output_args(&extra_output)?
+        .with_audio_encode(config.recording.audio_enabled))
+}
+
+/// Lint and render the user's extra arg templates for this encoder
+fn render_extra_args(
+    config: &Config,
+    encoder: &EncoderInfo,
+    source: &CaptureSource,
+) -> Result<(Vec<String>, Vec<String>)> {
+    let Some(extra) = config.recording.extra_args.get(&encoder.name) else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    template::lint_args(&extra.input)?;
+    template::lint_args(&extra.output)?;
+
+    let recording = &config.recording;
+    let (width, height) = recording
+        .output_resolution
+        .or_else(|| recording.crop.as_ref().map(|c| (c.w, c.h)))
+        .or_else(|| source.dimensions())
+        .unzip();
+    let qp = match encoder.hw_accel {
+        HwAccelType::Software => quality_to_crf(&recording.quality),
+        _ => quality_to_qp(&recording.quality),
+    };
+    let ctx = TemplateContext {
+        fps: recording.fps,
+        width,
+        height,
+        qp,
+    };
+
+    Ok((
+        template::render_args(&extra.input, &ctx)?,
+        template::render_args(&extra.output, &ctx)?,
+    ))
+}
+
+/// Maps the BT.709 capture into BT.2020/PQ with SDR white at 203 nits
+/// (BT.2408), so HDR tags describe the pixels
+const SDR_TO_PQ_FILTER: &str = "zscale=pin=bt709:tin=bt709:p=bt2020:t=smpte2084:m=bt2020nc:npl=203";
+
+/// Build the capture-to-encoder filter graph for a backend.
+///
+/// Cropping is done in software before upload; scaling uses the backend's
+/// own scaler so frames stay on the GPU. `ten_bit` switches the surface
+/// format from nv12 to p010 (yuv420p10le for software encoders), and `hdr`
+/// converts to BT.2020/PQ first.
+fn video_filter_graph(
+    hw_accel: HwAccelType,
+    transform: &VideoTransform,
+    ten_bit: bool,
+    hdr: bool,
+) -> FilterGraph {
+    let mut graph = FilterGraph::new("0:v", "vout").push_opt(transform.crop_filter());
+    let hw_format = if ten_bit { "p010" } else { "nv12" };
 
-    builder.build()
+    // Convert on the CPU before any upload; the GPU scalers keep colors as is
+    if hdr {
+        graph = graph.push(SDR_TO_PQ_FILTER);
+        if hw_accel == HwAccelType::Vaapi {
+            graph = graph.push("format=p010le");
+        }
+    }
+
+    match hw_accel {
+        HwAccelType::Vaapi => {
+            let scale = match transform.scale {
+                Some((w, h)) => format!("scale_vaapi=w={w}:h={h}:format={hw_format}"),
+                None => format!("scale_vaapi=format={hw_format}"),
+            };
+            graph.push("hwupload").push(scale)
+        }
+        HwAccelType::Nvenc => match transform.scale {
+            Some((w, h)) => graph
+                .push(format!("format={hw_format}"))
+                .push("hwupload_cuda")
+                .push(format!("scale_cuda=w={w}:h={h}")),
+            None if ten_bit => graph.push("format=p010le"),
+            None => graph,
+        },
+        HwAccelType::Qsv => match transform.scale {
+            Some((w, h)) => graph
+                .push("format=nv12")
+                .push("hwupload=extra_hw_frames=64")
+                .push(format!("vpp_qsv=w={w}:h={h}")),
+            None => graph,
+        },
+        HwAccelType::Software => {
+            let graph = graph.push_opt(
+                transform
+                    .scale
+                    .map(|(w, h)| format!("scale={w}:{h}:flags=lanczos")),
+            );
+            if ten_bit {
+                graph.push("format=yuv420p10le")
+            } else {
+                graph
+            }
+        }
+    }
 }
 
-fn quality_to_qp(quality: &Quality) -> u32 {
+pub(crate) fn quality_to_qp(quality: &Quality) -> u32 {
     match quality {
         Quality::Low => 30,
         Quality::Medium => 25,
@@ -305,4 +581,377 @@ mod tests {
         let args = builder.build();
         assert_eq!(args, vec!["-y"]);
     }
+
+    #[test]
+    fn crash_safe_muxing_follows_container() {
+        let mp4 = FfmpegCommandBuilder::new()
+            .with_crash_safe_muxing("mp4")
+            .with_output(Path::new("/rec/x.mp4"), "mp4")
+            .build();
+        assert_eq!(
+            mp4,
+            [
+                "-y",
+                "-movflags",
+                "+frag_keyframe+empty_moov+default_base_moof",
+                "-f",
+                "mp4",
+                "/rec/x.mp4"
+            ]
+        );
+
+        let mkv = FfmpegCommandBuilder::new()
+            .with_crash_safe_muxing("mkv")
+            .build();
+        assert!(mkv.windows(2).any(|w| w == ["-cluster_time_limit", "1000"]));
+
+        // Transport streams survive truncation as they are
+        let ts = FfmpegCommandBuilder::new()
+            .with_crash_safe_muxing("ts")
+            .build();
+        assert_eq!(ts, ["-y"]);
+    }
+
+    #[test]
+    fn segment_output_follows_container() {
+        let dir = Path::new("/dev/shm/ring");
+        let mkv = FfmpegCommandBuilder::new()
+            .with_segment_output(dir, 3, 42, 0, SegmentContainer::Matroska)
+            .build();
+        assert!(mkv.windows(2).any(|w| w == ["-segment_format", "matroska"]));
+        assert!(mkv.windows(2).any(|w| w == ["-reset_timestamps", "1"]));
+        assert_eq!(mkv.last().unwrap(), "/dev/shm/ring/seg_%03d.mkv");
+
+        // TS segments keep continuous timestamps for byte-level joins
+        let ts = FfmpegCommandBuilder::new()
+            .with_segment_output(dir, 3, 42, 0, SegmentContainer::Mpegts)
+            .build();
+        assert!(ts.windows(2).any(|w| w == ["-segment_format", "mpegts"]));
+        assert!(!ts.contains(&"-reset_timestamps".to_string()));
+        assert!(!ts.contains(&"-segment_start_number".to_string()));
+        assert_eq!(ts.last().unwrap(), "/dev/shm/ring/seg_%03d.ts");
+
+        // A resumed capture carries on through the slots
+        let resumed = FfmpegCommandBuilder::new()
+            .with_segment_output(dir, 3, 42, 57, SegmentContainer::Matroska)
+            .build();
+        assert!(resumed
+            .windows(2)
+            .any(|w| w == ["-segment_start_number", "57"]));
+    }
+
+    fn make_encoder(hw_accel: HwAccelType) -> EncoderInfo {
+        EncoderInfo {
+            name: "test".to_string(),
+            hw_accel,
+            available: true,
+            device: None,
+            ten_bit_codecs: vec![TenBitCodec::Hevc],
+            hdr_conversion: false,
+        }
+    }
+
+    fn downscale_1080p() -> VideoTransform {
+        VideoTransform {
+            crop: None,
+            scale: Some((1920, 1080)),
+        }
+    }
+
+    fn filter_complex(args: &[String]) -> Option<&str> {
+        args.iter()
+            .position(|a| a == "-filter_complex")
+            .map(|i| args[i + 1].as_str())
+    }
+
+    #[test]
+    fn vaapi_without_transform_keeps_format_conversion() {
+        let args = FfmpegCommandBuilder::new()
+            .with_encoder(&make_encoder(HwAccelType::Vaapi), &Quality::High)
+            .build();
+        assert_eq!(
+            filter_complex(&args),
+            Some("[0:v]hwupload,scale_vaapi=format=nv12[vout]")
+        );
+        assert!(args.contains(&"[vout]".to_string()));
+    }
+
+    #[test]
+    fn vaapi_scales_after_hwupload() {
+        let args = FfmpegCommandBuilder::new()
+            .with_transform(downscale_1080p())
+            .with_encoder(&make_encoder(HwAccelType::Vaapi), &Quality::High)
+            .build();
+        assert_eq!(
+            filter_complex(&args),
+            Some("[0:v]hwupload,scale_vaapi=w=1920:h=1080:format=nv12[vout]")
+        );
+    }
+
+    #[test]
+    fn nvenc_scales_with_scale_cuda() {
+        let args = FfmpegCommandBuilder::new()
+            .with_transform(downscale_1080p())
+            .with_encoder(&make_encoder(HwAccelType::Nvenc), &Quality::High)
+            .build();
+        let graph = filter_complex(&args).expect("expected filter graph");
+        assert!(graph.contains("hwupload_cuda,scale_cuda=w=1920:h=1080"));
+    }
+
+    #[test]
+    fn qsv_scales_with_vpp_qsv_and_inits_device() {
+        let args = FfmpegCommandBuilder::new()
+            .with_transform(downscale_1080p())
+            .with_encoder(&make_encoder(HwAccelType::Qsv), &Quality::High)
+            .build();
+        let graph = filter_complex(&args).expect("expected filter graph");
+        assert!(graph.contains("vpp_qsv=w=1920:h=1080"));
+        assert!(args.contains(&"-init_hw_device".to_string()));
+        assert!(args.contains(&"-filter_hw_device".to_string()));
+    }
+
+    #[test]
+    fn qsv_crop_alone_needs_no_device() {
+        let args = FfmpegCommandBuilder::new()
+            .with_transform(VideoTransform {
+                crop: Some(crate::config::CropRect {
+                    x: 0,
+                    y: 0,
+                    w: 2560,
+                    h: 1440,
+                }),
+                scale: None,
+            })
+            .with_encoder(&make_encoder(HwAccelType::Qsv), &Quality::High)
+            .build();
+        assert_eq!(filter_complex(&args), Some("[0:v]crop=2560:1440:0:0[vout]"));
+        assert!(!args.contains(&"-init_hw_device".to_string()));
+        assert!(!args.contains(&"-filter_hw_device".to_string()));
+    }
+
+    #[test]
+    fn software_crop_then_lanczos_scale() {
+        let transform = VideoTransform {
+            crop: Some(crate::config::CropRect {
+                x: 0,
+                y: 0,
+                w: 2560,
+                h: 1440,
+            }),
+            scale: Some((1280, 720)),
+        };
+        let args = FfmpegCommandBuilder::new()
+            .with_transform(transform)
+            .with_encoder(&make_encoder(HwAccelType::Software), &Quality::High)
+            .build();
+        assert_eq!(
+            filter_complex(&args),
+            Some("[0:v]crop=2560:1440:0:0,scale=1280:720:flags=lanczos[vout]")
+        );
+    }
+
+    #[test]
+    fn software_without_transform_maps_input_directly() {
+        let args = FfmpegCommandBuilder::new()
+            .with_encoder(&make_encoder(HwAccelType::Software), &Quality::High)
+            .build();
+        assert!(filter_complex(&args).is_none());
+        let map_idx = args.iter().position(|a| a == "-map").unwrap();
+        assert_eq!(args[map_idx + 1], "0:v");
+    }
+
+    fn arg_after<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        args.iter()
+            .position(|a| a == flag)
+            .map(|i| args[i + 1].as_str())
+    }
+
+    #[test]
+    fn vaapi_ten_bit_uses_p010_hevc_and_tags_hdr() {
+        let encoder = EncoderInfo {
+            hdr_conversion: true,
+            ..make_encoder(HwAccelType::Vaapi)
+        };
+        let args = FfmpegCommandBuilder::new()
+            .with_bit_depth(BitDepth::Ten {
+                codec: TenBitCodec::Hevc,
+                hdr: true,
+            })
+            .with_encoder(&encoder, &Quality::High)
+            .build();
+        assert_eq!(
+            filter_complex(&args),
+            Some(
+                "[0:v]zscale=pin=bt709:tin=bt709:p=bt2020:t=smpte2084:m=bt2020nc:npl=203,\
+                 format=p010le,hwupload,scale_vaapi=format=p010[vout]"
+            )
+        );
+        assert_eq!(arg_after(&args, "-c:v"), Some("hevc_vaapi"));
+        assert_eq!(arg_after(&args, "-profile:v"), Some("main10"));
+        assert_eq!(arg_after(&args, "-color_primaries"), Some("bt2020"));
+        assert_eq!(arg_after(&args, "-color_trc"), Some("smpte2084"));
+        assert_eq!(arg_after(&args, "-colorspace"), Some("bt2020nc"));
+    }
+
+    #[test]
+    fn hdr_without_zscale_stays_bt709() {
+        let args = FfmpegCommandBuilder::new()
+            .with_bit_depth(BitDepth::Ten {
+                codec: TenBitCodec::Hevc,
+                hdr: true,
+            })
+            .with_encoder(&make_encoder(HwAccelType::Software), &Quality::High)
+            .build();
+        assert_eq!(filter_complex(&args), Some("[0:v]format=yuv420p10le[vout]"));
+        assert_eq!(arg_after(&args, "-c:v"), Some("libx265"));
+        assert_eq!(arg_after(&args, "-color_primaries"), Some("bt709"));
+        assert_eq!(arg_after(&args, "-color_trc"), Some("bt709"));
+    }
+
+    #[test]
+    fn clip_encoding_tags_hdr_footage_without_converting_it_again() {
+        let mut config = Config::default();
+        config.recording.bit_depth = BitDepth::Ten {
+            codec: TenBitCodec::Hevc,
+            hdr: true,
+        };
+        let encoder = EncoderInfo {
+            device: Some("/dev/dri/renderD128".to_string()),
+            ..make_encoder(HwAccelType::Vaapi)
+        };
+        let args = build_clip_encoding(&config, &encoder);
+        assert_ne!(args[0], "-y");
+        assert_eq!(
+            arg_after(&args, "-vaapi_device"),
+            Some("/dev/dri/renderD128")
+        );
+        assert_eq!(
+            filter_complex(&args),
+            Some("[0:v]hwupload,scale_vaapi=format=p010[vout]")
+        );
+        assert_eq!(arg_after(&args, "-c:v"), Some("hevc_vaapi"));
+        assert_eq!(arg_after(&args, "-color_trc"), Some("smpte2084"));
+        assert_eq!(arg_after(&args, "-c:a"), Some("aac"));
+    }
+
+    #[test]
+    fn qsv_ten_bit_falls_back_without_panicking() {
+        // A deserialized encoder may claim codecs its backend has no name for
+        let args = FfmpegCommandBuilder::new()
+            .with_bit_depth(BitDepth::Ten {
+                codec: TenBitCodec::Hevc,
+                hdr: false,
+            })
+            .with_encoder(&make_encoder(HwAccelType::Qsv), &Quality::High)
+            .build();
+        assert_eq!(arg_after(&args, "-c:v"), Some("h264_qsv"));
+        assert!(!args.contains(&"-color_primaries".to_string()));
+    }
+
+    #[test]
+    fn software_ten_bit_sdr_uses_x265_with_bt709_tags() {
+        let args = FfmpegCommandBuilder::new()
+            .with_bit_depth(BitDepth::Ten {
+                codec: TenBitCodec::Hevc,
+                hdr: false,
+            })
+            .with_encoder(&make_encoder(HwAccelType::Software), &Quality::High)
+            .build();
+        assert_eq!(filter_complex(&args), Some("[0:v]format=yuv420p10le[vout]"));
+        assert_eq!(arg_after(&args, "-c:v"), Some("libx265"));
+        assert_eq!(arg_after(&args, "-color_trc"), Some("bt709"));
+    }
+
+    #[test]
+    fn unsupported_ten_bit_codec_falls_back_to_eight_bit() {
+        let args = FfmpegCommandBuilder::new()
+            .with_bit_depth(BitDepth::Ten {
+                codec: TenBitCodec::Av1,
+                hdr: true,
+            })
+            .with_encoder(&make_encoder(HwAccelType::Nvenc), &Quality::High)
+            .build();
+        assert_eq!(arg_after(&args, "-c:v"), Some("h264_nvenc"));
+        assert!(filter_complex(&args).is_none());
+        assert!(!args.contains(&"-color_primaries".to_string()));
+    }
+
+    #[test]
+    fn extra_output_args_override_generated_gop() {
+        let args = FfmpegCommandBuilder::new()
+            .with_encoder(&make_encoder(HwAccelType::Nvenc), &Quality::High)
+            .with_extra_output_args(&[
+                "-g".to_string(),
+                "288".to_string(),
+                "-tune".to_string(),
+                "ll".to_string(),
+            ])
+            .unwrap()
+            .with_audio_encode(true)
+            .build();
+        assert_eq!(args.iter().filter(|a| *a == "-g").count(), 1);
+        assert_eq!(arg_after(&args, "-g"), Some("288"));
+        assert_eq!(arg_after(&args, "-tune"), Some("ll"));
+        // Encoder options stay ahead of the audio mapping
+        let tune_idx = args.iter().position(|a| a == "-tune").unwrap();
+        let audio_idx = args.iter().position(|a| a == "1:a").unwrap();
+        assert!(tune_idx < audio_idx);
+    }
+
+    #[test]
+    fn extra_input_args_precede_capture_input() {
+        let source = CaptureSource::X11Fullscreen {
+            display: ":0".to_string(),
+            width: 3840,
+            height: 2160,
+            fps: 60,
+        };
+        let args = FfmpegCommandBuilder::new()
+            .with_extra_input_args(vec!["-draw_mouse".to_string(), "0".to_string()])
+            .with_capture_source(&source)
+            .build();
+        let draw_idx = args.iter().position(|a| a == "-draw_mouse").unwrap();
+        let input_idx = args.iter().position(|a| a == "-i").unwrap();
+        assert!(draw_idx < input_idx);
+    }
+
+    #[test]
+    fn render_extra_args_uses_output_resolution_and_qp() {
+        let mut config = Config::default();
+        config.recording.output_resolution = Some((1920, 1080));
+        config.recording.extra_args.insert(
+            "test".to_string(),
+            crate::config::ExtraArgs {
+                input: Vec::new(),
+                output: vec!["-x".to_string(), "{width}x{height}@{fps}/{qp}".to_string()],
+            },
+        );
+        let source = CaptureSource::X11Fullscreen {
+            display: ":0".to_string(),
+            width: 3840,
+            height: 2160,
+            fps: 60,
+        };
+        let (_, output) =
+            render_extra_args(&config, &make_encoder(HwAccelType::Vaapi), &source).unwrap();
+        assert_eq!(output[1], "1920x1080@60/20");
+    }
+
+    #[test]
+    fn render_extra_args_rejects_denied_template() {
+        let mut config = Config::default();
+        config.recording.extra_args.insert(
+            "test".to_string(),
+            crate::config::ExtraArgs {
+                input: Vec::new(),
+                output: vec!["-f".to_string(), "mp4".to_string()],
+            },
+        );
+        let source = CaptureSource::X11Window {
+            display: ":0".to_string(),
+            window_id: "0x1".to_string(),
+            fps: 60,
+        };
+        assert!(render_extra_args(&config, &make_encoder(HwAccelType::Vaapi), &source).is_err());
+    }
 }
@@ -0,0 +1,134 @@
+use crate::config::{CropRect, RecordingConfig};
+use serde::{Deserialize, Serialize};
+
+/// Capture-time geometry changes applied before encoding
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VideoTransform {
+    pub crop: Option<CropRect>,
+    pub scale: Option<(u32, u32)>,
+}
+
+impl VideoTransform {
+    pub fn from_config(recording: &RecordingConfig) -> Self {
+        Self {
+            crop: recording.crop.clone(),
+            scale: recording.output_resolution,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.crop.is_none() && self.scale.is_none()
+    }
+
+    /// Software crop filter, if a crop is configured
+    pub fn crop_filter(&self) -> Option<String> {
+        self.crop
+            .as_ref()
+            .map(|c| format!("crop={}:{}:{}:{}", c.w, c.h, c.x, c.y))
+    }
+}
+
+/// Composable single-chain filter graph rendered for `-filter_complex`.
+///
+/// Filters are appended in order and joined with commas between the input
+/// and output pad labels, e.g. `[0:v]crop=...,hwupload,scale_vaapi=...[vout]`.
+#[derive(Debug, Clone)]
+pub struct FilterGraph {
+    input: String,
+    output: String,
+    filters: Vec<String>,
+}
+
+impl FilterGraph {
+    pub fn new(input: &str, output: &str) -> Self {
+        Self {
+            input: input.to_string(),
+            output: output.to_string(),
+            filters: Vec::new(),
+        }
+    }
+
+    /// Append a filter to the chain
+    pub fn push(mut self, filter: impl Into<String>) -> Self {
+        self.filters.push(filter.into());
+        self
+    }
+
+    /// Append a filter only if one is given
+    pub fn push_opt(self, filter: Option<String>) -> Self {
+        match filter {
+            Some(f) => self.push(f),
+            None => self,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Label to pass to `-map` for the graph output
+    pub fn output_label(&self) -> String {
+        format!("[{}]", self.output)
+    }
+
+    /// Render the graph, or `None` if no filters were added
+    pub fn render(&self) -> Option<String> {
+        if self.filters.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "[{}]{}[{}]",
+            self.input,
+            self.filters.join(","),
+            self.output
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_graph_renders_none() {
+        let graph = FilterGraph::new("0:v", "vout");
+        assert!(graph.is_empty());
+        assert!(graph.render().is_none());
+    }
+
+    #[test]
+    fn filters_are_joined_in_order() {
+        let graph = FilterGraph::new("0:v", "vout")
+            .push("hwupload")
+            .push("scale_vaapi=format=nv12");
+        assert_eq!(
+            graph.render().unwrap(),
+            "[0:v]hwupload,scale_vaapi=format=nv12[vout]"
+        );
+        assert_eq!(graph.output_label(), "[vout]");
+    }
+
+    #[test]
+    fn push_opt_skips_none() {
+        let graph = FilterGraph::new("0:v", "vout")
+            .push_opt(None)
+            .push_opt(Some("fps=30".to_string()));
+        assert_eq!(graph.render().unwrap(), "[0:v]fps=30[vout]");
+    }
+
+    #[test]
+    fn crop_filter_uses_ffmpeg_argument_order() {
+        let transform = VideoTransform {
+            crop: Some(CropRect {
+                x: 10,
+                y: 20,
+                w: 1280,
+                h: 720,
+            }),
+            scale: None,
+        };
+        assert_eq!(transform.crop_filter().unwrap(), "crop=1280:720:10:20");
+        assert!(!transform.is_identity());
+        assert!(VideoTransform::default().is_identity());
+    }
+}
@@ -1,6 +1,7 @@
+use crate::config::{EncoderPreference, TenBitCodec};
+use crate::process::to_owned_args;
+use crate::runner::{FfmpegRunner, Tool};
 use serde::{Deserialize, Serialize};
-use std::process::Stdio;
-use tokio::process::Command;
 use tracing::{debug, info};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -17,6 +18,13 @@ pub struct EncoderInfo {
     pub hw_accel: HwAccelType,
     pub available: bool,
     pub device: Option<String>,
+    /// Codecs this backend can encode in 10-bit (p010)
+    #[serde(default)]
+    pub ten_bit_codecs: Vec<TenBitCodec>,
+    /// FFmpeg can convert the SDR capture to BT.2020/PQ for HDR output
+    /// (needs the zscale filter)
+    #[serde(default)]
+    pub hdr_conversion: bool,
 }
 
 impl EncoderInfo {
@@ -27,47 +35,58 @@ impl EncoderInfo {
     pub fn is_hardware(&self) -> bool {
         self.hw_accel != HwAccelType::Software
     }
+
+    pub fn supports_ten_bit(&self, codec: TenBitCodec) -> bool {
+        self.ten_bit_codecs.contains(&codec)
+    }
 }
 
 /// Probe available hardware encoders by running test encodes.
 /// Returns a list sorted by priority (best first).
-pub async fn probe_encoders() -> Vec<EncoderInfo> {
+pub async fn probe_encoders(runner: &dyn FfmpegRunner) -> Vec<EncoderInfo> {
     let mut encoders = Vec::new();
+    let hdr_conversion = has_filter(runner, "zscale").await;
 
     // Test VA-API
     let vaapi_devices = find_vaapi_devices().await;
     for device in &vaapi_devices {
-        if test_vaapi_encoder(device).await {
+        if test_vaapi_encoder(runner, device).await {
             info!(device = %device, "VA-API encoder available");
             encoders.push(EncoderInfo {
                 name: "h264_vaapi".to_string(),
                 hw_accel: HwAccelType::Vaapi,
                 available: true,
                 device: Some(device.clone()),
+                ten_bit_codecs: probe_ten_bit(runner, HwAccelType::Vaapi, Some(device)).await,
+                hdr_conversion,
             });
             break; // Use first working device
         }
     }
 
     // Test NVENC
-    if test_nvenc_encoder().await {
+    if test_nvenc_encoder(runner).await {
         info!("NVENC encoder available");
         encoders.push(EncoderInfo {
             name: "h264_nvenc".to_string(),
             hw_accel: HwAccelType::Nvenc,
             available: true,
             device: None,
+            ten_bit_codecs: probe_ten_bit(runner, HwAccelType::Nvenc, None).await,
+            hdr_conversion,
         });
     }
 
     // Test QSV
-    if test_qsv_encoder().await {
+    if test_qsv_encoder(runner).await {
         info!("QSV encoder available");
         encoders.push(EncoderInfo {
             name: "h264_qsv".to_string(),
             hw_accel: HwAccelType::Qsv,
             available: true,
             device: None,
+            ten_bit_codecs: Vec::new(),
+            hdr_conversion,
         });
     }
 
@@ -77,11 +96,38 @@ pub async fn probe_encoders() -> Vec<EncoderInfo> {
         hw_accel: HwAccelType::Software,
         available: true,
         device: None,
+        ten_bit_codecs: probe_ten_bit(runner, HwAccelType::Software, None).await,
+        hdr_conversion,
     });
 
     encoders
 }
 
+/// Whether this FFmpeg build has the filter `name`
+pub async fn has_filter(runner: &dyn FfmpegRunner, name: &str) -> bool {
+    match runner
+        .run(Tool::Ffmpeg, to_owned_args(&["-hide_banner", "-filters"]))
+        .await
+    {
+        Ok(output) if output.success() => {
+            parse_filter_names(&String::from_utf8_lossy(&output.stdout)).any(|f| f == name)
+        }
+        _ => false,
+    }
+}
+
+/// Names from `ffmpeg -filters` lines like ` ... zscale  V->V  Apply ...`
+fn parse_filter_names(listing: &str) -> impl Iterator<Item = &str> {
+    listing.lines().filter_map(|line| {
+        let mut fields = line.split_whitespace();
+        let flags = fields.next()?;
+        let name = fields.next()?;
+        // The legend above the list has no "->" column
+        let io = fields.next()?;
+        (flags.len() == 3 && io.contains("->")).then_some(name)
+    })
+}
+
 /// Find VA-API render devices
 async fn find_vaapi_devices() -> Vec<String> {
     let mut devices = Vec::new();
@@ -95,36 +141,36 @@ async fn find_vaapi_devices() -> Vec<String> {
 }
 
 /// Test VA-API encoder with a 1-frame encode
-async fn test_vaapi_encoder(device: &str) -> bool {
-    let result = Command::new("ffmpeg")
-        .args([
-            "-y",
-            "-loglevel",
-            "error",
-            "-f",
-            "lavfi",
-            "-i",
-            "testsrc=duration=0.1:size=64x64:rate=1",
-            "-vaapi_device",
-            device,
-            "-vf",
-            "format=nv12,hwupload",
-            "-c:v",
-            "h264_vaapi",
-            "-frames:v",
-            "1",
-            "-f",
-            "null",
-            "-",
-        ])
-        .stdout(Stdio::null())
-        .stderr(Stdio::piped())
-        .output()
+async fn test_vaapi_encoder(runner: &dyn FfmpegRunner, device: &str) -> bool {
+    let result = runner
+        .run(
+            Tool::Ffmpeg,
+            to_owned_args(&[
+                "-y",
+                "-loglevel",
+                "error",
+                "-f",
+                "lavfi",
+                "-i",
+                "testsrc=duration=0.1:size=64x64:rate=1",
+                "-vaapi_device",
+                device,
+                "-vf",
+                "format=nv12,hwupload",
+                "-c:v",
+                "h264_vaapi",
+                "-frames:v",
+                "1",
+                "-f",
+                "null",
+                "-",
+            ]),
+        )
         .await;
 
     match result {
         Ok(output) => {
-            if output.status.success() {
+            if output.success() {
                 true
             } else {
                 let stderr = String::from_utf8_lossy(&output.stderr);
@@ -140,63 +186,118 @@ async fn test_vaapi_encoder(device: &str) -> bool {
 }
 
 /// Test NVENC encoder
-async fn test_nvenc_encoder() -> bool {
-    let result = Command::new("ffmpeg")
-        .args([
-            "-y",
-            "-loglevel",
-            "error",
-            "-f",
-            "lavfi",
-            "-i",
-            "testsrc=duration=0.1:size=64x64:rate=1",
-            "-c:v",
-            "h264_nvenc",
-            "-frames:v",
-            "1",
-            "-f",
-            "null",
-            "-",
-        ])
-        .stdout(Stdio::null())
-        .stderr(Stdio::piped())
-        .output()
+async fn test_nvenc_encoder(runner: &dyn FfmpegRunner) -> bool {
+    let result = runner
+        .run(
+            Tool::Ffmpeg,
+            to_owned_args(&[
+                "-y",
+                "-loglevel",
+                "error",
+                "-f",
+                "lavfi",
+                "-i",
+                "testsrc=duration=0.1:size=64x64:rate=1",
+                "-c:v",
+                "h264_nvenc",
+                "-frames:v",
+                "1",
+                "-f",
+                "null",
+                "-",
+            ]),
+        )
         .await;
 
     match result {
-        Ok(output) => output.status.success(),
+        Ok(output) => output.success(),
         Err(_) => false,
     }
 }
 
 /// Test QSV encoder
-async fn test_qsv_encoder() -> bool {
-    let result = Command::new("ffmpeg")
-        .args([
+async fn test_qsv_encoder(runner: &dyn FfmpegRunner) -> bool {
+    let result = runner
+        .run(
+            Tool::Ffmpeg,
+            to_owned_args(&[
+                "-y",
+                "-loglevel",
+                "error",
+                "-f",
+                "lavfi",
+                "-i",
+                "testsrc=duration=0.1:size=64x64:rate=1",
+                "-c:v",
+                "h264_qsv",
+                "-frames:v",
+                "1",
+                "-f",
+                "null",
+                "-",
+            ]),
+        )
+        .await;
+
+    match result {
+        Ok(output) => output.success(),
+        Err(_) => false,
+    }
+}
+
+/// Codec names used for 10-bit encoding on each backend
+pub fn ten_bit_encoder_name(hw_accel: HwAccelType, codec: TenBitCodec) -> Option<&'static str> {
+    match (hw_accel, codec) {
+        (HwAccelType::Vaapi, TenBitCodec::Hevc) => Some("hevc_vaapi"),
+        (HwAccelType::Vaapi, TenBitCodec::Av1) => Some("av1_vaapi"),
+        (HwAccelType::Nvenc, TenBitCodec::Hevc) => Some("hevc_nvenc"),
+        (HwAccelType::Nvenc, TenBitCodec::Av1) => Some("av1_nvenc"),
+        (HwAccelType::Software, TenBitCodec::Hevc) => Some("libx265"),
+        (HwAccelType::Software, TenBitCodec::Av1) => Some("libsvtav1"),
+        (HwAccelType::Qsv, _) => None,
+    }
+}
+
+/// Find which 10-bit codecs a backend can encode with a 1-frame p010 test
+async fn probe_ten_bit(
+    runner: &dyn FfmpegRunner,
+    hw_accel: HwAccelType,
+    device: Option<&str>,
+) -> Vec<TenBitCodec> {
+    let mut codecs = Vec::new();
+    for codec in [TenBitCodec::Hevc, TenBitCodec::Av1] {
+        let Some(name) = ten_bit_encoder_name(hw_accel, codec) else {
+            continue;
+        };
+
+        let mut args = vec![
             "-y",
             "-loglevel",
             "error",
             "-f",
             "lavfi",
             "-i",
-            "testsrc=duration=0.1:size=64x64:rate=1",
-            "-c:v",
-            "h264_qsv",
-            "-frames:v",
-            "1",
-            "-f",
-            "null",
-            "-",
-        ])
-        .stdout(Stdio::null())
-        .stderr(Stdio::piped())
-        .output()
-        .await;
+            "testsrc=duration=0.1:size=256x256:rate=1",
+        ];
+        match (hw_accel, device) {
+            (HwAccelType::Vaapi, Some(device)) => {
+                args.extend(["-vaapi_device", device, "-vf", "format=p010,hwupload"]);
+            }
+            (HwAccelType::Software, _) => args.extend(["-pix_fmt", "yuv420p10le"]),
+            _ => args.extend(["-pix_fmt", "p010le"]),
+        }
+        args.extend(["-c:v", name, "-frames:v", "1", "-f", "null", "-"]);
 
-    match result {
-        Ok(output) => output.status.success(),
-        Err(_) => false,
+        let ok = runner
+            .run(Tool::Ffmpeg, to_owned_args(&args))
+            .await
+            .is_ok_and(|o| o.success());
+        if ok {
+            debug!(encoder = name, "10-bit encode supported");
+            codecs.push(codec);
+        }
     }
+    codecs
 }
 
 /// Select the best encoder from probed results
@@ -207,6 +308,20 @@ pub fn select_best_encoder(encoders: &[EncoderInfo]) -> &EncoderInfo {
         .expect("at least software encoder should be available")
 }
 
+/// Select an encoder honoring the configured preference, falling back to the best available
+pub fn select_encoder<'a>(
+    encoders: &'a [EncoderInfo],
+    preference: &EncoderPreference,
+) -> &'a EncoderInfo {
+    match preference {
+        EncoderPreference::Auto => select_best_encoder(encoders),
+        EncoderPreference::Specific(name) => encoders
+            .iter()
+            .find(|e| e.available && &e.name == name)
+            .unwrap_or_else(|| select_best_encoder(encoders)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -217,6 +332,8 @@ mod tests {
             hw_accel: hw,
             available,
             device: None,
+            ten_bit_codecs: Vec::new(),
+            hdr_conversion: false,
         }
     }
 
@@ -241,6 +358,19 @@ mod tests {
         assert_eq!(best.name, "libx264");
     }
 
+    #[test]
+    fn select_encoder_honors_specific_preference() {
+        let encoders = vec![
+            make_encoder("h264_vaapi", HwAccelType::Vaapi, true),
+            make_encoder("libx264", HwAccelType::Software, true),
+        ];
+        let pref = EncoderPreference::Specific("libx264".to_string());
+        assert_eq!(select_encoder(&encoders, &pref).name, "libx264");
+
+        let missing = EncoderPreference::Specific("h264_nvenc".to_string());
+        assert_eq!(select_encoder(&encoders, &missing).name, "h264_vaapi");
+    }
+
     #[test]
     fn is_hardware_true_for_hw_types() {
         assert!(make_encoder("vaapi", HwAccelType::Vaapi, true).is_hardware());
@@ -249,6 +379,40 @@ mod tests {
         assert!(!make_encoder("sw", HwAccelType::Software, true).is_hardware());
     }
 
+    #[test]
+    fn supports_ten_bit_checks_probed_codecs() {
+        let mut enc = make_encoder("h264_nvenc", HwAccelType::Nvenc, true);
+        assert!(!enc.supports_ten_bit(TenBitCodec::Hevc));
+        enc.ten_bit_codecs.push(TenBitCodec::Hevc);
+        assert!(enc.supports_ten_bit(TenBitCodec::Hevc));
+        assert!(!enc.supports_ten_bit(TenBitCodec::Av1));
+    }
+
+    #[test]
+    fn filter_listing_is_parsed() {
+        let listing = "Filters:
+  T.. = Timeline support
+  ... = Reserved
+ ... zscale            V->V       Apply resizing, colorspace and bit depth conversion.
+ TSC scale             V->V       Scale the input video size and/or convert the image format.
+";
+        let names: Vec<&str> = parse_filter_names(listing).collect();
+        assert_eq!(names, ["zscale", "scale"]);
+    }
+
+    #[test]
+    fn qsv_has_no_ten_bit_encoder_names() {
+        assert_eq!(
+            ten_bit_encoder_name(HwAccelType::Vaapi, TenBitCodec::Hevc),
+            Some("hevc_vaapi")
+        );
+        assert_eq!(
+            ten_bit_encoder_name(HwAccelType::Software, TenBitCodec::Av1),
+            Some("libsvtav1")
+        );
+        assert!(ten_bit_encoder_name(HwAccelType::Qsv, TenBitCodec::Hevc).is_none());
+    }
+
     #[test]
     fn codec_name_returns_name() {
         let enc = make_encoder("h264_vaapi", HwAccelType::Vaapi, true);
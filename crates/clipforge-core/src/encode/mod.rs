@@ -1,7 +1,10 @@
 pub mod ffmpeg;
+pub mod filter;
 pub mod hw_probe;
 pub mod presets;
+pub mod template;
 
 pub use ffmpeg::FfmpegCommandBuilder;
+pub use filter::{FilterGraph, VideoTransform};
 pub use hw_probe::{probe_encoders, EncoderInfo, HwAccelType};
 pub use presets::QualityPreset;
@@ -0,0 +1,318 @@
+use crate::error::{Error, Result};
+
+/// Placeholders that may appear in user argument templates
+pub const PLACEHOLDERS: &[&str] = &["fps", "width", "height", "qp"];
+
+/// Args ClipForge manages itself. Overriding them would break capture,
+/// output selection, codec choice, split limits or process control (stdin
+/// 'q', progress parsing).
+const DENIED_ARGS: &[&str] = &[
+    "-y",
+    "-n",
+    "-f",
+    "-i",
+    "-map",
+    "-filter_complex",
+    "-lavfi",
+    "-vf",
+    "-filter:v",
+    "-af",
+    "-filter:a",
+    "-c",
+    "-codec",
+    "-c:v",
+    "-vcodec",
+    "-codec:v",
+    "-c:a",
+    "-acodec",
+    "-codec:a",
+    "-t",
+    "-to",
+    "-fs",
+    "-frames",
+    "-frames:v",
+    "-vframes",
+    "-nostdin",
+    "-progress",
+    "-stats_period",
+    "-v",
+    "-loglevel",
+];
+
+/// Arg prefixes ClipForge manages itself (segment muxer options)
+const DENIED_PREFIXES: &[&str] = &["-segment_"];
+
+/// Flags that take no value, so the next token is not consumed as one
+const VALUELESS_FLAGS: &[&str] = &[
+    "-an",
+    "-vn",
+    "-sn",
+    "-dn",
+    "-re",
+    "-shortest",
+    "-copyts",
+    "-start_at_zero",
+    "-hide_banner",
+];
+
+/// Values substituted into user argument templates
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+    pub fps: u32,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub qp: u32,
+}
+
+/// Check a user template against the denylist and placeholder names.
+///
+/// Bare tokens that aren't the value of a preceding flag are rejected since
+/// FFmpeg would treat them as extra output files.
+pub fn lint_args(args: &[String]) -> Result<()> {
+    for arg in args {
+        check_placeholders(arg)?;
+    }
+
+    for (flag, _) in tokenize(args)? {
+        if DENIED_ARGS.contains(&flag.as_str())
+            || DENIED_PREFIXES.iter().any(|p| flag.starts_with(p))
+        {
+            return Err(Error::Config(format!(
+                "argument '{flag}' is managed by ClipForge and cannot be overridden"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Substitute placeholders in every token of a template
+pub fn render_args(args: &[String], ctx: &TemplateContext) -> Result<Vec<String>> {
+    args.iter().map(|arg| render_token(arg, ctx)).collect()
+}
+
+/// Merge rendered extra args into `base`, starting the flag search at `from`.
+///
+/// Flags already present after `from` get their value replaced (so a custom
+/// `-g` overrides the generated one); new flags are appended.
+pub fn merge_args(base: &mut Vec<String>, from: usize, extra: &[String]) -> Result<()> {
+    for (flag, value) in tokenize(extra)? {
+        let existing = base
+            .iter()
+            .skip(from)
+            .position(|a| *a == flag)
+            .map(|i| i + from);
+
+        match (existing, value) {
+            // Replace the old value, unless the flag had none in `base`
+            (Some(idx), Some(value)) => match base.get(idx + 1) {
+                Some(next) if !is_flag(next) => base[idx + 1] = value,
+                _ => base.insert(idx + 1, value),
+            },
+            (Some(_), None) => {}
+            (None, value) => {
+                base.push(flag);
+                base.extend(value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Split args into (flag, value) pairs
+fn tokenize(args: &[String]) -> Result<Vec<(String, Option<String>)>> {
+    let mut pairs = Vec::new();
+    let mut iter = args.iter().peekable();
+
+    while let Some(arg) = iter.next() {
+        if !is_flag(arg) {
+            return Err(Error::Config(format!(
+                "unexpected bare argument '{arg}' (output paths are not allowed)"
+            )));
+        }
+        let value = if VALUELESS_FLAGS.contains(&arg.as_str()) {
+            None
+        } else {
+            match iter.peek() {
+                Some(next) if !is_flag(next) => iter.next().cloned(),
+                _ => None,
+            }
+        };
+        pairs.push((arg.clone(), value));
+    }
+    Ok(pairs)
+}
+
+/// A flag starts with '-' followed by a letter (so "-1" is a value)
+fn is_flag(arg: &str) -> bool {
+    let mut chars = arg.chars();
+    chars.next() == Some('-') && chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+}
+
+fn check_placeholders(arg: &str) -> Result<()> {
+    let mut rest = arg;
+    while let Some(open) = rest.find('{') {
+        let after = &rest[open + 1..];
+        let close = after
+            .find('}')
+            .ok_or_else(|| Error::Config(format!("unclosed placeholder in '{arg}'")))?;
+        let name = &after[..close];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(Error::Config(format!(
+                "unknown placeholder '{{{name}}}' in '{arg}' (available: {})",
+                PLACEHOLDERS.join(", ")
+            )));
+        }
+        rest = &after[close + 1..];
+    }
+    Ok(())
+}
+
+fn render_token(arg: &str, ctx: &TemplateContext) -> Result<String> {
+    check_placeholders(arg)?;
+
+    let dimension = |value: Option<u32>, name: &str| {
+        value.map(|v| v.to_string()).ok_or_else(|| {
+            Error::Config(format!(
+                "'{{{name}}}' is unknown for this capture mode (in '{arg}')"
+            ))
+        })
+    };
+
+    let mut out = arg.replace("{fps}", &ctx.fps.to_string());
+    out = out.replace("{qp}", &ctx.qp.to_string());
+    if out.contains("{width}") {
+        out = out.replace("{width}", &dimension(ctx.width, "width")?);
+    }
+    if out.contains("{height}") {
+        out = out.replace("{height}", &dimension(ctx.height, "height")?);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn ctx() -> TemplateContext {
+        TemplateContext {
+            fps: 144,
+            width: Some(1920),
+            height: Some(1080),
+            qp: 20,
+        }
+    }
+
+    #[test]
+    fn lint_accepts_encoder_tuning() {
+        let args = strings(&["-tune", "ll", "-spatial-aq", "1", "-g", "{fps}"]);
+        assert!(lint_args(&args).is_ok());
+    }
+
+    #[test]
+    fn lint_rejects_denied_args() {
+        for denied in [
+            "-y",
+            "-f",
+            "-map",
+            "-segment_time",
+            "-c",
+            "-c:a",
+            "-af",
+            "-t",
+            "-fs",
+            "-frames:v",
+        ] {
+            let args = strings(&[denied, "x"]);
+            let err = lint_args(&args).unwrap_err();
+            assert!(err.to_string().contains(denied), "got: {err}");
+        }
+    }
+
+    #[test]
+    fn lint_rejects_output_paths() {
+        let args = strings(&["-an", "/tmp/out.mp4"]);
+        let err = lint_args(&args).unwrap_err();
+        assert!(err.to_string().contains("/tmp/out.mp4"), "got: {err}");
+    }
+
+    #[test]
+    fn lint_rejects_unknown_placeholder() {
+        let args = strings(&["-b:v", "{bitrate}"]);
+        assert!(lint_args(&args).is_err());
+        let args = strings(&["-g", "{fps"]);
+        assert!(lint_args(&args).is_err());
+    }
+
+    #[test]
+    fn negative_numbers_are_values() {
+        let args = strings(&["-qmin", "-1"]);
+        assert!(lint_args(&args).is_ok());
+    }
+
+    #[test]
+    fn render_substitutes_placeholders() {
+        let args = strings(&["-g", "{fps}", "-qp", "{qp}", "-s", "{width}x{height}"]);
+        let rendered = render_args(&args, &ctx()).unwrap();
+        assert_eq!(
+            rendered,
+            strings(&["-g", "144", "-qp", "20", "-s", "1920x1080"])
+        );
+    }
+
+    #[test]
+    fn render_fails_on_unknown_dimensions() {
+        let mut ctx = ctx();
+        ctx.width = None;
+        let args = strings(&["-s", "{width}x{height}"]);
+        assert!(render_args(&args, &ctx).is_err());
+    }
+
+    #[test]
+    fn merge_replaces_generated_values_and_appends_new_flags() {
+        let mut base = strings(&[
+            "-i",
+            "in",
+            "-c:v",
+            "h264_nvenc",
+            "-preset",
+            "p4",
+            "-g",
+            "120",
+        ]);
+        let extra = strings(&["-g", "288", "-tune", "ll"]);
+        merge_args(&mut base, 2, &extra).unwrap();
+        assert_eq!(
+            base,
+            strings(&[
+                "-i",
+                "in",
+                "-c:v",
+                "h264_nvenc",
+                "-preset",
+                "p4",
+                "-g",
+                "288",
+                "-tune",
+                "ll"
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_does_not_overwrite_the_next_flag() {
+        let mut base = strings(&["-c:v", "libx264", "-bf", "-g", "120"]);
+        merge_args(&mut base, 0, &strings(&["-bf", "2"])).unwrap();
+        assert_eq!(base, strings(&["-c:v", "libx264", "-bf", "2", "-g", "120"]));
+    }
+
+    #[test]
+    fn merge_only_searches_after_start() {
+        let mut base = strings(&["-g", "1", "-c:v", "libx264"]);
+        merge_args(&mut base, 2, &strings(&["-g", "60"])).unwrap();
+        assert_eq!(base, strings(&["-g", "1", "-c:v", "libx264", "-g", "60"]));
+    }
+}
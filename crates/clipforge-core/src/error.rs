@@ -44,6 +44,9 @@ pub enum Error {
     #[error("Replay buffer not active")]
     ReplayNotActive,
 
+    #[error("Replay buffer already running (pid {0})")]
+    ReplayAlreadyRunning(u32),
+
     #[error("No segments available for replay save")]
     NoSegments,
 
@@ -1,6 +1,8 @@
+use crate::encode::hw_probe::has_filter;
 use crate::error::{Error, Result};
 use crate::export::presets::ExportPreset;
 use crate::process::FfmpegProcess;
+use crate::runner::FfmpegRunner;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tracing::info;
@@ -12,8 +14,15 @@ pub struct ExportJob {
     pub preset: ExportPreset,
     pub trim_start: Option<f64>,
     pub trim_end: Option<f64>,
+    /// Tonemap HDR (PQ/HLG) input down to BT.709 SDR
+    #[serde(default)]
+    pub tonemap_sdr: bool,
 }
 
+/// zscale/tonemap chain converting HDR input to 8-bit BT.709
+const TONEMAP_SDR_FILTER: &str = "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,\
+tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p";
+
 pub struct ExportPipeline;
 
 impl ExportPipeline {
@@ -35,6 +44,10 @@ impl ExportPipeline {
         // Build filter chain
         let mut filters = Vec::new();
 
+        if job.tonemap_sdr {
+            filters.push(TONEMAP_SDR_FILTER.to_string());
+        }
+
         // Crop to aspect ratio
         if let Some((aw, ah)) = job.preset.crop_aspect {
             filters.push(format!("crop=ih*{aw}/{ah}:ih"));
@@ -59,6 +72,16 @@ impl ExportPipeline {
         if let Some(ref bitrate) = job.preset.bitrate {
             args.extend(["-b:v".to_string(), bitrate.clone()]);
         }
+        if job.tonemap_sdr {
+            args.extend([
+                "-color_primaries".to_string(),
+                "bt709".to_string(),
+                "-color_trc".to_string(),
+                "bt709".to_string(),
+                "-colorspace".to_string(),
+                "bt709".to_string(),
+            ]);
+        }
 
         // Audio
         if job.preset.loudnorm {
@@ -84,27 +107,22 @@ impl ExportPipeline {
     }
 
     /// Run an export job
-    pub async fn run(job: &ExportJob) -> Result<()> {
+    pub async fn run(runner: &dyn FfmpegRunner, job: &ExportJob) -> Result<()> {
+        if job.tonemap_sdr && !has_filter(runner, "zscale").await {
+            return Err(Error::ExportFailed(
+                "tonemapping needs an FFmpeg built with zscale (libzimg)".into(),
+            ));
+        }
+
         let args = Self::build_args(job);
         info!(args = ?args, "starting export");
 
-        let process = FfmpegProcess::spawn(args).await?;
-
-        // Wait for completion by watching state
-        let mut state_rx = process.subscribe_state();
-        loop {
-            state_rx
-                .changed()
-                .await
-                .map_err(|_| Error::Other("state channel closed".into()))?;
-            let state = *state_rx.borrow();
-            match state {
-                crate::process::ProcessState::Stopped => break,
-                crate::process::ProcessState::Failed => {
-                    return Err(Error::ExportFailed("FFmpeg process failed".into()));
-                }
-                _ => continue,
-            }
+        let process = FfmpegProcess::spawn(runner, args).await?;
+
+        let exit = process.wait().await;
+        if let Some(reason) = exit.failure {
+            let detail = exit.stderr_tail.last().cloned().unwrap_or_default();
+            return Err(Error::ExportFailed(format!("{reason}: {detail}")));
         }
 
         info!(output = %job.output.display(), "export completed");
@@ -115,6 +133,8 @@ impl ExportPipeline {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner::fake::{FakeResponse, FakeRunner};
+    use crate::runner::Tool;
 
     fn make_job(preset: ExportPreset, trim_start: Option<f64>, trim_end: Option<f64>) -> ExportJob {
         ExportJob {
@@ -123,6 +143,7 @@ mod tests {
             preset,
             trim_start,
             trim_end,
+            tonemap_sdr: false,
         }
     }
 
@@ -181,4 +202,66 @@ mod tests {
         let args = ExportPipeline::build_args(&job);
         assert!(!args.contains(&"-af".to_string()));
     }
+
+    #[test]
+    fn tonemap_sdr_runs_before_scale_and_tags_bt709() {
+        let mut job = make_job(ExportPreset::youtube(), None, None);
+        job.tonemap_sdr = true;
+        let args = ExportPipeline::build_args(&job);
+        let vf_idx = args.iter().position(|a| a == "-vf").unwrap();
+        let filter = &args[vf_idx + 1];
+        assert!(filter.starts_with("zscale=t=linear"), "got: {filter}");
+        assert!(filter.find("tonemap=").unwrap() < filter.find("scale=1920").unwrap());
+        let trc_idx = args.iter().position(|a| a == "-color_trc").unwrap();
+        assert_eq!(args[trc_idx + 1], "bt709");
+    }
+
+    #[test]
+    fn no_color_tags_without_tonemap() {
+        let job = make_job(ExportPreset::youtube(), None, None);
+        let args = ExportPipeline::build_args(&job);
+        assert!(!args.contains(&"-color_trc".to_string()));
+    }
+
+    #[tokio::test]
+    async fn run_succeeds_when_ffmpeg_exits_cleanly() {
+        let runner = FakeRunner::new().on(Tool::Ffmpeg, "output.mp4", FakeResponse::ok());
+        let job = make_job(ExportPreset::youtube(), None, None);
+
+        ExportPipeline::run(&runner, &job).await.unwrap();
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].1.contains(&"/tmp/input.mkv".to_string()));
+    }
+
+    #[tokio::test]
+    async fn tonemap_needs_zscale() {
+        let runner = FakeRunner::new()
+            .on(
+                Tool::Ffmpeg,
+                "-filters",
+                FakeResponse::ok().stdout(" TSC scale  V->V  Scale the input video size.\n"),
+            )
+            .on(Tool::Ffmpeg, "output.mp4", FakeResponse::ok());
+        let mut job = make_job(ExportPreset::youtube(), None, None);
+        job.tonemap_sdr = true;
+
+        let err = ExportPipeline::run(&runner, &job).await.unwrap_err();
+        assert!(err.to_string().contains("zscale"), "got: {err}");
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn run_reports_classified_failure() {
+        let runner = FakeRunner::new().on(
+            Tool::Ffmpeg,
+            "output.mp4",
+            FakeResponse::failing(1, "/tmp/output.mp4: No space left on device\n"),
+        );
+        let job = make_job(ExportPreset::youtube(), None, None);
+
+        let err = ExportPipeline::run(&runner, &job).await.unwrap_err();
+        assert!(err.to_string().contains("disk is full"), "got: {err}");
+    }
 }
@@ -4,27 +4,33 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum HotkeyAction {
     ToggleRecording,
+    PauseRecording,
     SaveReplay,
     ToggleReplayBuffer,
     MarkHighlight,
+    CycleProfile,
 }
 
 impl HotkeyAction {
     pub fn all() -> &'static [HotkeyAction] {
         &[
             HotkeyAction::ToggleRecording,
+            HotkeyAction::PauseRecording,
             HotkeyAction::SaveReplay,
             HotkeyAction::ToggleReplayBuffer,
             HotkeyAction::MarkHighlight,
+            HotkeyAction::CycleProfile,
         ]
     }
 
     pub fn label(&self) -> &'static str {
         match self {
             HotkeyAction::ToggleRecording => "Toggle Recording",
+            HotkeyAction::PauseRecording => "Pause/Resume Recording",
             HotkeyAction::SaveReplay => "Save Replay",
             HotkeyAction::ToggleReplayBuffer => "Toggle Replay Buffer",
             HotkeyAction::MarkHighlight => "Mark Highlight",
+            HotkeyAction::CycleProfile => "Cycle Recording Profile",
         }
     }
 }
@@ -8,4 +8,6 @@ pub mod export;
 pub mod hotkeys;
 pub mod library;
 pub mod process;
+pub mod recording;
 pub mod replay;
+pub mod runner;
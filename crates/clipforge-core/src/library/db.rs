@@ -1,5 +1,7 @@
 use crate::error::{Error, Result};
 use crate::process::run_ffprobe;
+use crate::recording::PerformanceSummary;
+use crate::runner::FfmpegRunner;
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -20,6 +22,53 @@ pub struct Recording {
     pub game_name: Option<String>,
     pub created_at: String,
     pub thumbnail_path: Option<String>,
+    /// Shared by the parts of a split recording
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// 1-based position within the session
+    #[serde(default)]
+    pub part: Option<u32>,
+    /// Salvaged from a recording that was cut short by a crash
+    #[serde(default)]
+    pub recovered: bool,
+    /// Number of parts in the session; read from the library, not stored
+    #[serde(default)]
+    pub part_count: Option<u32>,
+}
+
+/// Columns of `recordings r`, with the size of each entry's session
+const RECORDING_COLUMNS: &str = "r.id, r.title, r.file_path, r.file_size, r.duration,
+    r.resolution, r.fps, r.codec, r.container, r.source_type, r.game_name, r.created_at,
+    r.thumbnail_path, r.session_id, r.part, r.recovered,
+    (SELECT COUNT(*) FROM recordings s WHERE s.session_id = r.session_id)";
+
+/// Columns added after the first release, created on open if missing
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("session_id", "TEXT"),
+    ("part", "INTEGER"),
+    ("recovered", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+fn recording_from_row(row: &rusqlite::Row) -> rusqlite::Result<Recording> {
+    Ok(Recording {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        file_path: row.get(2)?,
+        file_size: row.get(3)?,
+        duration: row.get(4)?,
+        resolution: row.get(5)?,
+        fps: row.get(6)?,
+        codec: row.get(7)?,
+        container: row.get(8)?,
+        source_type: row.get(9)?,
+        game_name: row.get(10)?,
+        created_at: row.get(11)?,
+        thumbnail_path: row.get(12)?,
+        session_id: row.get(13)?,
+        part: row.get(14)?,
+        recovered: row.get(15)?,
+        part_count: Some(row.get(16)?).filter(|&count: &u32| count > 0),
+    })
 }
 
 pub struct Library {
@@ -48,7 +97,20 @@ impl Library {
                 source_type TEXT NOT NULL DEFAULT 'recording',
                 game_name TEXT,
                 created_at TEXT NOT NULL,
-                thumbnail_path TEXT
+                thumbnail_path TEXT,
+                session_id TEXT,
+                part INTEGER,
+                recovered INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE TABLE IF NOT EXISTS recording_stats (
+                recording_id TEXT PRIMARY KEY,
+                avg_fps REAL NOT NULL DEFAULT 0,
+                low_1pct_fps REAL NOT NULL DEFAULT 0,
+                total_drops INTEGER NOT NULL DEFAULT 0,
+                total_dups INTEGER NOT NULL DEFAULT 0,
+                avg_speed REAL NOT NULL DEFAULT 0,
+                samples INTEGER NOT NULL DEFAULT 0
             );
 
             CREATE VIRTUAL TABLE IF NOT EXISTS recordings_fts USING fts5(
@@ -73,6 +135,7 @@ impl Library {
             END;",
         )
         .map_err(|e| Error::Database(e.to_string()))?;
+        add_missing_columns(&conn)?;
 
         Ok(Self { conn })
     }
@@ -82,8 +145,9 @@ impl Library {
             .execute(
                 "INSERT OR REPLACE INTO recordings
              (id, title, file_path, file_size, duration, resolution, fps, codec,
-              container, source_type, game_name, created_at, thumbnail_path)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+              container, source_type, game_name, created_at, thumbnail_path,
+              session_id, part, recovered)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
                 params![
                     recording.id,
                     recording.title,
@@ -98,6 +162,9 @@ impl Library {
                     recording.game_name,
                     recording.created_at,
                     recording.thumbnail_path,
+                    recording.session_id,
+                    recording.part,
+                    recording.recovered,
                 ],
             )
             .map_err(|e| Error::Database(e.to_string()))?;
@@ -109,31 +176,14 @@ impl Library {
     pub fn list(&self, limit: u32, offset: u32) -> Result<Vec<Recording>> {
         let mut stmt = self
             .conn
-            .prepare(
-                "SELECT id, title, file_path, file_size, duration, resolution, fps, codec,
-                    container, source_type, game_name, created_at, thumbnail_path
-             FROM recordings ORDER BY created_at DESC LIMIT ?1 OFFSET ?2",
-            )
+            .prepare(&format!(
+                "SELECT {RECORDING_COLUMNS}
+             FROM recordings r ORDER BY r.created_at DESC, r.part LIMIT ?1 OFFSET ?2"
+            ))
             .map_err(|e| Error::Database(e.to_string()))?;
 
         let rows = stmt
-            .query_map(params![limit, offset], |row| {
-                Ok(Recording {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    file_path: row.get(2)?,
-                    file_size: row.get(3)?,
-                    duration: row.get(4)?,
-                    resolution: row.get(5)?,
-                    fps: row.get(6)?,
-                    codec: row.get(7)?,
-                    container: row.get(8)?,
-                    source_type: row.get(9)?,
-                    game_name: row.get(10)?,
-                    created_at: row.get(11)?,
-                    thumbnail_path: row.get(12)?,
-                })
-            })
+            .query_map(params![limit, offset], recording_from_row)
             .map_err(|e| Error::Database(e.to_string()))?;
 
         let mut recordings = Vec::new();
@@ -146,35 +196,17 @@ impl Library {
     pub fn search(&self, query: &str) -> Result<Vec<Recording>> {
         let mut stmt = self
             .conn
-            .prepare(
-                "SELECT r.id, r.title, r.file_path, r.file_size, r.duration, r.resolution,
-                    r.fps, r.codec, r.container, r.source_type, r.game_name,
-                    r.created_at, r.thumbnail_path
+            .prepare(&format!(
+                "SELECT {RECORDING_COLUMNS}
              FROM recordings r
              JOIN recordings_fts f ON r.rowid = f.rowid
              WHERE recordings_fts MATCH ?1
-             ORDER BY r.created_at DESC",
-            )
+             ORDER BY r.created_at DESC, r.part"
+            ))
             .map_err(|e| Error::Database(e.to_string()))?;
 
         let rows = stmt
-            .query_map(params![query], |row| {
-                Ok(Recording {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    file_path: row.get(2)?,
-                    file_size: row.get(3)?,
-                    duration: row.get(4)?,
-                    resolution: row.get(5)?,
-                    fps: row.get(6)?,
-                    codec: row.get(7)?,
-                    container: row.get(8)?,
-                    source_type: row.get(9)?,
-                    game_name: row.get(10)?,
-                    created_at: row.get(11)?,
-                    thumbnail_path: row.get(12)?,
-                })
-            })
+            .query_map(params![query], recording_from_row)
             .map_err(|e| Error::Database(e.to_string()))?;
 
         let mut recordings = Vec::new();
@@ -188,36 +220,69 @@ impl Library {
         self.conn
             .execute("DELETE FROM recordings WHERE id = ?1", params![id])
             .map_err(|e| Error::Database(e.to_string()))?;
+        self.conn
+            .execute(
+                "DELETE FROM recording_stats WHERE recording_id = ?1",
+                params![id],
+            )
+            .map_err(|e| Error::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Store the capture performance summary for a recording
+    pub fn set_stats(&self, id: &str, stats: &PerformanceSummary) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO recording_stats
+             (recording_id, avg_fps, low_1pct_fps, total_drops, total_dups, avg_speed, samples)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    id,
+                    stats.avg_fps,
+                    stats.low_1pct_fps,
+                    stats.total_drops as i64,
+                    stats.total_dups as i64,
+                    stats.avg_speed,
+                    stats.samples as i64,
+                ],
+            )
+            .map_err(|e| Error::Database(e.to_string()))?;
         Ok(())
     }
 
+    pub fn get_stats(&self, id: &str) -> Result<Option<PerformanceSummary>> {
+        let result = self.conn.query_row(
+            "SELECT avg_fps, low_1pct_fps, total_drops, total_dups, avg_speed, samples
+             FROM recording_stats WHERE recording_id = ?1",
+            params![id],
+            |row| {
+                Ok(PerformanceSummary {
+                    avg_fps: row.get(0)?,
+                    low_1pct_fps: row.get(1)?,
+                    total_drops: row.get::<_, i64>(2)? as u64,
+                    total_dups: row.get::<_, i64>(3)? as u64,
+                    avg_speed: row.get(4)?,
+                    samples: row.get::<_, i64>(5)? as u64,
+                })
+            },
+        );
+
+        match result {
+            Ok(stats) => Ok(Some(stats)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(Error::Database(e.to_string())),
+        }
+    }
+
     pub fn get(&self, id: &str) -> Result<Option<Recording>> {
         let mut stmt = self
             .conn
-            .prepare(
-                "SELECT id, title, file_path, file_size, duration, resolution, fps, codec,
-                    container, source_type, game_name, created_at, thumbnail_path
-             FROM recordings WHERE id = ?1",
-            )
+            .prepare(&format!(
+                "SELECT {RECORDING_COLUMNS} FROM recordings r WHERE r.id = ?1"
+            ))
             .map_err(|e| Error::Database(e.to_string()))?;
 
-        let result = stmt.query_row(params![id], |row| {
-            Ok(Recording {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                file_path: row.get(2)?,
-                file_size: row.get(3)?,
-                duration: row.get(4)?,
-                resolution: row.get(5)?,
-                fps: row.get(6)?,
-                codec: row.get(7)?,
-                container: row.get(8)?,
-                source_type: row.get(9)?,
-                game_name: row.get(10)?,
-                created_at: row.get(11)?,
-                thumbnail_path: row.get(12)?,
-            })
-        });
+        let result = stmt.query_row(params![id], recording_from_row);
 
         match result {
             Ok(recording) => Ok(Some(recording)),
@@ -225,19 +290,64 @@ impl Library {
             Err(e) => Err(Error::Database(e.to_string())),
         }
     }
+
+    /// The parts of a split recording, in order
+    pub fn session_parts(&self, session_id: &str) -> Result<Vec<Recording>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {RECORDING_COLUMNS} FROM recordings r WHERE r.session_id = ?1 ORDER BY r.part"
+            ))
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(params![session_id], recording_from_row)
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let mut recordings = Vec::new();
+        for row in rows {
+            recordings.push(row.map_err(|e| Error::Database(e.to_string()))?);
+        }
+        Ok(recordings)
+    }
+}
+
+/// Add columns newer than an existing database
+fn add_missing_columns(conn: &Connection) -> Result<()> {
+    let mut stmt = conn
+        .prepare("SELECT name FROM pragma_table_info('recordings')")
+        .map_err(|e| Error::Database(e.to_string()))?;
+    let existing: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+    for (column, kind) in ADDED_COLUMNS {
+        if !existing.iter().any(|c| c == column) {
+            conn.execute(
+                &format!("ALTER TABLE recordings ADD COLUMN {column} {kind}"),
+                [],
+            )
+            .map_err(|e| Error::Database(e.to_string()))?;
+        }
+    }
+    Ok(())
 }
 
 /// Probe a media file with ffprobe and extract metadata
-pub async fn probe_media(file_path: &Path) -> Result<MediaInfo> {
-    let output = run_ffprobe(&[
-        "-v",
-        "quiet",
-        "-print_format",
-        "json",
-        "-show_format",
-        "-show_streams",
-        &file_path.to_string_lossy(),
-    ])
+pub async fn probe_media(runner: &dyn FfmpegRunner, file_path: &Path) -> Result<MediaInfo> {
+    let output = run_ffprobe(
+        runner,
+        &[
+            "-v",
+            "quiet",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+            &file_path.to_string_lossy(),
+        ],
+    )
     .await?;
 
     let json: serde_json::Value = serde_json::from_str(&output).map_err(Error::Json)?;
@@ -307,19 +417,26 @@ fn parse_frame_rate(s: &str) -> f64 {
 }
 
 /// Generate a thumbnail for a video file
-pub async fn generate_thumbnail(input: &Path, output: &Path) -> Result<()> {
-    crate::process::run_ffmpeg(&[
-        "-ss",
-        "5",
-        "-i",
-        &input.to_string_lossy(),
-        "-frames:v",
-        "1",
-        "-vf",
-        "scale=320:-1",
-        "-y",
-        &output.to_string_lossy(),
-    ])
+pub async fn generate_thumbnail(
+    runner: &dyn FfmpegRunner,
+    input: &Path,
+    output: &Path,
+) -> Result<()> {
+    crate::process::run_ffmpeg(
+        runner,
+        &[
+            "-ss",
+            "5",
+            "-i",
+            &input.to_string_lossy(),
+            "-frames:v",
+            "1",
+            "-vf",
+            "scale=320:-1",
+            "-y",
+            &output.to_string_lossy(),
+        ],
+    )
     .await?;
     Ok(())
 }
@@ -327,6 +444,8 @@ pub async fn generate_thumbnail(input: &Path, output: &Path) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner::fake::{FakeResponse, FakeRunner};
+    use crate::runner::Tool;
 
     fn sample_recording(id: &str, title: &str, game: Option<&str>) -> Recording {
         Recording {
@@ -343,6 +462,10 @@ mod tests {
             game_name: game.map(|s| s.to_string()),
             created_at: format!("2025-01-01T00:00:{:02}Z", id.len()),
             thumbnail_path: None,
+            session_id: None,
+            part: None,
+            recovered: false,
+            part_count: None,
         }
     }
 
@@ -393,6 +516,32 @@ mod tests {
         assert_eq!(page2.len(), 2);
     }
 
+    #[test]
+    fn list_keeps_session_parts_together_in_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lib = Library::open(&tmp.path().join("lib.db")).unwrap();
+
+        lib.insert(&sample_recording("solo", "Solo", None)).unwrap();
+        for part in [2, 3, 1] {
+            let mut rec = sample_recording(&format!("p{part}"), &format!("Part {part}"), None);
+            rec.created_at = "2025-01-02T00:00:00Z".to_string();
+            rec.session_id = Some("session".to_string());
+            rec.part = Some(part);
+            lib.insert(&rec).unwrap();
+        }
+
+        let listed = lib.list(10, 0).unwrap();
+        let order: Vec<_> = listed.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(order, ["p1", "p2", "p3", "solo"]);
+        assert!(listed[..3].iter().all(|r| r.part_count == Some(3)));
+        assert_eq!(listed[3].part_count, None);
+
+        // The count covers the whole session, not just the page that was loaded
+        let page = lib.list(1, 1).unwrap();
+        assert_eq!(page[0].id, "p2");
+        assert_eq!(page[0].part_count, Some(3));
+    }
+
     #[test]
     fn list_empty_returns_empty_vec() {
         let tmp = tempfile::tempdir().unwrap();
@@ -481,6 +630,81 @@ mod tests {
         assert_eq!(all.len(), 1);
     }
 
+    #[test]
+    fn stats_roundtrip_and_are_deleted_with_recording() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lib = Library::open(&tmp.path().join("lib.db")).unwrap();
+        lib.insert(&sample_recording("r1", "Match", None)).unwrap();
+        assert!(lib.get_stats("r1").unwrap().is_none());
+
+        let stats = PerformanceSummary {
+            avg_fps: 59.8,
+            low_1pct_fps: 41.0,
+            total_drops: 12,
+            total_dups: 3,
+            avg_speed: 1.0,
+            samples: 240,
+        };
+        lib.set_stats("r1", &stats).unwrap();
+        assert_eq!(lib.get_stats("r1").unwrap(), Some(stats));
+
+        lib.delete("r1").unwrap();
+        assert!(lib.get_stats("r1").unwrap().is_none());
+    }
+
+    #[test]
+    fn session_parts_come_back_in_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lib = Library::open(&tmp.path().join("lib.db")).unwrap();
+        for (id, part) in [("p2", 2), ("p1", 1), ("p3", 3)] {
+            let mut rec = sample_recording(id, &format!("Long Session {part}"), None);
+            rec.session_id = Some("s1".to_string());
+            rec.part = Some(part);
+            lib.insert(&rec).unwrap();
+        }
+        lib.insert(&sample_recording("other", "Other", None))
+            .unwrap();
+
+        let parts = lib.session_parts("s1").unwrap();
+        let ids: Vec<&str> = parts.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["p1", "p2", "p3"]);
+        assert_eq!(parts[2].part, Some(3));
+        assert!(lib.get("other").unwrap().unwrap().session_id.is_none());
+    }
+
+    #[test]
+    fn open_adds_session_columns_to_old_databases() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db_path = tmp.path().join("lib.db");
+        Connection::open(&db_path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE recordings (
+                    id TEXT PRIMARY KEY, title TEXT NOT NULL,
+                    file_path TEXT NOT NULL UNIQUE, file_size INTEGER NOT NULL DEFAULT 0,
+                    duration REAL NOT NULL DEFAULT 0, resolution TEXT NOT NULL DEFAULT '',
+                    fps REAL NOT NULL DEFAULT 0, codec TEXT NOT NULL DEFAULT '',
+                    container TEXT NOT NULL DEFAULT '',
+                    source_type TEXT NOT NULL DEFAULT 'recording', game_name TEXT,
+                    created_at TEXT NOT NULL, thumbnail_path TEXT
+                );
+                INSERT INTO recordings (id, title, file_path, created_at)
+                VALUES ('old', 'Old', '/tmp/old.mkv', '2024-01-01T00:00:00Z');",
+            )
+            .unwrap();
+
+        let lib = Library::open(&db_path).unwrap();
+        let old = lib.get("old").unwrap().unwrap();
+        assert_eq!((old.session_id, old.part), (None, None));
+        assert!(!old.recovered);
+
+        let mut rec = sample_recording("new", "New", None);
+        rec.session_id = Some("s1".to_string());
+        rec.part = Some(1);
+        lib.insert(&rec).unwrap();
+        assert_eq!(lib.session_parts("s1").unwrap().len(), 1);
+    }
+
     #[test]
     fn parse_frame_rate_fraction() {
         assert!((parse_frame_rate("30/1") - 30.0).abs() < 0.01);
@@ -503,4 +727,39 @@ mod tests {
     fn parse_frame_rate_divide_by_zero() {
         assert_eq!(parse_frame_rate("30/0"), 0.0);
     }
+
+    #[tokio::test]
+    async fn probe_media_parses_ffprobe_json() {
+        let json = r#"{
+            "streams": [
+                {"codec_type": "audio", "codec_name": "aac"},
+                {"codec_type": "video", "codec_name": "h264", "width": 1920,
+                 "height": 1080, "r_frame_rate": "60/1"}
+            ],
+            "format": {"duration": "12.500000", "size": "2048"}
+        }"#;
+        let runner =
+            FakeRunner::new().on(Tool::Ffprobe, "clip.mkv", FakeResponse::ok().stdout(json));
+
+        let info = probe_media(&runner, Path::new("/rec/clip.mkv"))
+            .await
+            .unwrap();
+        assert!((info.duration - 12.5).abs() < 0.001);
+        assert_eq!(info.file_size, 2048);
+        assert_eq!((info.width, info.height), (1920, 1080));
+        assert!((info.fps - 60.0).abs() < 0.01);
+        assert_eq!(info.codec, "h264");
+    }
+
+    #[tokio::test]
+    async fn probe_media_fails_when_ffprobe_fails() {
+        let runner = FakeRunner::new().on(
+            Tool::Ffprobe,
+            "clip.mkv",
+            FakeResponse::failing(1, "clip.mkv: Invalid data found"),
+        );
+        assert!(probe_media(&runner, Path::new("/rec/clip.mkv"))
+            .await
+            .is_err());
+    }
 }
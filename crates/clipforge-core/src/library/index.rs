@@ -0,0 +1,321 @@
+//! Adding finished files to the library.
+//!
+//! Probing and thumbnailing run without touching the database, so callers
+//! that share a `Library` behind a lock only hold it for the insert.
+
+use super::db::{generate_thumbnail, probe_media, Library, Recording};
+use crate::error::Result;
+use crate::recording::{PerformanceSummary, RecoveredRecording};
+use crate::runner::FfmpegRunner;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// What produced a library entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceType {
+    Recording,
+    Replay,
+}
+
+impl SourceType {
+    /// Value stored in the `source_type` column
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SourceType::Recording => "recording",
+            SourceType::Replay => "replay",
+        }
+    }
+}
+
+/// Probe a finished file and render its thumbnail into `thumbnails_dir`.
+///
+/// A failed thumbnail leaves `thumbnail_path` empty rather than failing.
+pub async fn describe(
+    runner: &dyn FfmpegRunner,
+    path: &Path,
+    thumbnails_dir: &Path,
+    source: SourceType,
+) -> Result<Recording> {
+    let info = probe_media(runner, path).await?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let title = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Untitled".to_string());
+
+    let _ = std::fs::create_dir_all(thumbnails_dir);
+    let thumb_path = thumbnails_dir.join(format!("{id}.jpg"));
+    let _ = generate_thumbnail(runner, path, &thumb_path).await;
+
+    Ok(Recording {
+        id,
+        title,
+        file_path: path.to_string_lossy().to_string(),
+        file_size: info.file_size,
+        duration: info.duration,
+        resolution: format!("{}x{}", info.width, info.height),
+        fps: info.fps,
+        codec: info.codec,
+        container: path
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        source_type: source.as_str().to_string(),
+        game_name: None,
+        created_at: chrono::Local::now().to_rfc3339(),
+        thumbnail_path: thumb_path
+            .exists()
+            .then(|| thumb_path.to_string_lossy().to_string()),
+        session_id: None,
+        part: None,
+        recovered: false,
+        part_count: None,
+    })
+}
+
+/// Describe the files of one recording. Several parts share a session id
+/// and are numbered in order.
+pub async fn describe_parts(
+    runner: &dyn FfmpegRunner,
+    parts: &[PathBuf],
+    thumbnails_dir: &Path,
+    source: SourceType,
+) -> Result<Vec<Recording>> {
+    let session_id = (parts.len() > 1).then(|| uuid::Uuid::new_v4().to_string());
+    let mut recordings: Vec<Recording> = Vec::with_capacity(parts.len());
+    for (i, path) in parts.iter().enumerate() {
+        let mut recording = describe(runner, path, thumbnails_dir, source).await?;
+        if session_id.is_some() {
+            recording.session_id = session_id.clone();
+            recording.part = Some(i as u32 + 1);
+            // Parts of a session share the first part's time so they list together, in order
+            if let Some(first) = recordings.first() {
+                recording.created_at = first.created_at.clone();
+            }
+        }
+        recordings.push(recording);
+    }
+    Ok(recordings)
+}
+
+/// Insert a described entry along with its capture performance, if any
+pub fn store(
+    library: &Library,
+    recording: &Recording,
+    performance: Option<&PerformanceSummary>,
+) -> Result<()> {
+    library.insert(recording)?;
+    if let Some(performance) = performance {
+        library.set_stats(&recording.id, performance)?;
+    }
+    Ok(())
+}
+
+/// Insert the parts of one recording; the performance covers the whole
+/// session and is stored with the first part
+pub fn store_parts(
+    library: &Library,
+    recordings: &[Recording],
+    performance: Option<&PerformanceSummary>,
+) -> Result<()> {
+    for (i, recording) in recordings.iter().enumerate() {
+        store(library, recording, performance.filter(|_| i == 0))?;
+    }
+    Ok(())
+}
+
+/// Describe and store a finished file in one step
+pub async fn index_file(
+    runner: &dyn FfmpegRunner,
+    library: &Library,
+    path: &Path,
+    thumbnails_dir: &Path,
+    source: SourceType,
+    performance: Option<&PerformanceSummary>,
+) -> Result<Recording> {
+    let recording = describe(runner, path, thumbnails_dir, source).await?;
+    store(library, &recording, performance)?;
+    Ok(recording)
+}
+
+/// Describe and store the parts of a finished recording in one step
+pub async fn index_parts(
+    runner: &dyn FfmpegRunner,
+    library: &Library,
+    parts: &[PathBuf],
+    thumbnails_dir: &Path,
+    source: SourceType,
+    performance: Option<&PerformanceSummary>,
+) -> Result<Vec<Recording>> {
+    let recordings = describe_parts(runner, parts, thumbnails_dir, source).await?;
+    store_parts(library, &recordings, performance)?;
+    Ok(recordings)
+}
+
+/// Describe and store a recording salvaged after a crash, flagged as
+/// recovered. No performance was kept for it.
+pub async fn index_recovered(
+    runner: &dyn FfmpegRunner,
+    library: &Library,
+    recovered: &RecoveredRecording,
+    thumbnails_dir: &Path,
+) -> Result<Vec<Recording>> {
+    let mut recordings = describe_parts(
+        runner,
+        &recovered.parts,
+        thumbnails_dir,
+        SourceType::Recording,
+    )
+    .await?;
+    for recording in &mut recordings {
+        recording.recovered = true;
+    }
+    store_parts(library, &recordings, None)?;
+    Ok(recordings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::{FakeResponse, FakeRunner};
+    use crate::runner::Tool;
+
+    const PROBE_JSON: &str = r#"{
+        "streams": [{"codec_type": "video", "codec_name": "h264", "width": 1280,
+                     "height": 720, "r_frame_rate": "60/1"}],
+        "format": {"duration": "30.000000", "size": "4096"}
+    }"#;
+
+    #[tokio::test]
+    async fn replay_is_indexed_with_thumbnail_and_source_type() {
+        let tmp = tempfile::tempdir().unwrap();
+        let library = Library::open(&tmp.path().join("lib.db")).unwrap();
+        let thumbs = tmp.path().join("thumbs");
+        let runner = FakeRunner::new()
+            .on(
+                Tool::Ffprobe,
+                "replay_1.mkv",
+                FakeResponse::ok().stdout(PROBE_JSON),
+            )
+            .on(Tool::Ffmpeg, "scale=320", FakeResponse::ok().writes(b"jpg"));
+
+        let recording = index_file(
+            &runner,
+            &library,
+            Path::new("/replays/replay_1.mkv"),
+            &thumbs,
+            SourceType::Replay,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let stored = library.get(&recording.id).unwrap().unwrap();
+        assert_eq!(stored.source_type, "replay");
+        assert_eq!(stored.title, "replay_1");
+        assert_eq!(stored.resolution, "1280x720");
+        assert_eq!(stored.container, "mkv");
+        let thumb = stored.thumbnail_path.unwrap();
+        assert!(thumb.starts_with(&*thumbs.to_string_lossy()));
+        assert!(library.get_stats(&recording.id).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn split_parts_share_a_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let library = Library::open(&tmp.path().join("lib.db")).unwrap();
+        let runner = FakeRunner::new()
+            .on(Tool::Ffprobe, "rec_", FakeResponse::ok().stdout(PROBE_JSON))
+            .on(Tool::Ffmpeg, "scale=320", FakeResponse::ok().writes(b"jpg"));
+        let parts = [
+            PathBuf::from("/rec/rec_001.mkv"),
+            PathBuf::from("/rec/rec_002.mkv"),
+        ];
+        let performance = PerformanceSummary::default();
+
+        let recordings = index_parts(
+            &runner,
+            &library,
+            &parts,
+            &tmp.path().join("thumbs"),
+            SourceType::Recording,
+            Some(&performance),
+        )
+        .await
+        .unwrap();
+
+        let session = recordings[0].session_id.clone().unwrap();
+        let stored = library.session_parts(&session).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[1].title, "rec_002");
+        assert_eq!(stored[1].part, Some(2));
+        assert!(library.get_stats(&stored[0].id).unwrap().is_some());
+        assert!(library.get_stats(&stored[1].id).unwrap().is_none());
+
+        // A single file is not a session
+        let single = describe_parts(&runner, &parts[..1], tmp.path(), SourceType::Recording)
+            .await
+            .unwrap();
+        assert_eq!(
+            (single[0].session_id.as_ref(), single[0].part),
+            (None, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn recovered_recording_is_flagged() {
+        let tmp = tempfile::tempdir().unwrap();
+        let library = Library::open(&tmp.path().join("lib.db")).unwrap();
+        let runner = FakeRunner::new()
+            .on(
+                Tool::Ffprobe,
+                "rec_x",
+                FakeResponse::ok().stdout(PROBE_JSON),
+            )
+            .on(Tool::Ffmpeg, "scale=320", FakeResponse::ok().writes(b"jpg"));
+        let recovered = RecoveredRecording {
+            path: PathBuf::from("/rec/rec_x.mkv"),
+            parts: vec![PathBuf::from("/rec/rec_x.mkv")],
+        };
+
+        let recordings = index_recovered(&runner, &library, &recovered, tmp.path())
+            .await
+            .unwrap();
+        let stored = library.get(&recordings[0].id).unwrap().unwrap();
+        assert!(stored.recovered);
+        assert!(library.get_stats(&stored.id).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_thumbnail_still_indexes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let library = Library::open(&tmp.path().join("lib.db")).unwrap();
+        let runner = FakeRunner::new()
+            .on(
+                Tool::Ffprobe,
+                "clip.mkv",
+                FakeResponse::ok().stdout(PROBE_JSON),
+            )
+            .on(
+                Tool::Ffmpeg,
+                "scale=320",
+                FakeResponse::failing(1, "too short"),
+            );
+
+        let recording = index_file(
+            &runner,
+            &library,
+            Path::new("/rec/clip.mkv"),
+            tmp.path(),
+            SourceType::Recording,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(recording.source_type, "recording");
+        assert!(recording.thumbnail_path.is_none());
+        assert!(library.get(&recording.id).unwrap().is_some());
+    }
+}
@@ -1,3 +1,5 @@
 pub mod db;
+pub mod index;
 
 pub use db::Library;
+pub use index::{index_file, index_parts, index_recovered, SourceType};
@@ -1,8 +1,9 @@
 use crate::error::{Error, Result};
-use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::{Child, Command};
-use tokio::sync::watch;
+use crate::runner::{FfmpegRunner, Tool};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{oneshot, watch};
 use tracing::{debug, info, warn};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
@@ -14,115 +15,317 @@ pub enum ProcessState {
     Failed,
 }
 
+/// Global args prepended to every spawned FFmpeg so progress arrives as
+/// key=value blocks on stdout instead of being scraped from stderr.
+/// Outputs are always files, so stdout is otherwise unused.
+const PROGRESS_ARGS: [&str; 3] = ["-progress", "pipe:1", "-nostats"];
+
 #[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct FfmpegProgress {
     pub frame: u64,
     pub fps: f64,
+    /// Output position as reported (`HH:MM:SS.micro`)
     pub time: String,
+    /// Encode speed as reported (e.g. `1.02x`)
     pub speed: String,
     pub size_kb: u64,
+    pub out_time_us: u64,
+    pub bitrate_kbps: f64,
+    pub total_size: u64,
+    pub dup_frames: u64,
+    pub drop_frames: u64,
+    pub speed_x: f64,
+    /// Set on the final report (`progress=end`)
+    pub finished: bool,
+}
+
+impl FfmpegProgress {
+    pub fn out_time_secs(&self) -> f64 {
+        self.out_time_us as f64 / 1_000_000.0
+    }
+}
+
+/// Accumulates `-progress` key=value lines into complete reports
+#[derive(Debug, Default)]
+struct ProgressParser {
+    current: FfmpegProgress,
+}
+
+impl ProgressParser {
+    /// Feed one line; returns a report when a block ends with `progress=...`
+    fn feed(&mut self, line: &str) -> Option<FfmpegProgress> {
+        let (key, value) = line.trim().split_once('=')?;
+        let value = value.trim();
+        let p = &mut self.current;
+
+        match key {
+            "frame" => p.frame = value.parse().unwrap_or(0),
+            "fps" => p.fps = value.parse().unwrap_or(0.0),
+            "bitrate" => p.bitrate_kbps = value.trim_end_matches("kbits/s").parse().unwrap_or(0.0),
+            "total_size" => {
+                p.total_size = value.parse().unwrap_or(0);
+                p.size_kb = p.total_size / 1024;
+            }
+            "out_time_us" => p.out_time_us = value.parse().unwrap_or(0),
+            "out_time" => p.time = value.to_string(),
+            "dup_frames" => p.dup_frames = value.parse().unwrap_or(0),
+            "drop_frames" => p.drop_frames = value.parse().unwrap_or(0),
+            "speed" => {
+                p.speed = value.to_string();
+                p.speed_x = value.trim_end_matches('x').parse().unwrap_or(0.0);
+            }
+            "progress" => {
+                p.finished = value == "end";
+                return Some(std::mem::take(&mut self.current));
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Number of trailing stderr lines kept for failure classification
+const STDERR_TAIL_LINES: usize = 64;
+
+/// Why an FFmpeg process exited unexpectedly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum FailureReason {
+    /// Capture device or GPU encoder already in use
+    DeviceBusy,
+    /// Encoder or hardware context failed to initialize
+    EncoderInit,
+    /// Output filesystem ran out of space
+    DiskFull,
+    /// Input file, device or display does not exist
+    MissingInput,
+    Unknown,
+}
+
+impl FailureReason {
+    /// Classify a failure from the tail of FFmpeg's stderr
+    pub fn classify<S: AsRef<str>>(stderr: &[S]) -> Self {
+        let has = |needles: &[&str]| {
+            stderr
+                .iter()
+                .any(|line| needles.iter().any(|n| line.as_ref().contains(n)))
+        };
+
+        if has(&["No space left on device", "Disk quota exceeded"]) {
+            Self::DiskFull
+        } else if has(&["Device or resource busy", "resource busy"]) {
+            Self::DeviceBusy
+        } else if has(&[
+            "Error while opening encoder",
+            "Error initializing output stream",
+            "Failed to initialise VAAPI",
+            "Cannot load libcuda",
+            "No capable devices found",
+            "OpenEncodeSessionEx failed",
+            "Device creation failed",
+            "Unknown encoder",
+        ]) {
+            Self::EncoderInit
+        } else if has(&[
+            "No such file or directory",
+            "Cannot open display",
+            "No such device",
+        ]) {
+            Self::MissingInput
+        } else {
+            Self::Unknown
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::DeviceBusy => "capture device is busy",
+            Self::EncoderInit => "encoder failed to initialize",
+            Self::DiskFull => "disk is full",
+            Self::MissingInput => "input not found",
+            Self::Unknown => "unknown error",
+        }
+    }
+}
+
+impl std::fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// How an FFmpeg process ended
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProcessExit {
+    /// Exit code, `None` if terminated by a signal
+    pub code: Option<i32>,
+    /// Set when the process exited on its own with an error
+    pub failure: Option<FailureReason>,
+    /// Last stderr lines before exit
+    pub stderr_tail: Vec<String>,
+}
+
+impl ProcessExit {
+    /// Error describing the failure, if any
+    pub fn error(&self) -> Option<Error> {
+        let reason = self.failure?;
+        let last = self.stderr_tail.last().map(String::as_str).unwrap_or("");
+        Some(Error::FfmpegFailed(format!("{reason}: {last}")))
+    }
 }
 
 pub struct FfmpegProcess {
-    child: Child,
+    pid: Option<u32>,
+    stdin: Option<Box<dyn AsyncWrite + Send + Sync + Unpin>>,
+    kill_tx: Option<oneshot::Sender<()>>,
     state_tx: watch::Sender<ProcessState>,
     state_rx: watch::Receiver<ProcessState>,
-    #[allow(dead_code)]
-    progress_tx: watch::Sender<FfmpegProgress>,
+    exit_rx: watch::Receiver<Option<ProcessExit>>,
     progress_rx: watch::Receiver<FfmpegProgress>,
+    stalled_tx: watch::Sender<bool>,
 }
 
 impl FfmpegProcess {
-    pub async fn spawn(args: Vec<String>) -> Result<Self> {
+    pub async fn spawn(runner: &dyn FfmpegRunner, args: Vec<String>) -> Result<Self> {
         info!(args = ?args, "spawning ffmpeg");
 
-        let mut child = Command::new("ffmpeg")
-            .args(&args)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    Error::FfmpegNotFound
-                } else {
-                    Error::Io(e)
-                }
-            })?;
+        let full_args = PROGRESS_ARGS
+            .iter()
+            .map(|a| a.to_string())
+            .chain(args)
+            .collect();
+
+        let (kill_tx, kill_rx) = oneshot::channel();
+        let child = runner.spawn(Tool::Ffmpeg, full_args, kill_rx)?;
 
         let (state_tx, state_rx) = watch::channel(ProcessState::Starting);
         let (progress_tx, progress_rx) = watch::channel(FfmpegProgress::default());
+        let (exit_tx, exit_rx) = watch::channel(None);
 
-        // Spawn stderr reader for progress parsing
-        let stderr = child.stderr.take().expect("stderr was piped");
-        let progress_tx_clone = progress_tx.clone();
+        // Spawn stdout reader for -progress reports
+        let stdout = child.stdout;
         let state_tx_clone = state_tx.clone();
 
         tokio::spawn(async move {
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
+            let mut lines = BufReader::new(stdout).lines();
+            let mut parser = ProgressParser::default();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Some(progress) = parser.feed(&line) {
+                    mark_running(&state_tx_clone);
+                    let _ = progress_tx.send(progress);
+                }
+            }
+        });
+
+        // Spawn stderr reader for logs; keeps the tail for classification
+        let stderr = child.stderr;
+        let state_tx_clone = state_tx.clone();
+
+        let stderr_task = tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
             let mut saw_output = false;
 
             while let Ok(Some(line)) = lines.next_line().await {
                 debug!(line = %line, "ffmpeg stderr");
 
-                if !saw_output && (line.contains("Output #0") || line.contains("frame=")) {
+                if !saw_output && line.contains("Output #0") {
                     saw_output = true;
-                    let _ = state_tx_clone.send(ProcessState::Running);
+                    mark_running(&state_tx_clone);
                 }
 
-                if let Some(progress) = parse_progress(&line) {
-                    let _ = progress_tx_clone.send(progress);
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+            tail
+        });
+
+        // Background waiter records how the process ended
+        let exit = child.exit;
+        let state_tx_clone = state_tx.clone();
+
+        tokio::spawn(async move {
+            let code = match exit.await {
+                Ok(code) => code,
+                Err(e) => {
+                    warn!(error = %e, "failed to wait for ffmpeg");
+                    None
                 }
+            };
+
+            // Pipes can outlive the child if it forked; don't wait forever
+            let tail: Vec<String> =
+                match tokio::time::timeout(Duration::from_secs(2), stderr_task).await {
+                    Ok(Ok(tail)) => tail.into(),
+                    _ => Vec::new(),
+                };
+
+            let requested = *state_tx_clone.borrow() == ProcessState::Stopping;
+            let success = code == Some(0);
+
+            let failure = if requested || success {
+                None
+            } else {
+                Some(FailureReason::classify(&tail))
+            };
 
-                if line.contains("Exiting normally") {
-                    debug!("ffmpeg exiting normally");
+            match failure {
+                Some(reason) => {
+                    warn!(code = ?code, reason = %reason, "ffmpeg exited unexpectedly");
+                    let _ = state_tx_clone.send(ProcessState::Failed);
+                }
+                None => {
+                    info!(code = ?code, "ffmpeg stopped");
+                    let _ = state_tx_clone.send(ProcessState::Stopped);
                 }
             }
-        });
 
-        // Mark as starting (will transition to Running once output is detected)
-        let _ = state_tx.send(ProcessState::Starting);
+            let _ = exit_tx.send(Some(ProcessExit {
+                code,
+                failure,
+                stderr_tail: tail,
+            }));
+        });
 
         Ok(Self {
-            child,
+            pid: child.pid,
+            stdin: Some(child.stdin),
+            kill_tx: Some(kill_tx),
             state_tx,
             state_rx,
-            progress_tx,
+            exit_rx,
             progress_rx,
+            stalled_tx: watch::Sender::new(false),
         })
     }
 
     /// Sends 'q' to stdin for graceful FFmpeg shutdown
     pub async fn stop_graceful(&mut self) -> Result<()> {
+        self.stop_within(Duration::from_secs(10)).await
+    }
+
+    /// Graceful stop that force kills if FFmpeg hasn't exited after `timeout`
+    pub async fn stop_within(&mut self, timeout: Duration) -> Result<()> {
+        if self.exit().is_some() {
+            return Ok(());
+        }
         let _ = self.state_tx.send(ProcessState::Stopping);
 
-        if let Some(stdin) = self.child.stdin.as_mut() {
+        if let Some(stdin) = self.stdin.as_mut() {
             if let Err(e) = stdin.write_all(b"q").await {
                 warn!(error = %e, "failed to write 'q' to ffmpeg stdin, force killing");
                 return self.kill().await;
             }
         }
 
-        // Wait for process to exit with timeout
-        match tokio::time::timeout(std::time::Duration::from_secs(10), self.child.wait()).await {
-            Ok(Ok(status)) => {
-                let new_state = if status.success() {
-                    ProcessState::Stopped
-                } else {
-                    // FFmpeg often exits with code 255 on 'q' which is fine
-                    ProcessState::Stopped
-                };
-                let _ = self.state_tx.send(new_state);
-                info!(status = ?status, "ffmpeg stopped");
-                Ok(())
-            }
-            Ok(Err(e)) => {
-                let _ = self.state_tx.send(ProcessState::Failed);
-                Err(Error::Io(e))
-            }
+        // FFmpeg often exits with code 255 on 'q'; the waiter treats any
+        // exit after Stopping as a clean stop.
+        match tokio::time::timeout(timeout, self.wait()).await {
+            Ok(_) => Ok(()),
             Err(_) => {
-                warn!("ffmpeg didn't exit within 10s, force killing");
+                warn!(?timeout, "ffmpeg didn't exit in time, force killing");
                 self.kill().await
             }
         }
@@ -130,14 +333,42 @@ impl FfmpegProcess {
 
     /// Force kill the FFmpeg process
     pub async fn kill(&mut self) -> Result<()> {
+        if self.exit().is_some() {
+            return Ok(());
+        }
         let _ = self.state_tx.send(ProcessState::Stopping);
-        self.child.kill().await.map_err(Error::Io)?;
-        let _ = self.child.wait().await;
-        let _ = self.state_tx.send(ProcessState::Stopped);
+        if let Some(kill_tx) = self.kill_tx.take() {
+            let _ = kill_tx.send(());
+        }
+        self.wait().await;
         info!("ffmpeg force killed");
         Ok(())
     }
 
+    /// Wait until the process exits, however it ends
+    pub async fn wait(&self) -> ProcessExit {
+        let mut exit_rx = self.exit_rx.clone();
+        let exit = match exit_rx.wait_for(Option::is_some).await {
+            Ok(exit) => exit.clone(),
+            Err(_) => None,
+        };
+        exit.unwrap_or(ProcessExit {
+            code: None,
+            failure: Some(FailureReason::Unknown),
+            stderr_tail: Vec::new(),
+        })
+    }
+
+    /// Exit details, once the process has ended
+    pub fn exit(&self) -> Option<ProcessExit> {
+        self.exit_rx.borrow().clone()
+    }
+
+    /// Why the process failed, if it exited on its own with an error
+    pub fn failure(&self) -> Option<FailureReason> {
+        self.exit_rx.borrow().as_ref().and_then(|e| e.failure)
+    }
+
     pub fn state(&self) -> ProcessState {
         *self.state_rx.borrow()
     }
@@ -155,84 +386,88 @@ impl FfmpegProcess {
     }
 
     pub fn pid(&self) -> Option<u32> {
-        self.child.id()
+        self.pid
     }
-}
 
-fn parse_progress(line: &str) -> Option<FfmpegProgress> {
-    // FFmpeg progress lines look like:
-    // frame=  123 fps= 60 q=20.0 size=    1234kB time=00:00:02.05 speed=1.00x
-    if !line.contains("frame=") || !line.contains("time=") {
-        return None;
-    }
-
-    let mut progress = FfmpegProgress::default();
-
-    for part in line.split_whitespace() {
-        if let Some(val) = part.strip_prefix("frame=") {
-            progress.frame = val.parse().unwrap_or(0);
-        } else if let Some(val) = part.strip_prefix("fps=") {
-            progress.fps = val.parse().unwrap_or(0.0);
-        } else if let Some(val) = part.strip_prefix("time=") {
-            progress.time = val.to_string();
-        } else if let Some(val) = part.strip_prefix("speed=") {
-            progress.speed = val.to_string();
-        } else if let Some(val) = part.strip_prefix("size=") {
-            let val = val.trim_end_matches("kB").trim_end_matches("KiB");
-            progress.size_kb = val.trim().parse().unwrap_or(0);
-        }
-    }
+    /// Flag the process as stalled when neither `frame` nor `out_time`
+    /// advance for `timeout`. The flag clears if progress resumes.
+    ///
+    /// Timing starts at the first progress report, so a slow encoder setup
+    /// (NVENC/VAAPI initialization) is not taken for a stall.
+    pub fn watch_stalls(&self, timeout: Duration) {
+        let mut progress_rx = self.progress_rx.clone();
+        let mut exit_rx = self.exit_rx.clone();
+        let state_rx = self.state_rx.clone();
+        let stalled_tx = self.stalled_tx.clone();
+        let check_every = (timeout / 4).clamp(Duration::from_millis(50), Duration::from_secs(1));
 
-    // Also handle "key=  value" with separate whitespace
-    if progress.frame == 0 {
-        if let Some(idx) = line.find("frame=") {
-            let rest = &line[idx + 6..];
-            let val: String = rest
-                .chars()
-                .take_while(|c| c.is_ascii_digit() || c.is_whitespace())
-                .collect();
-            progress.frame = val.trim().parse().unwrap_or(0);
-        }
+        tokio::spawn(async move {
+            tokio::select! {
+                changed = progress_rx.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+                _ = exit_rx.wait_for(|e| e.is_some()) => return,
+            }
+
+            let mut interval = tokio::time::interval(check_every);
+            let mut last = {
+                let p = progress_rx.borrow();
+                (p.frame, p.out_time_us)
+            };
+            let mut last_advance = Instant::now();
+
+            loop {
+                interval.tick().await;
+                if exit_rx.borrow().is_some() || *state_rx.borrow() == ProcessState::Stopping {
+                    break;
+                }
+
+                let current = {
+                    let p = progress_rx.borrow();
+                    (p.frame, p.out_time_us)
+                };
+                if current != last {
+                    last = current;
+                    last_advance = Instant::now();
+                    stalled_tx.send_if_modified(|s| std::mem::replace(s, false));
+                } else if last_advance.elapsed() >= timeout
+                    && stalled_tx.send_if_modified(|s| !std::mem::replace(s, true))
+                {
+                    warn!(frame = current.0, ?timeout, "ffmpeg progress stalled");
+                }
+            }
+        });
     }
 
-    if progress.time.is_empty() {
-        if let Some(idx) = line.find("time=") {
-            let rest = &line[idx + 5..];
-            let val: String = rest.chars().take_while(|c| *c != ' ').collect();
-            progress.time = val.trim().to_string();
-        }
+    /// Whether the stall watchdog currently considers the process frozen
+    pub fn is_stalled(&self) -> bool {
+        *self.stalled_tx.borrow()
     }
 
-    if progress.speed.is_empty() {
-        if let Some(idx) = line.find("speed=") {
-            let rest = &line[idx + 6..];
-            let val: String = rest.chars().take_while(|c| !c.is_whitespace()).collect();
-            progress.speed = val.trim().to_string();
-        }
+    pub fn subscribe_stalled(&self) -> watch::Receiver<bool> {
+        self.stalled_tx.subscribe()
     }
+}
 
-    Some(progress)
+/// Move Starting -> Running once FFmpeg produces output
+fn mark_running(state_tx: &watch::Sender<ProcessState>) {
+    state_tx.send_if_modified(|state| {
+        let starting = *state == ProcessState::Starting;
+        if starting {
+            *state = ProcessState::Running;
+        }
+        starting
+    });
 }
 
 /// Run FFmpeg with args and wait for completion. Returns stderr output.
-pub async fn run_ffmpeg(args: &[&str]) -> Result<String> {
-    let output = Command::new("ffmpeg")
-        .args(args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                Error::FfmpegNotFound
-            } else {
-                Error::Io(e)
-            }
-        })?;
+pub async fn run_ffmpeg(runner: &dyn FfmpegRunner, args: &[&str]) -> Result<String> {
+    let output = runner.run(Tool::Ffmpeg, to_owned_args(args)).await?;
 
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-
-    if output.status.success() {
+    if output.success() {
         Ok(stderr)
     } else {
         Err(Error::FfmpegFailed(stderr))
@@ -240,16 +475,10 @@ pub async fn run_ffmpeg(args: &[&str]) -> Result<String> {
 }
 
 /// Run ffprobe and return stdout
-pub async fn run_ffprobe(args: &[&str]) -> Result<String> {
-    let output = Command::new("ffprobe")
-        .args(args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(Error::Io)?;
-
-    if output.status.success() {
+pub async fn run_ffprobe(runner: &dyn FfmpegRunner, args: &[&str]) -> Result<String> {
+    let output = runner.run(Tool::Ffprobe, to_owned_args(args)).await?;
+
+    if output.success() {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -257,32 +486,207 @@ pub async fn run_ffprobe(args: &[&str]) -> Result<String> {
     }
 }
 
+pub(crate) fn to_owned_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner::SystemRunner;
+
+    fn feed_all(parser: &mut ProgressParser, block: &str) -> Vec<FfmpegProgress> {
+        block.lines().filter_map(|l| parser.feed(l)).collect()
+    }
 
     #[test]
-    fn parse_progress_standard_line() {
-        let line = "frame=  123 fps= 60.0 q=20.0 size=    1234kB time=00:00:02.05 speed=1.00x";
-        let progress = parse_progress(line).expect("should parse");
-        assert_eq!(progress.frame, 123);
-        assert_eq!(progress.time, "00:00:02.05");
-        assert_eq!(progress.speed, "1.00x");
+    fn progress_block_parses_typed_fields() {
+        let block = "frame=123\nfps=60.00\nstream_0_0_q=20.0\nbitrate=1234.5kbits/s\n\
+total_size=2097152\nout_time_us=2050000\nout_time_ms=2050000\n\
+out_time=00:00:02.050000\ndup_frames=2\ndrop_frames=5\nspeed=1.02x\nprogress=continue\n";
+        let mut parser = ProgressParser::default();
+        let reports = feed_all(&mut parser, block);
+        assert_eq!(reports.len(), 1);
+
+        let p = &reports[0];
+        assert_eq!(p.frame, 123);
+        assert!((p.fps - 60.0).abs() < 0.01);
+        assert!((p.bitrate_kbps - 1234.5).abs() < 0.01);
+        assert_eq!(p.total_size, 2_097_152);
+        assert_eq!(p.size_kb, 2048);
+        assert_eq!(p.out_time_us, 2_050_000);
+        assert!((p.out_time_secs() - 2.05).abs() < 0.001);
+        assert_eq!(p.time, "00:00:02.050000");
+        assert_eq!(p.dup_frames, 2);
+        assert_eq!(p.drop_frames, 5);
+        assert_eq!(p.speed, "1.02x");
+        assert!((p.speed_x - 1.02).abs() < 0.001);
+        assert!(!p.finished);
     }
 
     #[test]
-    fn parse_progress_non_progress_line_returns_none() {
-        assert!(parse_progress("Input #0, matroska,webm").is_none());
-        assert!(parse_progress("Stream #0:0: Video").is_none());
-        assert!(parse_progress("").is_none());
+    fn progress_na_values_default_to_zero() {
+        let block =
+            "frame=0\nbitrate=N/A\ntotal_size=N/A\nout_time_us=N/A\nspeed=N/A\nprogress=continue\n";
+        let mut parser = ProgressParser::default();
+        let p = feed_all(&mut parser, block).remove(0);
+        assert_eq!(p.bitrate_kbps, 0.0);
+        assert_eq!(p.total_size, 0);
+        assert_eq!(p.out_time_us, 0);
+        assert_eq!(p.speed_x, 0.0);
+    }
+
+    #[test]
+    fn progress_blocks_are_independent_and_end_is_flagged() {
+        let block = "frame=10\ndrop_frames=3\nprogress=continue\nframe=20\nprogress=end\n";
+        let mut parser = ProgressParser::default();
+        let reports = feed_all(&mut parser, block);
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].frame, 20);
+        assert_eq!(reports[1].drop_frames, 0);
+        assert!(reports[1].finished);
+    }
+
+    #[test]
+    fn non_progress_lines_are_ignored() {
+        let mut parser = ProgressParser::default();
+        assert!(parser.feed("Input #0, matroska,webm").is_none());
+        assert!(parser.feed("").is_none());
+        assert!(parser.feed("frame=1").is_none());
+    }
+
+    /// Spawn a real process running `script` in place of ffmpeg
+    async fn sh(script: &str) -> (tempfile::TempDir, FfmpegProcess) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ffmpeg");
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let runner = SystemRunner {
+            ffmpeg_path: path,
+            ..SystemRunner::default()
+        };
+        let process = FfmpegProcess::spawn(&runner, Vec::new()).await.unwrap();
+        (dir, process)
     }
 
     #[test]
-    fn parse_progress_compact_format() {
-        let line = "frame=500 fps=60 q=20.0 size=5000kB time=00:00:08.33 speed=1.02x";
-        let progress = parse_progress(line).expect("should parse");
-        assert_eq!(progress.frame, 500);
-        assert!((progress.fps - 60.0).abs() < 0.1);
-        assert_eq!(progress.time, "00:00:08.33");
+    fn classify_known_failures() {
+        let cases = [
+            (
+                "/rec/out.mkv: No space left on device",
+                FailureReason::DiskFull,
+            ),
+            (
+                "[x11grab] Cannot open video device: Device or resource busy",
+                FailureReason::DeviceBusy,
+            ),
+            (
+                "[h264_nvenc] OpenEncodeSessionEx failed: out of memory (10)",
+                FailureReason::EncoderInit,
+            ),
+            (
+                "Error while opening encoder for output stream #0:0",
+                FailureReason::EncoderInit,
+            ),
+            (
+                "in.mkv: No such file or directory",
+                FailureReason::MissingInput,
+            ),
+            ("Conversion failed!", FailureReason::Unknown),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(FailureReason::classify(&[line]), expected, "{line}");
+        }
+    }
+
+    #[tokio::test]
+    async fn crash_sets_failed_with_reason() {
+        let (_dir, process) =
+            sh("echo 'frame=1' >&2; echo 'out.mkv: No space left on device' >&2; exit 1").await;
+        let exit = process.wait().await;
+
+        assert_eq!(exit.code, Some(1));
+        assert_eq!(exit.failure, Some(FailureReason::DiskFull));
+        assert_eq!(exit.stderr_tail.len(), 2);
+        assert!(exit.error().is_some());
+        assert_eq!(process.state(), ProcessState::Failed);
+        assert_eq!(process.failure(), Some(FailureReason::DiskFull));
+    }
+
+    #[tokio::test]
+    async fn clean_exit_sets_stopped() {
+        let (_dir, process) = sh("exit 0").await;
+        let exit = process.wait().await;
+        assert_eq!(exit.failure, None);
+        assert_eq!(process.state(), ProcessState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn requested_stop_is_not_a_failure() {
+        // Mimics FFmpeg exiting non-zero after receiving 'q'
+        let (_dir, mut process) = sh("head -c 1 >/dev/null; exit 255").await;
+        process.stop_graceful().await.unwrap();
+        assert_eq!(process.state(), ProcessState::Stopped);
+        assert_eq!(process.failure(), None);
+    }
+
+    #[tokio::test]
+    async fn kill_stops_hung_process() {
+        let (_dir, mut process) = sh("exec sleep 30").await;
+        process.kill().await.unwrap();
+        assert_eq!(process.state(), ProcessState::Stopped);
+        assert_eq!(process.exit().unwrap().failure, None);
+    }
+
+    #[tokio::test]
+    async fn stderr_tail_is_bounded() {
+        let (_dir, process) =
+            sh("i=0; while [ $i -lt 100 ]; do echo line$i >&2; i=$((i+1)); done; exit 1").await;
+        let exit = process.wait().await;
+        assert_eq!(exit.stderr_tail.len(), STDERR_TAIL_LINES);
+        assert_eq!(exit.stderr_tail.last().unwrap(), "line99");
+    }
+
+    #[tokio::test]
+    async fn watchdog_flags_stalled_process() {
+        // One progress report, then nothing while staying alive
+        let (_dir, mut process) =
+            sh("printf 'frame=5\\nout_time_us=1000\\nprogress=continue\\n'; exec sleep 30").await;
+        process.watch_stalls(Duration::from_millis(200));
+
+        let mut stalled = process.subscribe_stalled();
+        tokio::time::timeout(Duration::from_secs(5), stalled.wait_for(|s| *s))
+            .await
+            .expect("watchdog should fire")
+            .unwrap();
+        assert_eq!(process.progress().frame, 5);
+        assert_eq!(process.state(), ProcessState::Running);
+
+        process.kill().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn watchdog_waits_for_the_first_report() {
+        // Encoder setup takes longer than the timeout before any progress
+        let (_dir, mut process) = sh(
+            "sleep 0.6; printf 'frame=1\\nout_time_us=1000\\nprogress=continue\\n'; exec sleep 30",
+        )
+        .await;
+        process.watch_stalls(Duration::from_millis(200));
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!process.is_stalled());
+
+        let mut stalled = process.subscribe_stalled();
+        tokio::time::timeout(Duration::from_secs(5), stalled.wait_for(|s| *s))
+            .await
+            .expect("watchdog should fire after the first report")
+            .unwrap();
+        assert_eq!(process.progress().frame, 1);
+
+        process.kill().await.unwrap();
     }
 }
@@ -0,0 +1,223 @@
+//! Journal of recordings in progress.
+//!
+//! A session writes an entry when capture starts and again for every new
+//! part, and removes it once the recording is finalized. Next to each entry
+//! is a lock file the session holds while it runs; the OS releases it when
+//! the process dies, so an entry whose lock can be taken marks files that
+//! were never finalized, because the app or the machine went down
+//! mid-recording. `recovery` repairs them on the next start.
+
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, TryLockError};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// One unfinished recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub started_at: String,
+    /// Where the finished recording goes
+    pub output: PathBuf,
+    /// Files written so far, in order
+    pub parts: Vec<PathBuf>,
+    /// Parts are files of their own rather than pieces to join
+    pub split: bool,
+}
+
+/// The journal entry of a running session, locked until it is dropped
+pub struct Journal {
+    path: PathBuf,
+    /// Held for the session's lifetime; marks the entry as live
+    _lock: File,
+    entry: JournalEntry,
+}
+
+impl Journal {
+    /// Record a session writing `first` on its way to `output`. Paths are
+    /// stored absolute, as recovery may run from another directory.
+    pub fn create(dir: &Path, output: &Path, first: &Path, split: bool) -> Result<Self> {
+        std::fs::create_dir_all(dir).map_err(Error::Io)?;
+        let path = dir.join(format!("{}.json", uuid::Uuid::new_v4()));
+        // Lock before the entry exists, so it is never seen unlocked
+        let lock = open_lock(&path)?;
+        lock.lock().map_err(Error::Io)?;
+        let journal = Self {
+            path,
+            _lock: lock,
+            entry: JournalEntry {
+                started_at: chrono::Local::now().to_rfc3339(),
+                output: std::path::absolute(output)?,
+                parts: vec![std::path::absolute(first)?],
+                split,
+            },
+        };
+        journal.write()?;
+        Ok(journal)
+    }
+
+    pub fn entry(&self) -> &JournalEntry {
+        &self.entry
+    }
+
+    /// Note a new part the session started writing
+    pub fn add_part(&mut self, part: &Path) -> Result<()> {
+        self.entry.parts.push(std::path::absolute(part)?);
+        self.write()
+    }
+
+    /// The recording was finalized; nothing is left to recover
+    pub fn remove(self) -> Result<()> {
+        remove_entry(&self.path)
+    }
+
+    /// Replace the entry in one step so a crash never leaves half of it
+    fn write(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.entry)?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(Error::Io)?;
+        std::fs::rename(&tmp, &self.path).map_err(Error::Io)
+    }
+}
+
+/// An entry left behind by a process that is no longer running. Its lock
+/// is held until the orphan is dropped, so no one else recovers it too.
+#[derive(Debug)]
+pub struct Orphan {
+    /// The entry's file
+    pub path: PathBuf,
+    pub entry: JournalEntry,
+    _lock: File,
+}
+
+impl Orphan {
+    /// The recording was handled; drop the entry and its lock file
+    pub fn remove(self) -> Result<()> {
+        remove_entry(&self.path)
+    }
+}
+
+/// Entries in `dir` whose lock is free, oldest first. Unreadable entries
+/// are skipped with a warning.
+pub fn orphans(dir: &Path) -> Result<Vec<Orphan>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut orphans = Vec::new();
+    for dirent in std::fs::read_dir(dir).map_err(Error::Io)? {
+        let path = dirent.map_err(Error::Io)?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let lock = match open_lock(&path) {
+            Ok(lock) => lock,
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "could not open journal lock");
+                continue;
+            }
+        };
+        match lock.try_lock() {
+            Ok(()) => {}
+            // Still recording
+            Err(TryLockError::WouldBlock) => continue,
+            Err(TryLockError::Error(e)) => {
+                warn!(path = %path.display(), error = %e, "could not lock journal entry");
+                continue;
+            }
+        }
+        // Read only once locked: the session may have finished in between
+        if !path.exists() {
+            let _ = std::fs::remove_file(path.with_extension("lock"));
+            continue;
+        }
+        match read_entry(&path) {
+            Ok(entry) => orphans.push(Orphan {
+                path,
+                entry,
+                _lock: lock,
+            }),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "skipping unreadable journal entry");
+                let _ = std::fs::remove_file(path.with_extension("lock"));
+            }
+        }
+    }
+    orphans.sort_by(|a, b| a.entry.started_at.cmp(&b.entry.started_at));
+    Ok(orphans)
+}
+
+fn read_entry(path: &Path) -> Result<JournalEntry> {
+    let json = std::fs::read_to_string(path).map_err(Error::Io)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// The lock file beside the entry at `path`, created if missing
+fn open_lock(path: &Path) -> Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_extension("lock"))
+        .map_err(Error::Io)
+}
+
+/// Remove the entry, then its lock file, while the lock is still held
+fn remove_entry(path: &Path) -> Result<()> {
+    std::fs::remove_file(path).map_err(Error::Io)?;
+    std::fs::remove_file(path.with_extension("lock")).map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_tracks_parts_until_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join(".journal");
+        let output = dir.path().join("recording_x.mkv");
+
+        let mut journal = Journal::create(&journal_dir, &output, &output, false).unwrap();
+        journal
+            .add_part(&dir.path().join("recording_x_part2.mkv"))
+            .unwrap();
+        let written = read_entry(&journal.path).unwrap();
+        assert_eq!(&written, journal.entry());
+        assert_eq!(written.parts.len(), 2);
+
+        journal.remove().unwrap();
+        assert_eq!(std::fs::read_dir(&journal_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn only_unlocked_entries_are_orphans() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("recording_x.mkv");
+
+        // Still recording
+        let live = Journal::create(dir.path(), &output, &output, false).unwrap();
+        // Left by a session that went down: its lock was released
+        let dead = Journal::create(dir.path(), &output, &output, true).unwrap();
+        let dead_entry = dead.entry().clone();
+        let dead_path = dead.path.clone();
+        drop(dead);
+        std::fs::write(dir.path().join("garbage.json"), "{").unwrap();
+
+        let found = orphans(dir.path()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, dead_path);
+        assert_eq!(found[0].entry, dead_entry);
+
+        // Taken while being recovered, then gone for good
+        assert!(orphans(dir.path()).unwrap().is_empty());
+        found.into_iter().next().unwrap().remove().unwrap();
+        live.remove().unwrap();
+        assert!(orphans(dir.path()).unwrap().is_empty());
+        let left: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|d| d.unwrap().file_name())
+            .collect();
+        assert_eq!(left, ["garbage.json"]);
+    }
+}
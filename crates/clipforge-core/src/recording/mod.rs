@@ -0,0 +1,8 @@
+pub mod journal;
+pub mod perf;
+pub mod recovery;
+pub mod session;
+
+pub use perf::{DropWarning, PerformanceSummary, PerformanceTracker};
+pub use recovery::{recover_orphans, RecoveredRecording};
+pub use session::{FinishedRecording, RecordingSession, SessionOptions};
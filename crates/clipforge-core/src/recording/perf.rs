@@ -0,0 +1,280 @@
+use crate::process::FfmpegProgress;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Sliding window used for live drop warnings
+const WARNING_WINDOW: Duration = Duration::from_secs(5);
+
+/// Capture performance over a whole recording
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceSummary {
+    pub avg_fps: f64,
+    /// Mean fps of the slowest 1% of progress intervals
+    pub low_1pct_fps: f64,
+    pub total_drops: u64,
+    pub total_dups: u64,
+    pub avg_speed: f64,
+    /// Number of progress intervals the summary is based on
+    pub samples: u64,
+}
+
+/// Raised when too many frames were dropped within the warning window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DropWarning {
+    pub dropped: u64,
+    pub frames: u64,
+    pub percent: f64,
+}
+
+/// Counters from one progress report, relative to the start of capture
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Duration,
+    frames: u64,
+    drops: u64,
+}
+
+/// Accumulates FFmpeg progress reports into a `PerformanceSummary`.
+///
+/// Counters restart at zero when capture restarts into a new part; the
+/// session calls `start_part` then, and the tracker folds the finished part
+/// into running totals.
+#[derive(Debug)]
+pub struct PerformanceTracker {
+    warn_percent: f64,
+    /// Totals from finished parts
+    base_frames: u64,
+    base_drops: u64,
+    base_dups: u64,
+    /// Last raw report of the current part
+    last: Option<(Duration, FfmpegProgress)>,
+    /// Index of the current part
+    part: usize,
+    /// No report of the current part yet, so there is no interval to measure
+    part_started: bool,
+    interval_fps: Vec<f64>,
+    speed_sum: f64,
+    speed_samples: u64,
+    window: VecDeque<Sample>,
+    warning_active: bool,
+}
+
+impl PerformanceTracker {
+    /// Warn when more than `warn_percent` of frames drop within a few seconds
+    pub fn new(warn_percent: f64) -> Self {
+        Self {
+            warn_percent,
+            base_frames: 0,
+            base_drops: 0,
+            base_dups: 0,
+            last: None,
+            part: 0,
+            part_started: false,
+            interval_fps: Vec::new(),
+            speed_sum: 0.0,
+            speed_samples: 0,
+            window: VecDeque::new(),
+            warning_active: false,
+        }
+    }
+
+    /// Capture continues in a new part whose counters start at zero: fold
+    /// the current part into the totals
+    pub fn start_part(&mut self) {
+        if let Some((at, last)) = self.last.take() {
+            self.base_frames += last.frame;
+            self.base_drops += last.drop_frames;
+            self.base_dups += last.dup_frames;
+            self.last = Some((at, FfmpegProgress::default()));
+        }
+        self.part += 1;
+        self.part_started = true;
+    }
+
+    /// Index of the part reports are currently recorded for
+    pub fn part(&self) -> usize {
+        self.part
+    }
+
+    /// Record a progress report received `at` after capture started.
+    ///
+    /// Returns a warning when drops first cross the threshold; it re-arms
+    /// once the drop rate falls back below it.
+    pub fn record(&mut self, progress: &FfmpegProgress, at: Duration) -> Option<DropWarning> {
+        // The gap between parts is not a capture interval
+        if let Some((last_at, last)) = self.last.as_ref().filter(|_| !self.part_started) {
+            let elapsed = at.saturating_sub(*last_at).as_secs_f64();
+            if elapsed > 0.0 {
+                self.interval_fps
+                    .push(progress.frame.saturating_sub(last.frame) as f64 / elapsed);
+            }
+        }
+        self.part_started = false;
+        if progress.speed_x > 0.0 {
+            self.speed_sum += progress.speed_x;
+            self.speed_samples += 1;
+        }
+        self.last = Some((at, progress.clone()));
+
+        self.window.push_back(Sample {
+            at,
+            frames: self.base_frames + progress.frame,
+            drops: self.base_drops + progress.drop_frames,
+        });
+        while self
+            .window
+            .front()
+            .is_some_and(|s| at.saturating_sub(s.at) > WARNING_WINDOW)
+        {
+            self.window.pop_front();
+        }
+        self.check_window()
+    }
+
+    fn check_window(&mut self) -> Option<DropWarning> {
+        let (first, last) = (self.window.front()?, self.window.back()?);
+        let dropped = last.drops.saturating_sub(first.drops);
+        let frames = last.frames.saturating_sub(first.frames) + dropped;
+        if frames == 0 {
+            return None;
+        }
+
+        let percent = dropped as f64 * 100.0 / frames as f64;
+        if percent <= self.warn_percent {
+            self.warning_active = false;
+            return None;
+        }
+        if self.warning_active {
+            return None;
+        }
+        self.warning_active = true;
+        Some(DropWarning {
+            dropped,
+            frames,
+            percent,
+        })
+    }
+
+    pub fn summary(&self) -> PerformanceSummary {
+        let (frames, drops, dups, duration) = match &self.last {
+            Some((at, p)) => (
+                self.base_frames + p.frame,
+                self.base_drops + p.drop_frames,
+                self.base_dups + p.dup_frames,
+                at.as_secs_f64(),
+            ),
+            None => (0, 0, 0, 0.0),
+        };
+
+        let mut sorted = self.interval_fps.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let worst = sorted.len().div_ceil(100);
+        let low_1pct_fps = if worst == 0 {
+            0.0
+        } else {
+            sorted[..worst].iter().sum::<f64>() / worst as f64
+        };
+
+        PerformanceSummary {
+            avg_fps: if duration > 0.0 {
+                frames as f64 / duration
+            } else {
+                0.0
+            },
+            low_1pct_fps,
+            total_drops: drops,
+            total_dups: dups,
+            avg_speed: if self.speed_samples > 0 {
+                self.speed_sum / self.speed_samples as f64
+            } else {
+                0.0
+            },
+            samples: self.interval_fps.len() as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(frame: u64, drops: u64) -> FfmpegProgress {
+        FfmpegProgress {
+            frame,
+            drop_frames: drops,
+            speed_x: 1.0,
+            ..FfmpegProgress::default()
+        }
+    }
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn summary_reports_average_and_worst_interval() {
+        let mut tracker = PerformanceTracker::new(5.0);
+        // 60 fps for 100 intervals, then one interval at 30 fps
+        for i in 0..=100 {
+            tracker.record(&progress(i * 30, 0), secs(i as f64 * 0.5));
+        }
+        tracker.record(&progress(3015, 0), secs(50.5));
+
+        let summary = tracker.summary();
+        assert_eq!(summary.samples, 101);
+        assert!((summary.avg_fps - 3015.0 / 50.5).abs() < 0.01);
+        // 101 intervals -> worst 2 are averaged: (30 + 60) / 2
+        assert!((summary.low_1pct_fps - 45.0).abs() < 0.01);
+        assert!((summary.avg_speed - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn totals_carry_across_parts() {
+        let mut tracker = PerformanceTracker::new(100.0);
+        tracker.record(&progress(600, 3), secs(10.0));
+        // Capture restarted into part 2: counters reset
+        tracker.start_part();
+        tracker.record(&progress(60, 1), secs(12.0));
+        tracker.record(&progress(120, 2), secs(13.0));
+
+        let summary = tracker.summary();
+        assert_eq!(summary.total_drops, 5);
+        assert!((summary.avg_fps - 720.0 / 13.0).abs() < 0.01);
+        // Only the interval within part 2 is measured
+        assert_eq!(summary.samples, 1);
+    }
+
+    #[test]
+    fn new_part_is_folded_even_when_its_counters_pass_the_last() {
+        let mut tracker = PerformanceTracker::new(100.0);
+        tracker.record(&progress(60, 0), secs(1.0));
+        tracker.start_part();
+        // Part 2 has already captured more frames than part 1 ever did
+        tracker.record(&progress(120, 0), secs(3.0));
+
+        let summary = tracker.summary();
+        assert!((summary.avg_fps - 180.0 / 3.0).abs() < 0.01);
+        assert_eq!(summary.samples, 0);
+        assert_eq!(tracker.part(), 1);
+    }
+
+    #[test]
+    fn warning_fires_once_until_drops_recover() {
+        let mut tracker = PerformanceTracker::new(5.0);
+        assert!(tracker.record(&progress(0, 0), secs(0.0)).is_none());
+        assert!(tracker.record(&progress(60, 0), secs(1.0)).is_none());
+
+        // 10 drops against 50 frames in the window: ~14%
+        let warning = tracker.record(&progress(110, 10), secs(2.0)).unwrap();
+        assert_eq!(warning.dropped, 10);
+        assert!(warning.percent > 5.0);
+        assert!(tracker.record(&progress(150, 15), secs(3.0)).is_none());
+
+        // Window slides past the drops, then they start again
+        for s in 4..=9 {
+            tracker.record(&progress(150 + s * 60, 15), secs(s as f64));
+        }
+        assert!(tracker.record(&progress(750, 60), secs(10.0)).is_some());
+    }
+}
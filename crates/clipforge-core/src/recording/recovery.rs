@@ -0,0 +1,285 @@
+//! Repairing recordings cut short by a crash.
+//!
+//! Without a clean stop FFmpeg never writes the trailer, so the file lacks
+//! its index (MP4 without fragments cannot be played at all). Remuxing what
+//! was written rebuilds the index; a stream copy loses nothing. Parts of an
+//! unsplit session are joined like `finish` would have.
+
+use crate::error::{Error, Result};
+use crate::process::FfmpegProcess;
+use crate::recording::journal::{orphans, JournalEntry};
+use crate::recording::session::{join_session, temp_path};
+use crate::runner::FfmpegRunner;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Files salvaged from one unfinished recording
+#[derive(Debug, Clone)]
+pub struct RecoveredRecording {
+    /// The recording, or its first part when split
+    pub path: PathBuf,
+    /// Every file of the recording, in order; just `path` unless split
+    pub parts: Vec<PathBuf>,
+}
+
+/// Repair the recordings of dead sessions journaled in `journal_dir`.
+///
+/// An entry is removed once handled, also when its files cannot be
+/// repaired; they are then left as they are rather than retried on every
+/// start.
+pub async fn recover_orphans(
+    runner: &dyn FfmpegRunner,
+    journal_dir: &Path,
+) -> Result<Vec<RecoveredRecording>> {
+    let mut recovered = Vec::new();
+    for orphan in orphans(journal_dir)? {
+        match recover(runner, &orphan.entry).await {
+            Ok(Some(recording)) => {
+                info!(path = %recording.path.display(), "recovered unfinished recording");
+                recovered.push(recording);
+            }
+            Ok(None) => {}
+            Err(e) => warn!(
+                output = %orphan.entry.output.display(),
+                error = %e,
+                "could not recover unfinished recording"
+            ),
+        }
+        let path = orphan.path.clone();
+        if let Err(e) = orphan.remove() {
+            warn!(path = %path.display(), error = %e, "could not remove journal entry");
+        }
+    }
+    Ok(recovered)
+}
+
+/// Remux the entry's files and join unsplit parts. `None` when no file
+/// with any data survived.
+async fn recover(
+    runner: &dyn FfmpegRunner,
+    entry: &JournalEntry,
+) -> Result<Option<RecoveredRecording>> {
+    let parts: Vec<PathBuf> = entry
+        .parts
+        .iter()
+        .filter(|p| std::fs::metadata(p).is_ok_and(|m| m.len() > 0))
+        .cloned()
+        .collect();
+    if parts.is_empty() {
+        return Ok(None);
+    }
+
+    for part in &parts {
+        remux_in_place(runner, part).await?;
+    }
+
+    if entry.split {
+        return Ok(Some(RecoveredRecording {
+            path: parts[0].clone(),
+            parts,
+        }));
+    }
+    if parts.len() > 1 || parts[0] != entry.output {
+        join_session(runner, &entry.output, &parts).await?;
+    }
+    Ok(Some(RecoveredRecording {
+        path: entry.output.clone(),
+        parts: vec![entry.output.clone()],
+    }))
+}
+
+/// Stream-copy `path` into a fresh file of the same container and replace it
+async fn remux_in_place(runner: &dyn FfmpegRunner, path: &Path) -> Result<()> {
+    let tmp = temp_path(path, "recovering");
+    let args = vec![
+        "-y".to_string(),
+        "-i".to_string(),
+        path.to_string_lossy().to_string(),
+        "-map".to_string(),
+        "0".to_string(),
+        "-c".to_string(),
+        "copy".to_string(),
+        tmp.to_string_lossy().to_string(),
+    ];
+    let exit = FfmpegProcess::spawn(runner, args).await?.wait().await;
+
+    let result = match exit.error() {
+        Some(e) => Err(e),
+        None if exit.code == Some(0) => Ok(()),
+        None => Err(Error::FfmpegExitCode(exit.code.unwrap_or(-1))),
+    };
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+        return result;
+    }
+    std::fs::rename(&tmp, path).map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording::session::{RecordingSession, SessionOptions};
+    use crate::runner::fake::{FakeResponse, FakeRunner};
+    use crate::runner::Tool;
+    use std::sync::Arc;
+
+    /// Remuxes mark the file as repaired; concat joins the listed files
+    fn fake_ffmpeg() -> FakeRunner {
+        FakeRunner::new()
+            .on_with(Tool::Ffmpeg, "concat", |args| {
+                let list = args.iter().skip_while(|a| *a != "-i").nth(1).unwrap();
+                let joined: String = std::fs::read_to_string(list)
+                    .unwrap()
+                    .lines()
+                    .map(|l| l.trim_start_matches("file '").trim_end_matches('\''))
+                    .map(|f| std::fs::read_to_string(f).unwrap())
+                    .collect();
+                FakeResponse::ok().writes(joined)
+            })
+            .on_with(Tool::Ffmpeg, "-map 0", |args| {
+                let input = args.iter().skip_while(|a| *a != "-i").nth(1).unwrap();
+                let input = std::fs::read_to_string(input).unwrap();
+                FakeResponse::ok().writes(format!("fixed {input}"))
+            })
+    }
+
+    fn write_entry(journal_dir: &Path, name: &str, entry: &JournalEntry) {
+        std::fs::create_dir_all(journal_dir).unwrap();
+        std::fs::write(
+            journal_dir.join(format!("{name}.json")),
+            serde_json::to_string(entry).unwrap(),
+        )
+        .unwrap();
+    }
+
+    fn dead_entry(output: &Path, parts: Vec<PathBuf>, split: bool) -> JournalEntry {
+        JournalEntry {
+            started_at: "2025-01-01T00:00:00+00:00".to_string(),
+            output: output.to_path_buf(),
+            parts,
+            split,
+        }
+    }
+
+    #[tokio::test]
+    async fn unsplit_parts_are_remuxed_and_joined() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join(".journal");
+        let output = dir.path().join("recording_x.mkv");
+        let part2 = dir.path().join("recording_x_part2.mkv");
+        std::fs::write(&output, "one\n").unwrap();
+        std::fs::write(&part2, "two\n").unwrap();
+        write_entry(
+            &journal_dir,
+            "a",
+            &dead_entry(&output, vec![output.clone(), part2.clone()], false),
+        );
+
+        let recovered = recover_orphans(&fake_ffmpeg(), &journal_dir).await.unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].path, output);
+        assert_eq!(recovered[0].parts.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "fixed one\nfixed two\n"
+        );
+        assert!(!part2.exists());
+        assert_eq!(std::fs::read_dir(&journal_dir).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn parts_of_a_failed_join_are_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join(".journal");
+        let output = dir.path().join("recording_x.mkv");
+        let runner = FakeRunner::new()
+            .on(
+                Tool::Ffmpeg,
+                "concat",
+                FakeResponse::failing(1, "No space left"),
+            )
+            .on_with(Tool::Ffmpeg, "x11grab", |args| {
+                let name = Path::new(args.last().unwrap()).file_name().unwrap();
+                FakeResponse::capture(1).writes(format!("{}\n", name.to_string_lossy()))
+            });
+        let args = ["-f", "x11grab", "-i", ":0"]
+            .into_iter()
+            .map(String::from)
+            .chain([output.to_string_lossy().to_string()])
+            .collect();
+
+        let mut session = RecordingSession::start(
+            Arc::new(runner),
+            args,
+            SessionOptions::default().with_journal(&journal_dir),
+        )
+        .await
+        .unwrap();
+        session.pause().await.unwrap();
+        session.resume().await.unwrap();
+        let parts = session.parts().to_vec();
+        assert!(session.finish().await.is_err());
+        // Nothing was moved, so the journal still lists every part
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "recording_x.mkv\n"
+        );
+        assert!(parts[1].exists());
+
+        let recovered = recover_orphans(&fake_ffmpeg(), &journal_dir).await.unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "fixed recording_x.mkv\nfixed recording_x_part2.mkv\n"
+        );
+        assert!(!parts[1].exists());
+        assert_eq!(std::fs::read_dir(&journal_dir).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn split_parts_are_kept_and_empty_ones_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join(".journal");
+        let output = dir.path().join("recording_x.mp4");
+        let parts: Vec<PathBuf> = (1..=3)
+            .map(|n| dir.path().join(format!("recording_x_00{n}.mp4")))
+            .collect();
+        std::fs::write(&parts[0], "one").unwrap();
+        std::fs::write(&parts[1], "two").unwrap();
+        // The third part was created but nothing reached it
+        std::fs::write(&parts[2], "").unwrap();
+        write_entry(&journal_dir, "a", &dead_entry(&output, parts.clone(), true));
+
+        let runner = fake_ffmpeg();
+        let recovered = recover_orphans(&runner, &journal_dir).await.unwrap();
+        assert_eq!(recovered[0].parts, parts[..2]);
+        assert_eq!(std::fs::read_to_string(&parts[1]).unwrap(), "fixed two");
+        // The remux keeps the container
+        let (_, args) = &runner.calls()[0];
+        assert!(args.last().unwrap().ends_with(".recovering.mp4"));
+    }
+
+    #[tokio::test]
+    async fn unrepairable_files_are_left_and_the_entry_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join(".journal");
+        let output = dir.path().join("recording_x.mp4");
+        std::fs::write(&output, "no moov").unwrap();
+        write_entry(
+            &journal_dir,
+            "a",
+            &dead_entry(&output, vec![output.clone()], false),
+        );
+
+        let runner = FakeRunner::new().on(
+            Tool::Ffmpeg,
+            "-map 0",
+            FakeResponse::failing(1, "moov atom not found"),
+        );
+        let recovered = recover_orphans(&runner, &journal_dir).await.unwrap();
+        assert!(recovered.is_empty());
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "no moov");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert_eq!(std::fs::read_dir(&journal_dir).unwrap().count(), 0);
+    }
+}
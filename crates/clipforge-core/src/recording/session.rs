@@ -0,0 +1,704 @@
+use crate::config::{RecordingConfig, SplitConfig};
+use crate::error::{Error, Result};
+use crate::process::FfmpegProcess;
+use crate::recording::journal::Journal;
+use crate::recording::perf::{DropWarning, PerformanceSummary, PerformanceTracker};
+use crate::runner::FfmpegRunner;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+/// How long a stalled part gets to finalize before it is killed
+const PART_STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Share of the part size limit kept spare: -fs stops a little past the
+/// limit it is given, and the trailer is written after that
+const SIZE_LIMIT_SPARE_PERCENT: u64 = 2;
+
+/// Tunables for a recording session
+#[derive(Debug, Clone)]
+pub struct SessionOptions {
+    /// Restart into a new part when progress stalls this long
+    pub stall_timeout: Option<Duration>,
+    /// Warn when more than this percentage of frames drop
+    pub drop_warning_percent: f64,
+    /// Keep the recording as numbered parts cut at these limits
+    pub split: SplitConfig,
+    /// Journal the session's files here so they can be recovered after a
+    /// crash
+    pub journal_dir: Option<PathBuf>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            stall_timeout: None,
+            drop_warning_percent: 5.0,
+            split: SplitConfig::default(),
+            journal_dir: None,
+        }
+    }
+}
+
+impl SessionOptions {
+    pub fn from_config(recording: &RecordingConfig) -> Self {
+        Self {
+            stall_timeout: recording.stall_timeout(),
+            drop_warning_percent: recording.drop_warning_percent,
+            split: recording.split.clone(),
+            journal_dir: None,
+        }
+    }
+
+    pub fn with_journal(mut self, dir: &Path) -> Self {
+        self.journal_dir = Some(dir.to_path_buf());
+        self
+    }
+}
+
+/// Result of a finished session
+#[derive(Debug, Clone)]
+pub struct FinishedRecording {
+    /// The recording, or its first part when split
+    pub path: PathBuf,
+    /// Every file of the recording, in order; just `path` unless split
+    pub parts: Vec<PathBuf>,
+    pub performance: PerformanceSummary,
+}
+
+/// Performance tracking shared with the per-part progress listeners
+#[derive(Clone)]
+struct Telemetry {
+    started: Instant,
+    tracker: Arc<Mutex<PerformanceTracker>>,
+    warnings: broadcast::Sender<DropWarning>,
+}
+
+impl Telemetry {
+    fn new(drop_warning_percent: f64) -> Self {
+        Self {
+            started: Instant::now(),
+            tracker: Arc::new(Mutex::new(PerformanceTracker::new(drop_warning_percent))),
+            warnings: broadcast::channel(16).0,
+        }
+    }
+
+    /// Feed a part's progress reports into the tracker until it exits or
+    /// the next part starts
+    fn listen(&self, process: &FfmpegProcess) {
+        let mut progress_rx = process.subscribe_progress();
+        let telemetry = self.clone();
+        let part = self.tracker.lock().unwrap().part();
+
+        tokio::spawn(async move {
+            while progress_rx.changed().await.is_ok() {
+                let progress = progress_rx.borrow_and_update().clone();
+                let at = telemetry.started.elapsed();
+                let warning = {
+                    let mut tracker = telemetry.tracker.lock().unwrap();
+                    // A late report of a finished part would count twice
+                    if tracker.part() != part {
+                        break;
+                    }
+                    tracker.record(&progress, at)
+                };
+                if let Some(warning) = warning {
+                    warn!(
+                        dropped = warning.dropped,
+                        percent = warning.percent,
+                        "capture is dropping frames"
+                    );
+                    let _ = telemetry.warnings.send(warning);
+                }
+            }
+        });
+    }
+
+    fn summary(&self) -> PerformanceSummary {
+        self.tracker.lock().unwrap().summary()
+    }
+}
+
+/// A recording made of one or more consecutive parts.
+///
+/// Capture normally writes straight to the output file. If the stall
+/// watchdog fires, or the recording is paused and resumed, the current part
+/// is finalized and capture restarts into `<stem>_part2.<ext>` and so on;
+/// `finish` joins the parts back into the output file. Joining butts the
+/// parts together, so paused spans leave no gap in the result.
+///
+/// With split limits, each part is a file of its own named from the part
+/// template. FFmpeg ends a part itself at the limit (`-t`/`-fs`), so every
+/// part starts on a keyframe; `next_part` continues capture in the next
+/// one, and `finish` leaves the parts as they are.
+pub struct RecordingSession {
+    runner: Arc<dyn FfmpegRunner>,
+    /// FFmpeg args for the first part; the output path is the last arg
+    args: Vec<String>,
+    output: PathBuf,
+    parts: Vec<PathBuf>,
+    process: FfmpegProcess,
+    options: SessionOptions,
+    telemetry: Telemetry,
+    /// Entry listing the files to recover if the app dies mid-recording
+    journal: Option<Journal>,
+    /// When the current pause began
+    paused_at: Option<Instant>,
+    /// Total length of earlier pauses
+    paused_for: Duration,
+}
+
+impl RecordingSession {
+    /// Start recording with args from `build_recording_command`
+    pub async fn start(
+        runner: Arc<dyn FfmpegRunner>,
+        args: Vec<String>,
+        options: SessionOptions,
+    ) -> Result<Self> {
+        let output = args
+            .last()
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::Other("recording command has no output".into()))?;
+
+        let telemetry = Telemetry::new(options.drop_warning_percent);
+        let first = if options.split.is_enabled() {
+            split_part_path(&options.split, &output, 1)
+        } else {
+            output.clone()
+        };
+        let part_args = part_args(&args, &first, &options.split);
+        let process = spawn_part(runner.as_ref(), &part_args, &options, &telemetry).await?;
+
+        // Losing the journal only costs recovery, not the recording
+        let journal = options.journal_dir.as_deref().and_then(|dir| {
+            Journal::create(dir, &output, &first, options.split.is_enabled())
+                .inspect_err(|e| warn!(error = %e, "could not journal recording"))
+                .ok()
+        });
+
+        Ok(Self {
+            runner,
+            args,
+            parts: vec![first],
+            output,
+            process,
+            options,
+            telemetry,
+            journal,
+            paused_at: None,
+            paused_for: Duration::ZERO,
+        })
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// The file being recorded: the output, or the current part when split
+    pub fn current_output(&self) -> &Path {
+        if self.options.split.is_enabled() {
+            self.parts.last().expect("a session has a part")
+        } else {
+            &self.output
+        }
+    }
+
+    /// Files written so far, in order
+    pub fn parts(&self) -> &[PathBuf] {
+        &self.parts
+    }
+
+    /// The FFmpeg process writing the current part, or the one that wrote
+    /// the last part while paused
+    pub fn process(&self) -> &FfmpegProcess {
+        &self.process
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        !self.is_paused() && self.process.is_stalled()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Whether the current part ended at its split limit, so capture should
+    /// continue in the next one
+    pub fn part_complete(&self) -> bool {
+        self.options.split.is_enabled()
+            && !self.is_paused()
+            && self
+                .process
+                .exit()
+                .is_some_and(|exit| exit.failure.is_none())
+    }
+
+    /// Time spent recording, not counting pauses
+    pub fn recorded_time(&self) -> Duration {
+        let paused = self.paused_for + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.telemetry.started.elapsed().saturating_sub(paused)
+    }
+
+    /// Live warnings raised when capture drops too many frames
+    pub fn subscribe_warnings(&self) -> broadcast::Receiver<DropWarning> {
+        self.telemetry.warnings.subscribe()
+    }
+
+    /// Performance so far
+    pub fn performance(&self) -> PerformanceSummary {
+        self.telemetry.summary()
+    }
+
+    /// Finalize the current part and continue capture in a new one
+    pub async fn restart(&mut self) -> Result<&Path> {
+        if self.is_paused() {
+            return Err(Error::Other("recording is paused".into()));
+        }
+        self.process.stop_within(PART_STOP_TIMEOUT).await?;
+        self.start_next_part().await?;
+        warn!(
+            part = self.parts.len(),
+            "capture stalled, restarted into a new part"
+        );
+
+        Ok(self.parts.last().expect("just pushed"))
+    }
+
+    /// Continue capture in the next part after the current one reached its
+    /// split limit
+    pub async fn next_part(&mut self) -> Result<&Path> {
+        if !self.part_complete() {
+            return Err(Error::Other("current part is still recording".into()));
+        }
+        self.start_next_part().await?;
+        info!(part = self.parts.len(), "recording split into a new part");
+
+        Ok(self.parts.last().expect("just pushed"))
+    }
+
+    /// Finalize the current part and stop capturing until `resume`
+    pub async fn pause(&mut self) -> Result<()> {
+        if self.is_paused() {
+            return Err(Error::Other("recording is already paused".into()));
+        }
+        self.process.stop_within(PART_STOP_TIMEOUT).await?;
+        self.paused_at = Some(Instant::now());
+        info!(part = self.parts.len(), "recording paused");
+        Ok(())
+    }
+
+    /// Continue capture in a new part
+    pub async fn resume(&mut self) -> Result<&Path> {
+        let Some(paused_at) = self.paused_at else {
+            return Err(Error::Other("recording is not paused".into()));
+        };
+        self.start_next_part().await?;
+        self.paused_for += paused_at.elapsed();
+        self.paused_at = None;
+        info!(part = self.parts.len(), "recording resumed");
+
+        Ok(self.parts.last().expect("just pushed"))
+    }
+
+    async fn start_next_part(&mut self) -> Result<()> {
+        let n = self.parts.len() + 1;
+        let next = if self.options.split.is_enabled() {
+            split_part_path(&self.options.split, &self.output, n)
+        } else {
+            part_path(&self.output, n)
+        };
+        let args = part_args(&self.args, &next, &self.options.split);
+
+        self.telemetry.tracker.lock().unwrap().start_part();
+        self.process =
+            spawn_part(self.runner.as_ref(), &args, &self.options, &self.telemetry).await?;
+        if let Some(journal) = &mut self.journal {
+            if let Err(e) = journal.add_part(&next) {
+                warn!(error = %e, "could not journal recording part");
+            }
+        }
+        self.parts.push(next);
+        Ok(())
+    }
+
+    /// Stop capture and join all parts into the output file. A paused
+    /// session has nothing left to stop. The journal entry is kept if
+    /// joining fails, so the parts are recovered on the next start.
+    pub async fn finish(mut self) -> Result<FinishedRecording> {
+        self.process.stop_graceful().await?;
+        let performance = self.telemetry.summary();
+
+        if !self.options.split.is_enabled() && self.parts.len() > 1 {
+            join_session(self.runner.as_ref(), &self.output, &self.parts).await?;
+        }
+        if let Some(journal) = self.journal.take() {
+            if let Err(e) = journal.remove() {
+                warn!(error = %e, "could not remove recording journal entry");
+            }
+        }
+
+        if self.options.split.is_enabled() {
+            info!(parts = self.parts.len(), "split recording finished");
+            return Ok(FinishedRecording {
+                path: self.parts[0].clone(),
+                parts: self.parts,
+                performance,
+            });
+        }
+
+        Ok(FinishedRecording {
+            parts: vec![self.output.clone()],
+            path: self.output,
+            performance,
+        })
+    }
+}
+
+async fn spawn_part(
+    runner: &dyn FfmpegRunner,
+    args: &[String],
+    options: &SessionOptions,
+    telemetry: &Telemetry,
+) -> Result<FfmpegProcess> {
+    let process = FfmpegProcess::spawn(runner, args.to_vec()).await?;
+    if let Some(timeout) = options.stall_timeout {
+        process.watch_stalls(timeout);
+    }
+    telemetry.listen(&process);
+    Ok(process)
+}
+
+/// `recording_x.mkv` -> `recording_x_partN.mkv`
+fn part_path(output: &Path, n: usize) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}_part{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_part{n}"),
+    };
+    output.with_file_name(name)
+}
+
+/// `recording_x.mkv` -> `recording_x_00N.mkv` with the default template
+fn split_part_path(split: &SplitConfig, output: &Path, n: usize) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = split.part_name(&stem, n);
+    let name = match output.extension() {
+        Some(ext) => format!("{name}.{}", ext.to_string_lossy()),
+        None => name,
+    };
+    output.with_file_name(name)
+}
+
+/// Args writing to `path`, with the split limits as output options
+fn part_args(args: &[String], path: &Path, split: &SplitConfig) -> Vec<String> {
+    // The output path is the last arg; output options go right before it
+    let mut args = args[..args.len().saturating_sub(1)].to_vec();
+    if let Some(duration) = split.max_duration() {
+        args.extend(["-t".to_string(), duration.as_secs().to_string()]);
+    }
+    if let Some(bytes) = split.max_bytes() {
+        let limit = bytes / 100 * (100 - SIZE_LIMIT_SPARE_PERCENT);
+        args.extend(["-fs".to_string(), limit.to_string()]);
+    }
+    args.push(path.to_string_lossy().to_string());
+    args
+}
+
+/// Join the parts of a session into `output`, then delete them. The first
+/// part may have been written to `output` itself; the join goes to a
+/// temporary file that replaces the output only once it succeeded, so a
+/// failed join leaves every part where the journal says it is.
+pub(crate) async fn join_session(
+    runner: &dyn FfmpegRunner,
+    output: &Path,
+    parts: &[PathBuf],
+) -> Result<()> {
+    let joined = temp_path(output, "joining");
+    if let Err(e) = join_parts(runner, parts, &joined).await {
+        let _ = std::fs::remove_file(&joined);
+        return Err(e);
+    }
+    std::fs::rename(&joined, output)?;
+    for part in parts.iter().filter(|p| *p != output) {
+        let _ = std::fs::remove_file(part);
+    }
+
+    info!(
+        output = %output.display(),
+        parts = parts.len(),
+        "recording parts joined"
+    );
+    Ok(())
+}
+
+/// `recording_x.mkv` -> `recording_x.<tag>.mkv`; the extension is kept so
+/// FFmpeg picks the same muxer
+pub(crate) fn temp_path(path: &Path, tag: &str) -> PathBuf {
+    match path.extension() {
+        Some(ext) => path.with_extension(format!("{tag}.{}", ext.to_string_lossy())),
+        None => path.with_extension(tag),
+    }
+}
+
+/// Losslessly concatenate parts with the concat demuxer
+async fn join_parts(runner: &dyn FfmpegRunner, parts: &[PathBuf], output: &Path) -> Result<()> {
+    let list = output.with_extension("parts.txt");
+    let content: String = parts
+        .iter()
+        .map(|p| format!("file '{}'\n", p.to_string_lossy()))
+        .collect();
+    std::fs::write(&list, content)?;
+
+    let args = vec![
+        "-y".to_string(),
+        "-f".to_string(),
+        "concat".to_string(),
+        "-safe".to_string(),
+        "0".to_string(),
+        "-i".to_string(),
+        list.to_string_lossy().to_string(),
+        "-c".to_string(),
+        "copy".to_string(),
+        output.to_string_lossy().to_string(),
+    ];
+    let exit = FfmpegProcess::spawn(runner, args).await?.wait().await;
+    let _ = std::fs::remove_file(&list);
+
+    match exit.error() {
+        Some(e) => Err(e),
+        None if exit.code == Some(0) => Ok(()),
+        None => Err(Error::FfmpegExitCode(exit.code.unwrap_or(-1))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::fake::{FakeResponse, FakeRunner};
+    use crate::runner::Tool;
+
+    /// Captures write their output name; concat joins the listed files
+    fn fake_ffmpeg() -> Arc<FakeRunner> {
+        let runner = FakeRunner::new()
+            .on_with(Tool::Ffmpeg, "concat", |args| {
+                let list = args.iter().skip_while(|a| *a != "-i").nth(1).unwrap();
+                let joined: String = std::fs::read_to_string(list)
+                    .unwrap()
+                    .lines()
+                    .map(|l| l.trim_start_matches("file '").trim_end_matches('\''))
+                    .map(|f| std::fs::read_to_string(f).unwrap())
+                    .collect();
+                FakeResponse::ok().writes(joined)
+            })
+            .on_with(Tool::Ffmpeg, "x11grab", |args| {
+                let name = Path::new(args.last().unwrap()).file_name().unwrap();
+                FakeResponse::capture(1).writes(format!("{}\n", name.to_string_lossy()))
+            });
+        Arc::new(runner)
+    }
+
+    fn capture_args(output: &Path) -> Vec<String> {
+        vec![
+            "-f".into(),
+            "x11grab".into(),
+            "-i".into(),
+            ":0".into(),
+            output.to_string_lossy().into(),
+        ]
+    }
+
+    #[test]
+    fn part_paths_keep_extension() {
+        let output = Path::new("/rec/recording_x.mkv");
+        assert_eq!(
+            part_path(output, 2),
+            PathBuf::from("/rec/recording_x_part2.mkv")
+        );
+    }
+
+    #[tokio::test]
+    async fn single_part_is_left_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("recording_x.mkv");
+        let runner = fake_ffmpeg();
+
+        let session = RecordingSession::start(
+            runner.clone(),
+            capture_args(&output),
+            SessionOptions::default(),
+        )
+        .await
+        .unwrap();
+        let path = session.finish().await.unwrap().path;
+
+        assert_eq!(path, output);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "recording_x.mkv\n");
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn journal_entry_lives_until_the_recording_is_finished() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join(".journal");
+        let output = dir.path().join("recording_x.mkv");
+
+        let mut session = RecordingSession::start(
+            fake_ffmpeg(),
+            capture_args(&output),
+            SessionOptions::default().with_journal(&journal_dir),
+        )
+        .await
+        .unwrap();
+        session.pause().await.unwrap();
+        session.resume().await.unwrap();
+        let entry = session.journal.as_ref().unwrap().entry().clone();
+        assert_eq!(entry.parts, session.parts());
+        // The entry and its lock
+        assert_eq!(std::fs::read_dir(&journal_dir).unwrap().count(), 2);
+
+        session.finish().await.unwrap();
+        assert_eq!(std::fs::read_dir(&journal_dir).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn stall_restarts_into_new_part_and_joins_on_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("recording_x.mkv");
+
+        let mut session = RecordingSession::start(
+            fake_ffmpeg(),
+            capture_args(&output),
+            SessionOptions {
+                stall_timeout: Some(Duration::from_millis(200)),
+                ..SessionOptions::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut stalled = session.process().subscribe_stalled();
+        tokio::time::timeout(Duration::from_secs(5), stalled.wait_for(|s| *s))
+            .await
+            .expect("watchdog should fire")
+            .unwrap();
+
+        let part2 = session.restart().await.unwrap().to_path_buf();
+        assert_eq!(part2, dir.path().join("recording_x_part2.mkv"));
+        assert!(!session.is_stalled());
+
+        let finished = session.finish().await.unwrap();
+        assert_eq!(finished.path, output);
+        assert_eq!(
+            std::fs::read_to_string(&finished.path).unwrap(),
+            "recording_x.mkv\nrecording_x_part2.mkv\n"
+        );
+        // One frame reported by each part
+        assert_eq!(finished.performance.total_drops, 0);
+        assert!(!part2.exists());
+        assert!(!dir.path().join("recording_x_part1.mkv").exists());
+    }
+
+    #[tokio::test]
+    async fn split_parts_are_kept_and_numbered() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("recording_x.mkv");
+        // The first two parts run into their limit, the last is stopped
+        let started = std::sync::atomic::AtomicUsize::new(0);
+        let runner = Arc::new(
+            FakeRunner::new().on_with(Tool::Ffmpeg, "x11grab", move |args| {
+                let name = Path::new(args.last().unwrap()).file_name().unwrap();
+                let contents = format!("{}\n", name.to_string_lossy());
+                match started.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                    0 | 1 => FakeResponse::ok().writes(contents),
+                    _ => FakeResponse::capture(1).writes(contents),
+                }
+            }),
+        );
+        let options = SessionOptions {
+            split: SplitConfig {
+                max_size_mb: 100,
+                max_minutes: 30,
+                ..SplitConfig::default()
+            },
+            ..SessionOptions::default()
+        };
+
+        let mut session = RecordingSession::start(runner.clone(), capture_args(&output), options)
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            session.process().wait().await;
+            assert!(session.part_complete());
+            session.next_part().await.unwrap();
+        }
+        assert!(!session.part_complete());
+
+        let finished = session.finish().await.unwrap();
+        let names: Vec<_> = finished
+            .parts
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "recording_x_001.mkv",
+                "recording_x_002.mkv",
+                "recording_x_003.mkv"
+            ]
+        );
+        assert_eq!(finished.path, finished.parts[0]);
+        for part in &finished.parts {
+            assert!(part.exists());
+        }
+        assert!(!output.exists());
+
+        // Limits are output options right before the part's path
+        let (_, args) = &runner.calls()[0];
+        let tail = &args[args.len() - 5..];
+        assert_eq!(tail[..4], ["-t", "1800", "-fs", "102760448"]);
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume_join_parts_without_paused_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("recording_x.mkv");
+        let runner = fake_ffmpeg();
+
+        let mut session = RecordingSession::start(
+            runner.clone(),
+            capture_args(&output),
+            SessionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        session.pause().await.unwrap();
+        assert!(session.is_paused());
+        assert!(session.pause().await.is_err());
+        assert!(session.restart().await.is_err());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        // Only the pause so far, and it is not counted
+        assert!(session.recorded_time() < Duration::from_millis(250));
+        assert_eq!(runner.calls().len(), 1);
+
+        let part2 = session.resume().await.unwrap().to_path_buf();
+        assert_eq!(part2, dir.path().join("recording_x_part2.mkv"));
+        assert!(session.resume().await.is_err());
+
+        session.pause().await.unwrap();
+        let finished = session.finish().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&finished.path).unwrap(),
+            "recording_x.mkv\nrecording_x_part2.mkv\n"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use crate::config::Config;
+use crate::encode::ffmpeg::quality_to_qp;
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Resolution assumed when neither scaling nor cropping pins it down
+const ASSUMED_RESOLUTION: (u32, u32) = (1920, 1080);
+
+/// Audio track bitrate used by recording commands
+const AUDIO_KBPS: u64 = 192;
+
+/// Warn once the ring would take more than this share of its limit
+const WARN_FRACTION: f64 = 0.8;
+
+/// Expected size of the replay ring, and how much room it has
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RingPlan {
+    pub segments: u32,
+    pub estimated_kbps: u64,
+    pub estimated_bytes: u64,
+    /// Free space where the segments are written, if known
+    pub available_bytes: Option<u64>,
+    /// Configured `memory_budget_mb` in bytes
+    pub budget_bytes: Option<u64>,
+    /// Disk tier holding footage older than `ram_secs`, if tiered
+    pub disk: Option<TierPlan>,
+}
+
+/// Expected size of the replay disk tier, and how much room it has
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TierPlan {
+    pub estimated_bytes: u64,
+    /// Free space in `replay_disk_dir`, if known
+    pub available_bytes: Option<u64>,
+    /// Configured `disk_budget_mb` in bytes
+    pub budget_bytes: Option<u64>,
+}
+
+/// Whether a ring fits in its limit
+#[derive(Debug, Clone, PartialEq)]
+pub enum RingFit {
+    Fits,
+    /// Fits, but leaves little room
+    Tight(String),
+}
+
+impl RingPlan {
+    /// Plan for the configured ring, checking free space in the cache dir
+    pub fn for_config(config: &Config) -> Self {
+        let available = available_space(&config.paths.replay_cache_dir);
+        let mut plan = Self::new(config, available);
+        if let Some(ref mut disk) = plan.disk {
+            disk.available_bytes = available_space(&config.paths.replay_disk_dir);
+        }
+        plan
+    }
+
+    pub fn new(config: &Config, available_bytes: Option<u64>) -> Self {
+        let segments = config.replay.segment_count();
+        let estimated_kbps = estimate_kbps(config);
+        let ring_secs = segments as u64 * config.replay.segment_secs.max(1) as u64;
+        let budget_mb = config.replay.memory_budget_mb;
+        let byte_rate = estimated_kbps * 1000 / 8;
+
+        // Every finished segment is copied to disk, so the disk tier holds
+        // the whole buffer plus the segment that just closed
+        let disk = config.replay.is_tiered().then(|| {
+            let disk_secs = (config.replay.duration_secs as u64)
+                .next_multiple_of(config.replay.segment_secs.max(1) as u64)
+                + config.replay.segment_secs as u64;
+            let disk_mb = config.replay.disk_budget_mb;
+            TierPlan {
+                estimated_bytes: byte_rate * disk_secs,
+                available_bytes: None,
+                budget_bytes: (disk_mb > 0).then_some(disk_mb * 1024 * 1024),
+            }
+        });
+
+        Self {
+            segments,
+            estimated_kbps,
+            estimated_bytes: byte_rate * ring_secs,
+            available_bytes,
+            budget_bytes: (budget_mb > 0).then_some(budget_mb * 1024 * 1024),
+            disk,
+        }
+    }
+
+    /// The tighter of the byte budget and the free space
+    pub fn limit(&self) -> Option<u64> {
+        tighter(self.budget_bytes, self.available_bytes)
+    }
+
+    /// Refuse a ring that cannot fit; flag one that barely does
+    pub fn check(&self) -> Result<RingFit> {
+        let hint = match self.disk {
+            Some(_) => "lower replay.ram_secs or the recording quality",
+            None => "lower replay.duration_secs or the recording quality",
+        };
+        let ram = fit("replay buffer", self.estimated_bytes, self.limit(), hint)?;
+        let Some(ref disk) = self.disk else {
+            return Ok(ram);
+        };
+        let disk = fit(
+            "replay disk tier",
+            disk.estimated_bytes,
+            tighter(disk.budget_bytes, disk.available_bytes),
+            "lower replay.duration_secs or raise replay.disk_budget_mb",
+        )?;
+        Ok(match ram {
+            RingFit::Fits => disk,
+            tight => tight,
+        })
+    }
+}
+
+fn tighter(budget: Option<u64>, available: Option<u64>) -> Option<u64> {
+    match (budget, available) {
+        (Some(b), Some(a)) => Some(b.min(a)),
+        (b, a) => b.or(a),
+    }
+}
+
+/// Compare an estimate with its limit, naming `what` in messages
+fn fit(what: &str, estimated: u64, limit: Option<u64>, hint: &str) -> Result<RingFit> {
+    let Some(limit) = limit else {
+        return Ok(RingFit::Fits);
+    };
+    let needed = mib(estimated);
+    if estimated > limit {
+        return Err(Error::Config(format!(
+            "{what} needs ~{needed} MiB but only {} MiB is available; {hint}",
+            mib(limit)
+        )));
+    }
+    if estimated as f64 > limit as f64 * WARN_FRACTION {
+        return Ok(RingFit::Tight(format!(
+            "{what} needs ~{needed} MiB of {} MiB available",
+            mib(limit)
+        )));
+    }
+    Ok(RingFit::Fits)
+}
+
+pub fn mib(bytes: u64) -> u64 {
+    bytes.div_ceil(1024 * 1024)
+}
+
+/// Rough encoder output rate for the configured quality.
+///
+/// Recordings use constant QP, so size follows bits per pixel; every 6 QP
+/// steps roughly halves it.
+fn estimate_kbps(config: &Config) -> u64 {
+    let recording = &config.recording;
+    let (width, height) = recording
+        .output_resolution
+        .or(recording.crop.as_ref().map(|c| (c.w, c.h)))
+        .unwrap_or(ASSUMED_RESOLUTION);
+    let qp = quality_to_qp(&recording.quality) as f64;
+    let bits_per_pixel = 0.1 * 2f64.powf((20.0 - qp) / 6.0);
+    let video = width as f64 * height as f64 * recording.fps as f64 * bits_per_pixel / 1000.0;
+    let audio = if recording.audio_enabled {
+        AUDIO_KBPS
+    } else {
+        0
+    };
+    video as u64 + audio
+}
+
+/// Free bytes on the filesystem holding `path` (or its nearest existing
+/// ancestor), as available to unprivileged users
+pub fn available_space(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let c_path = CString::new(existing.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `c_path` is NUL-terminated and `stat` is only read once
+    // statvfs has filled it in
+    let stat = unsafe {
+        if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Quality;
+
+    fn config(duration_secs: u32) -> Config {
+        let mut config = Config::default();
+        config.replay.duration_secs = duration_secs;
+        config.replay.segment_secs = 3;
+        config.recording.fps = 60;
+        config.recording.quality = Quality::High;
+        config.recording.output_resolution = Some((1920, 1080));
+        config
+    }
+
+    #[test]
+    fn estimate_scales_with_duration_and_quality() {
+        let plan = RingPlan::new(&config(30), None);
+        assert_eq!(plan.segments, 12);
+        // 1080p60 at 0.1 bpp ~ 12.4 Mbps, plus audio
+        assert_eq!(plan.estimated_kbps, 12441 + AUDIO_KBPS);
+        assert_eq!(plan.estimated_bytes, plan.estimated_kbps * 125 * 36);
+
+        let mut low = config(30);
+        low.recording.quality = Quality::Low;
+        assert!(RingPlan::new(&low, None).estimated_kbps < plan.estimated_kbps / 2);
+    }
+
+    #[test]
+    fn ring_larger_than_free_space_is_refused() {
+        let plan = RingPlan::new(&config(120), Some(100 * 1024 * 1024));
+        let err = plan.check().unwrap_err();
+        assert!(
+            err.to_string().contains("replay buffer needs"),
+            "got: {err}"
+        );
+    }
+
+    #[test]
+    fn budget_limits_below_free_space() {
+        let mut cfg = config(30);
+        cfg.replay.memory_budget_mb = 80;
+        let plan = RingPlan::new(&cfg, Some(u64::MAX));
+        assert_eq!(plan.limit(), Some(80 * 1024 * 1024));
+        assert_eq!(plan.check().unwrap(), RingFit::Fits);
+
+        cfg.replay.memory_budget_mb = 60;
+        let plan = RingPlan::new(&cfg, Some(u64::MAX));
+        assert!(matches!(plan.check().unwrap(), RingFit::Tight(_)));
+    }
+
+    #[test]
+    fn unknown_space_and_no_budget_always_fits() {
+        let plan = RingPlan::new(&config(600), None);
+        assert_eq!(plan.check().unwrap(), RingFit::Fits);
+    }
+
+    #[test]
+    fn tiered_ring_checks_each_tier_separately() {
+        let mut cfg = config(1800);
+        cfg.replay.ram_secs = 30;
+        cfg.replay.memory_budget_mb = 200;
+        let plan = RingPlan::new(&cfg, None);
+        // The cache dir holds 30s plus headroom, the disk the full 30min
+        assert_eq!(plan.segments, 12);
+        let disk = plan.disk.as_ref().unwrap();
+        assert_eq!(disk.estimated_bytes, plan.estimated_kbps * 125 * 1803);
+        assert_eq!(plan.check().unwrap(), RingFit::Fits);
+
+        cfg.replay.disk_budget_mb = 1024;
+        let err = RingPlan::new(&cfg, None).check().unwrap_err();
+        assert!(err.to_string().contains("disk tier"), "got: {err}");
+    }
+
+    #[test]
+    fn available_space_uses_existing_ancestor() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("not/yet/created");
+        assert!(available_space(&missing).is_some_and(|b| b > 0));
+    }
+}
@@ -0,0 +1,412 @@
+//! Control plane for a replay buffer running in the background.
+//!
+//! The buffer holds a lockfile next to its segment directory so only one
+//! process writes there, and answers newline-delimited JSON requests on a
+//! Unix socket beside it.
+
+use crate::error::{Error, Result};
+use crate::replay::lookahead::PendingSaveStatus;
+use crate::replay::pause::PauseReason;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Lockfile and control socket for a replay cache directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct DaemonPaths {
+    pub lock: PathBuf,
+    pub socket: PathBuf,
+}
+
+impl DaemonPaths {
+    /// Paths beside `cache_dir`, which is wiped whenever a buffer starts
+    pub fn for_cache_dir(cache_dir: &Path) -> Self {
+        Self {
+            lock: cache_dir.with_extension("lock"),
+            socket: cache_dir.with_extension("sock"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    Status,
+    Save {
+        seconds: u32,
+        /// Keep recording this long after the request before saving
+        #[serde(default)]
+        after_secs: u32,
+        #[serde(default)]
+        output: Option<PathBuf>,
+    },
+    Stop,
+    ReloadConfig,
+    /// Begin a full recording that starts with the buffered footage
+    StartRecording {
+        #[serde(default)]
+        output: Option<PathBuf>,
+    },
+    StopRecording,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub duration_secs: u32,
+    /// Footage currently held in finished segments
+    pub buffered_secs: f64,
+    pub segments: usize,
+    pub started_at: String,
+    pub cache_dir: PathBuf,
+    /// Output of a recording started from the buffer, while it runs
+    #[serde(default)]
+    pub recording: Option<PathBuf>,
+    /// Look-ahead saves still waiting for footage
+    #[serde(default)]
+    pub pending_saves: Vec<PendingSaveStatus>,
+    /// Why the capture is paused, while an auto-pause condition holds
+    #[serde(default)]
+    pub paused: Option<PauseReason>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum DaemonResponse {
+    Status(DaemonStatus),
+    Saved {
+        path: PathBuf,
+    },
+    /// A look-ahead save was queued, or merged into the one writing `path`
+    SavePending {
+        id: u64,
+        path: PathBuf,
+        remaining_secs: f64,
+    },
+    Stopping,
+    /// `restarted` is set when the capture had to restart to apply changes
+    Reloaded {
+        restarted: bool,
+    },
+    /// `buffered_secs` of footage from before the request are included
+    RecordingStarted {
+        path: PathBuf,
+        buffered_secs: f64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Exclusive claim on a replay cache directory, released on drop.
+///
+/// An flock on the lockfile, so the claim ends with its process however
+/// that exits; the file holds the owner's PID for error messages only.
+#[derive(Debug)]
+pub struct ReplayLock {
+    file: File,
+}
+
+impl ReplayLock {
+    pub fn acquire(path: &Path) -> Result<Self> {
+        // Never removed, so every claimant locks the same inode
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(Error::Io)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut owner = String::new();
+                let _ = file.read_to_string(&mut owner);
+                return Err(Error::ReplayAlreadyRunning(
+                    owner.trim().parse().unwrap_or(0),
+                ));
+            }
+            Err(TryLockError::Error(e)) => return Err(Error::Io(e)),
+        }
+
+        file.set_len(0).map_err(Error::Io)?;
+        write!(file, "{}", std::process::id()).map_err(Error::Io)?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for ReplayLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock; clear the PID first
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// How long a client may take to send its request line
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Listening end of the control socket. Only bind while holding the lock.
+///
+/// Each connection is read on a task of its own, so a slow or silent client
+/// cannot hold up others, and a request is never lost half read when the
+/// daemon stops waiting on `next` to do something else.
+pub struct ControlSocket {
+    requests: mpsc::Receiver<Result<PendingRequest>>,
+    accept: JoinHandle<()>,
+    path: PathBuf,
+}
+
+/// A request waiting for its response
+pub struct PendingRequest {
+    pub request: DaemonRequest,
+    stream: UnixStream,
+}
+
+impl PendingRequest {
+    pub async fn respond(mut self, response: &DaemonResponse) -> Result<()> {
+        write_message(&mut self.stream, response).await
+    }
+}
+
+impl ControlSocket {
+    pub fn bind(path: &Path) -> Result<Self> {
+        // A socket file left by a crashed daemon blocks bind
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(Error::Io(e)),
+            _ => {}
+        }
+        let listener = UnixListener::bind(path).map_err(Error::Io)?;
+
+        let (tx, requests) = mpsc::channel(16);
+        let accept = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        let _ = tx.send(Err(Error::Io(e))).await;
+                        return;
+                    }
+                };
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    if let Some(pending) = read_request(stream).await {
+                        let _ = tx.send(Ok(pending)).await;
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            requests,
+            accept,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Wait for the next well-formed request; malformed ones are answered
+    /// with an error and skipped
+    pub async fn next(&mut self) -> Result<PendingRequest> {
+        self.requests
+            .recv()
+            .await
+            .unwrap_or_else(|| Err(Error::Other("control socket closed".into())))
+    }
+}
+
+/// Read one request line, answering malformed ones with an error. `None`
+/// when the client sends nothing usable in time.
+async fn read_request(stream: UnixStream) -> Option<PendingRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    match tokio::time::timeout(REQUEST_TIMEOUT, reader.read_line(&mut line)).await {
+        Ok(Ok(n)) if n > 0 => {}
+        _ => return None,
+    }
+    let mut stream = reader.into_inner();
+    match serde_json::from_str(&line) {
+        Ok(request) => Some(PendingRequest { request, stream }),
+        Err(e) => {
+            let response = DaemonResponse::Error {
+                message: format!("invalid request: {e}"),
+            };
+            let _ = write_message(&mut stream, &response).await;
+            None
+        }
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.accept.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Send one request to a running daemon and wait for its answer
+pub async fn send_request(socket: &Path, request: &DaemonRequest) -> Result<DaemonResponse> {
+    let mut stream = match UnixStream::connect(socket).await {
+        Ok(stream) => stream,
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            return Err(Error::ReplayNotActive);
+        }
+        Err(e) => return Err(Error::Io(e)),
+    };
+    write_message(&mut stream, request).await?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .await
+        .map_err(Error::Io)?;
+    serde_json::from_str(&line).map_err(Error::Json)
+}
+
+async fn write_message(stream: &mut UnixStream, message: &impl Serialize) -> Result<()> {
+    let mut line = serde_json::to_string(message).map_err(Error::Json)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).await.map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_sit_beside_the_cache_dir() {
+        let paths = DaemonPaths::for_cache_dir(Path::new("/dev/shm/clipforge-replay"));
+        assert_eq!(paths.lock, PathBuf::from("/dev/shm/clipforge-replay.lock"));
+        assert_eq!(
+            paths.socket,
+            PathBuf::from("/dev/shm/clipforge-replay.sock")
+        );
+    }
+
+    #[test]
+    fn requests_use_command_tag() {
+        let json = serde_json::to_string(&DaemonRequest::Save {
+            seconds: 30,
+            after_secs: 0,
+            output: None,
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            r#"{"command":"save","seconds":30,"after_secs":0,"output":null}"#
+        );
+        let parsed: DaemonRequest =
+            serde_json::from_str(r#"{"command":"save","seconds":10}"#).unwrap();
+        assert!(matches!(
+            parsed,
+            DaemonRequest::Save {
+                after_secs: 0,
+                output: None,
+                ..
+            }
+        ));
+        let parsed: DaemonRequest = serde_json::from_str(r#"{"command":"reload_config"}"#).unwrap();
+        assert_eq!(parsed, DaemonRequest::ReloadConfig);
+    }
+
+    #[test]
+    fn lock_is_exclusive_and_released_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.lock");
+
+        let lock = ReplayLock::acquire(&path).unwrap();
+        let err = ReplayLock::acquire(&path).unwrap_err();
+        assert!(matches!(err, Error::ReplayAlreadyRunning(pid) if pid == std::process::id()));
+
+        drop(lock);
+        ReplayLock::acquire(&path).unwrap();
+    }
+
+    #[test]
+    fn stale_lock_is_reclaimed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.lock");
+        // Left by a crash; PID 1 is alive, but nothing holds the lock
+        std::fs::write(&path, "1").unwrap();
+
+        let _lock = ReplayLock::acquire(&path).unwrap();
+        let owner = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(owner, std::process::id().to_string());
+    }
+
+    #[tokio::test]
+    async fn requests_round_trip_over_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.sock");
+        let mut socket = ControlSocket::bind(&path).unwrap();
+
+        let server = tokio::spawn(async move {
+            let pending = socket.next().await.unwrap();
+            assert_eq!(pending.request, DaemonRequest::Stop);
+            pending.respond(&DaemonResponse::Stopping).await.unwrap();
+        });
+
+        let response = send_request(&path, &DaemonRequest::Stop).await.unwrap();
+        assert_eq!(response, DaemonResponse::Stopping);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn silent_client_does_not_block_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.sock");
+        let mut socket = ControlSocket::bind(&path).unwrap();
+
+        // Connects and never finishes its request line
+        let mut silent = UnixStream::connect(&path).await.unwrap();
+        silent.write_all(b"{\"command\":").await.unwrap();
+
+        let server = tokio::spawn(async move {
+            let pending = socket.next().await.unwrap();
+            assert_eq!(pending.request, DaemonRequest::Status);
+            pending.respond(&DaemonResponse::Stopping).await.unwrap();
+        });
+        let response = tokio::time::timeout(
+            Duration::from_secs(2),
+            send_request(&path, &DaemonRequest::Status),
+        )
+        .await
+        .expect("request was held up by the silent client")
+        .unwrap();
+        assert_eq!(response, DaemonResponse::Stopping);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn malformed_request_gets_error_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replay.sock");
+        let mut socket = ControlSocket::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let _ = socket.next().await;
+        });
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"{\"command\":\"explode\"}\n")
+            .await
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).await.unwrap();
+        let response: DaemonResponse = serde_json::from_str(&line).unwrap();
+        assert!(matches!(response, DaemonResponse::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn missing_daemon_reports_not_active() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = send_request(&dir.path().join("none.sock"), &DaemonRequest::Status)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ReplayNotActive));
+    }
+}
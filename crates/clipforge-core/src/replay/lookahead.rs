@@ -0,0 +1,252 @@
+//! Saves that keep recording for a while after they are triggered.
+//!
+//! A save asked for N seconds before and M seconds after the trigger waits
+//! until the ring has finished a segment past `t + M`, then cuts the window
+//! `[t - N, t + M]`. Windows of saves still waiting are merged when they
+//! overlap, so mashing the hotkey produces one clip.
+
+use crate::error::{Error, Result};
+use crate::replay::ring::ReplayRing;
+use crate::replay::save::save_window;
+use crate::runner::FfmpegRunner;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How often a waiting save should check the ring
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Extra wait past the window before saving whatever the ring has, in case
+/// capture stopped producing segments
+const DEADLINE_GRACE: Duration = Duration::from_secs(5);
+
+/// A save waiting for the ring to record the end of its window
+#[derive(Debug, Clone)]
+pub struct PendingSave {
+    pub id: u64,
+    /// Window on the ring's timeline
+    pub start: f64,
+    pub end: f64,
+    pub output: PathBuf,
+    deadline: Instant,
+}
+
+impl PendingSave {
+    /// Cut the window out of the ring into `output`
+    pub async fn save(&self, runner: &dyn FfmpegRunner, ring: &ReplayRing) -> Result<PathBuf> {
+        save_window(runner, ring, self.start, self.end, &self.output).await
+    }
+}
+
+/// Progress of a pending save, for status displays
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingSaveStatus {
+    pub id: u64,
+    pub output: PathBuf,
+    /// Length of the clip once saved
+    pub window_secs: f64,
+    /// Footage still to be recorded before it can be saved
+    pub remaining_secs: f64,
+}
+
+/// Look-ahead saves waiting on the ring
+#[derive(Debug, Default)]
+pub struct PendingSaves {
+    pending: Vec<PendingSave>,
+    next_id: u64,
+}
+
+impl PendingSaves {
+    /// Queue a save of `before` seconds before now and `after` seconds
+    /// after. A window overlapping a queued one is merged into it; returns
+    /// the id and output of the save that will contain the clip.
+    ///
+    /// Refuses a window longer than the ring keeps: its start would be
+    /// overwritten before its end is recorded.
+    pub fn request(
+        &mut self,
+        ring: &ReplayRing,
+        before: u32,
+        after: u32,
+        output: PathBuf,
+    ) -> Result<(u64, PathBuf)> {
+        let capacity = ring.capacity_secs();
+        if before + after > capacity {
+            return Err(Error::Other(format!(
+                "a clip of {before}s before and {after}s after does not fit in the {capacity}s replay buffer"
+            )));
+        }
+
+        let now = ring.live_edge()?;
+        let wait = Duration::from_secs(after as u64 + 2 * ring.segment_time as u64);
+        let mut save = PendingSave {
+            id: self.next_id,
+            start: now - before as f64,
+            end: now + after as f64,
+            output,
+            deadline: Instant::now() + wait + DEADLINE_GRACE,
+        };
+
+        // Fold in every queued window the new one touches; the oldest
+        // save keeps its id and output
+        while let Some(i) = self
+            .pending
+            .iter()
+            .position(|p| save.start <= p.end && p.start <= save.end)
+        {
+            let other = self.pending.remove(i);
+            save = PendingSave {
+                id: other.id.min(save.id),
+                start: other.start.min(save.start),
+                end: other.end.max(save.end),
+                output: if other.id < save.id {
+                    other.output
+                } else {
+                    save.output
+                },
+                deadline: other.deadline.max(save.deadline),
+            };
+        }
+        if save.id == self.next_id {
+            self.next_id += 1;
+        }
+
+        let result = (save.id, save.output.clone());
+        self.pending.push(save);
+        Ok(result)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn status(&self, ring: &ReplayRing) -> Vec<PendingSaveStatus> {
+        let finished = ring.finished_until().ok().flatten().unwrap_or(0.0);
+        self.pending
+            .iter()
+            .map(|p| PendingSaveStatus {
+                id: p.id,
+                output: p.output.clone(),
+                window_secs: p.end - p.start.max(0.0),
+                remaining_secs: (p.end - finished).max(0.0),
+            })
+            .collect()
+    }
+
+    /// Remove and return saves whose window the ring has finished, or that
+    /// waited past their deadline
+    pub fn take_due(&mut self, ring: &ReplayRing) -> Result<Vec<PendingSave>> {
+        let finished = ring.finished_until()?;
+        let now = Instant::now();
+        let (due, waiting) = self
+            .pending
+            .drain(..)
+            .partition(|p| finished.is_some_and(|f| f >= p.end) || now >= p.deadline);
+        self.pending = waiting;
+        Ok(due)
+    }
+
+    /// Remove every queued save, e.g. to flush them before the ring stops
+    pub fn take_all(&mut self) -> Vec<PendingSave> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ring::tests::{close_now, produce};
+    use std::path::Path;
+
+    /// Ring of 3s segments up to `segments * 3` seconds, the newest closed
+    /// just now
+    fn ring_at(dir: &Path, segments: u64) -> ReplayRing {
+        let ring = ReplayRing::new(dir, 3, 40);
+        let listed = ring.parse_segments().unwrap().len() as u64;
+        produce(&ring, listed..segments, "mkv");
+        close_now(&ring);
+        ring
+    }
+
+    #[test]
+    fn save_waits_until_window_end_is_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = ring_at(dir.path(), 4);
+        let mut saves = PendingSaves::default();
+
+        let (id, output) = saves
+            .request(&ring, 6, 4, PathBuf::from("/r/a.mkv"))
+            .unwrap();
+        assert_eq!((id, output), (0, PathBuf::from("/r/a.mkv")));
+        assert!(saves.take_due(&ring).unwrap().is_empty());
+        let status = saves.status(&ring);
+        assert!((status[0].window_secs - 10.0).abs() < 0.5);
+        assert!((status[0].remaining_secs - 4.0).abs() < 0.5);
+
+        // Two more segments carry the ring past 12 + 4s
+        let ring = ring_at(dir.path(), 6);
+        let due = saves.take_due(&ring).unwrap();
+        assert_eq!(due.len(), 1);
+        assert!((due[0].start - 6.0).abs() < 0.5);
+        assert!((due[0].end - 16.0).abs() < 0.5);
+        assert!(saves.is_empty());
+    }
+
+    #[test]
+    fn overlapping_saves_merge_into_the_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = ring_at(dir.path(), 4);
+        let mut saves = PendingSaves::default();
+
+        saves
+            .request(&ring, 5, 5, PathBuf::from("/r/first.mkv"))
+            .unwrap();
+        let ring = ring_at(dir.path(), 5);
+        let (id, output) = saves
+            .request(&ring, 5, 5, PathBuf::from("/r/second.mkv"))
+            .unwrap();
+        assert_eq!((id, output), (0, PathBuf::from("/r/first.mkv")));
+
+        let status = saves.status(&ring);
+        assert_eq!(status.len(), 1);
+        // 12-5 .. 15+5
+        assert!((status[0].window_secs - 13.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn disjoint_saves_stay_separate() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = ring_at(dir.path(), 4);
+        let mut saves = PendingSaves::default();
+
+        saves
+            .request(&ring, 2, 1, PathBuf::from("/r/a.mkv"))
+            .unwrap();
+        let ring = ring_at(dir.path(), 6);
+        let (id, _) = saves
+            .request(&ring, 2, 1, PathBuf::from("/r/b.mkv"))
+            .unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(saves.status(&ring).len(), 2);
+        assert_eq!(saves.take_all().len(), 2);
+        assert!(saves.is_empty());
+    }
+
+    #[test]
+    fn window_longer_than_the_ring_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        // 38 usable slots of 3s
+        let ring = ring_at(dir.path(), 4);
+        assert_eq!(ring.capacity_secs(), 114);
+        let mut saves = PendingSaves::default();
+
+        let err = saves
+            .request(&ring, 100, 15, PathBuf::from("/r/a.mkv"))
+            .unwrap_err();
+        assert!(err.to_string().contains("114s replay buffer"), "got: {err}");
+        assert!(saves.is_empty());
+        saves
+            .request(&ring, 100, 14, PathBuf::from("/r/a.mkv"))
+            .unwrap();
+    }
+}
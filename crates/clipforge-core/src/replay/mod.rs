@@ -1,5 +1,17 @@
+pub mod budget;
+pub mod daemon;
+pub mod lookahead;
+pub mod partial;
+pub mod pause;
+pub mod record;
 pub mod ring;
 pub mod save;
+pub mod tier;
 
-pub use ring::ReplayRing;
+pub use budget::{RingFit, RingPlan};
+pub use lookahead::{PendingSaveStatus, PendingSaves};
+pub use pause::{AutoPause, PauseChange, PauseReason, SystemSignals};
+pub use record::BufferRecording;
+pub use ring::{InProgressSegment, ReplayRing, ReplaySelection};
 pub use save::save_replay;
+pub use tier::{DiskTier, Migration};
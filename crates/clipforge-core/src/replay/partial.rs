@@ -0,0 +1,187 @@
+//! Reading the segment FFmpeg is still writing.
+//!
+//! The newest footage lives in a file that has not been closed yet. A
+//! snapshot of it is cut back to the last complete Matroska cluster (or
+//! MPEG-TS packet) so it can be concatenated like a finished segment.
+
+use crate::error::{Error, Result};
+use std::path::Path;
+
+const EBML_HEADER_ID: u32 = 0x1A45_DFA3;
+const SEGMENT_ID: u32 = 0x1853_8067;
+const CLUSTER_ID: u32 = 0x1F43_B675;
+const TS_PACKET_SIZE: usize = 188;
+
+/// Length of the prefix of a growing file that demuxes cleanly, or `None`
+/// if it has no complete media data yet
+pub fn complete_prefix_len(data: &[u8], ext: &str) -> Option<usize> {
+    match ext {
+        "ts" => {
+            let len = data.len() - data.len() % TS_PACKET_SIZE;
+            (len > 0).then_some(len)
+        }
+        _ => matroska_prefix_len(data),
+    }
+}
+
+/// Copy the complete part of `src` into `dest`; `false` if there is none
+pub fn snapshot(src: &Path, dest: &Path) -> Result<bool> {
+    let data = std::fs::read(src).map_err(Error::Io)?;
+    let ext = src.extension().and_then(|e| e.to_str()).unwrap_or("mkv");
+    match complete_prefix_len(&data, ext) {
+        Some(len) => {
+            std::fs::write(dest, &data[..len]).map_err(Error::Io)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Walk the top-level elements of the Segment and stop before the first
+/// one that is unsized or runs past the end of the data
+fn matroska_prefix_len(data: &[u8]) -> Option<usize> {
+    let (id, header, size) = read_element(data, 0)?;
+    if id != EBML_HEADER_ID {
+        return None;
+    }
+    let mut pos = header + size?;
+
+    let (id, header, _) = read_element(data, pos)?;
+    if id != SEGMENT_ID {
+        return None;
+    }
+    pos += header;
+
+    let mut complete = None;
+    while let Some((id, header, Some(size))) = read_element(data, pos) {
+        let end = pos + header + size;
+        if end > data.len() {
+            break;
+        }
+        pos = end;
+        if id == CLUSTER_ID {
+            complete = Some(pos);
+        }
+    }
+    complete
+}
+
+/// Element ID, header length and data size (`None` if unknown) at `pos`
+fn read_element(data: &[u8], pos: usize) -> Option<(u32, usize, Option<usize>)> {
+    let (id, id_len) = read_vint(data, pos, true)?;
+    let (size, size_len) = read_vint(data, pos + id_len, false)?;
+    let unknown = size == (1u64 << (7 * size_len)) - 1;
+    let size = if unknown {
+        None
+    } else {
+        Some(usize::try_from(size).ok()?)
+    };
+    Some((id as u32, id_len + size_len, size))
+}
+
+/// EBML variable-length integer; IDs keep their length marker bit
+fn read_vint(data: &[u8], pos: usize, keep_marker: bool) -> Option<(u64, usize)> {
+    let first = *data.get(pos)?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || (keep_marker && len > 4) {
+        return None;
+    }
+    let bytes = data.get(pos..pos + len)?;
+    let mut value = if keep_marker {
+        first as u64
+    } else {
+        (first as u64) & (0xFF >> len)
+    };
+    for b in &bytes[1..] {
+        value = (value << 8) | *b as u64;
+    }
+    Some((value, len))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn element(id: &[u8], body: &[u8]) -> Vec<u8> {
+        assert!(body.len() < 0x7F);
+        let mut out = id.to_vec();
+        out.push(0x80 | body.len() as u8);
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// A Matroska file with `clusters` complete clusters followed by a
+    /// cluster cut off mid-write
+    pub(crate) fn growing_mkv(clusters: usize) -> (Vec<u8>, usize) {
+        let mut data = element(&[0x1A, 0x45, 0xDF, 0xA3], &[0x42, 0x86, 0x81, 0x01]);
+        // Segment of unknown size, as written by a live muxer
+        data.extend_from_slice(&[
+            0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ]);
+        data.extend(element(
+            &[0x15, 0x49, 0xA9, 0x66],
+            &[0x2A, 0xD7, 0xB1, 0x83, 0x0F, 0x42, 0x40],
+        ));
+        data.extend(element(
+            &[0x16, 0x54, 0xAE, 0x6B],
+            &[0xAE, 0x83, 0xD7, 0x81, 0x01],
+        ));
+        for i in 0..clusters {
+            data.extend(element(
+                &[0x1F, 0x43, 0xB6, 0x75],
+                &[0xE7, 0x81, i as u8, 0xA3, 0x82, 0x00, 0x01],
+            ));
+        }
+        let complete = data.len();
+        let partial = element(
+            &[0x1F, 0x43, 0xB6, 0x75],
+            &[0xE7, 0x81, 0x09, 0xA3, 0x8A, 0x00],
+        );
+        data.extend_from_slice(&partial[..partial.len() - 2]);
+        (data, complete)
+    }
+
+    #[test]
+    fn matroska_is_cut_after_last_complete_cluster() {
+        let (data, complete) = growing_mkv(3);
+        assert_eq!(complete_prefix_len(&data, "mkv"), Some(complete));
+        assert_eq!(
+            complete_prefix_len(&data[..complete], "mkv"),
+            Some(complete)
+        );
+    }
+
+    #[test]
+    fn matroska_without_complete_cluster_is_unusable() {
+        let (data, _) = growing_mkv(0);
+        assert_eq!(complete_prefix_len(&data, "mkv"), None);
+        assert_eq!(complete_prefix_len(b"not matroska", "mkv"), None);
+    }
+
+    #[test]
+    fn unsized_cluster_stops_the_walk() {
+        let (mut data, complete) = growing_mkv(2);
+        data.truncate(complete);
+        data.extend_from_slice(&[0x1F, 0x43, 0xB6, 0x75, 0xFF, 0xE7, 0x81, 0x05]);
+        assert_eq!(complete_prefix_len(&data, "mkv"), Some(complete));
+    }
+
+    #[test]
+    fn mpegts_is_cut_to_whole_packets() {
+        let data = vec![0x47; TS_PACKET_SIZE * 5 + 100];
+        assert_eq!(complete_prefix_len(&data, "ts"), Some(TS_PACKET_SIZE * 5));
+        assert_eq!(complete_prefix_len(&data[..100], "ts"), None);
+    }
+
+    #[test]
+    fn snapshot_writes_complete_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let (data, complete) = growing_mkv(1);
+        let src = dir.path().join("seg_004.mkv");
+        let dest = dir.path().join("snapshot.mkv");
+        std::fs::write(&src, &data).unwrap();
+
+        assert!(snapshot(&src, &dest).unwrap());
+        assert_eq!(std::fs::read(&dest).unwrap().len(), complete);
+    }
+}
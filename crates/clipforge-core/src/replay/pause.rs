@@ -0,0 +1,451 @@
+//! Pausing the replay buffer while nobody is playing.
+//!
+//! A `SignalSource` samples the desktop (lock state, fullscreen window,
+//! running games, input idle time) and `AutoPause` turns the samples into
+//! pause and resume decisions. A condition has to hold for `PAUSE_GRACE`
+//! before the buffer pauses, so alt-tabbing out of a game doesn't restart
+//! the capture; it resumes as soon as no condition holds.
+
+use crate::capture::x11;
+use crate::config::AutoPauseConfig;
+use crate::runner::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// How often the desktop should be sampled
+pub const PAUSE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a condition must hold before the buffer pauses
+pub const PAUSE_GRACE: Duration = Duration::from_secs(10);
+
+/// Longest process name the kernel keeps in `/proc/<pid>/comm`
+const COMM_LEN: usize = 15;
+
+/// Why the buffer is paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseReason {
+    Locked,
+    ScreenBlanked,
+    Idle,
+    NoGameRunning,
+    NotFullscreen,
+}
+
+impl PauseReason {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Locked => "screen locked",
+            Self::ScreenBlanked => "screen blanked",
+            Self::Idle => "no input",
+            Self::NoGameRunning => "no game running",
+            Self::NotFullscreen => "no fullscreen window",
+        }
+    }
+}
+
+/// One sample of the desktop. `None` means the signal was not checked or
+/// could not be read; unknown signals never pause the buffer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DesktopSignals {
+    pub locked: Option<bool>,
+    pub screen_blanked: Option<bool>,
+    pub fullscreen: Option<bool>,
+    pub game_running: Option<bool>,
+    /// Time since the last keyboard or mouse input
+    pub idle: Option<Duration>,
+}
+
+/// Where desktop samples come from; tests script their own
+pub trait SignalSource: Send + Sync {
+    /// Sample the signals `config` has conditions for
+    fn sample<'a>(&'a self, config: &'a AutoPauseConfig) -> BoxFuture<'a, DesktopSignals>;
+}
+
+/// Samples the running session through loginctl, X11 tools and `/proc`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemSignals;
+
+impl SignalSource for SystemSignals {
+    fn sample<'a>(&'a self, config: &'a AutoPauseConfig) -> BoxFuture<'a, DesktopSignals> {
+        Box::pin(async move {
+            let mut signals = DesktopSignals::default();
+            if config.when_locked {
+                signals.locked = session_locked().await;
+                signals.screen_blanked = x11::screen_blanked().await;
+            }
+            if config.require_fullscreen {
+                signals.fullscreen = x11::active_window_fullscreen().await;
+            }
+            if !config.games.is_empty() {
+                signals.game_running = any_process_running(Path::new("/proc"), &config.games);
+            }
+            if config.idle_minutes > 0 {
+                signals.idle = x11::idle_time().await;
+            }
+            signals
+        })
+    }
+}
+
+/// A change in whether the buffer should run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseChange {
+    Paused(PauseReason),
+    Resumed,
+}
+
+/// Decides from desktop samples when the buffer pauses and resumes
+#[derive(Debug, Clone, Default)]
+pub struct AutoPause {
+    config: AutoPauseConfig,
+    paused: Option<PauseReason>,
+    /// When a pause condition started holding, while not yet paused
+    holding_since: Option<Instant>,
+}
+
+impl AutoPause {
+    pub fn new(config: AutoPauseConfig) -> Self {
+        Self {
+            config,
+            paused: None,
+            holding_since: None,
+        }
+    }
+
+    pub fn config(&self) -> &AutoPauseConfig {
+        &self.config
+    }
+
+    /// Apply changed conditions from the next sample on
+    pub fn set_config(&mut self, config: AutoPauseConfig) {
+        self.config = config;
+    }
+
+    /// Why the buffer is paused, if it is
+    pub fn paused(&self) -> Option<PauseReason> {
+        self.paused
+    }
+
+    /// The first configured condition that holds in `signals`
+    pub fn reason(&self, signals: &DesktopSignals) -> Option<PauseReason> {
+        let config = &self.config;
+        let idle_limit = Duration::from_secs(config.idle_minutes as u64 * 60);
+        if config.when_locked && signals.locked == Some(true) {
+            Some(PauseReason::Locked)
+        } else if config.when_locked && signals.screen_blanked == Some(true) {
+            Some(PauseReason::ScreenBlanked)
+        } else if config.idle_minutes > 0 && signals.idle.is_some_and(|i| i >= idle_limit) {
+            Some(PauseReason::Idle)
+        } else if !config.games.is_empty() && signals.game_running == Some(false) {
+            Some(PauseReason::NoGameRunning)
+        } else if config.require_fullscreen && signals.fullscreen == Some(false) {
+            Some(PauseReason::NotFullscreen)
+        } else {
+            None
+        }
+    }
+
+    /// Take a sample taken at `now` into account; returns a change when the
+    /// buffer should pause or resume
+    pub fn update(&mut self, signals: &DesktopSignals, now: Instant) -> Option<PauseChange> {
+        match (self.reason(signals), self.paused) {
+            (None, None) => {
+                self.holding_since = None;
+                None
+            }
+            (None, Some(_)) => {
+                self.paused = None;
+                Some(PauseChange::Resumed)
+            }
+            // Stay paused, reporting whichever condition holds now
+            (Some(reason), Some(_)) => {
+                self.paused = Some(reason);
+                None
+            }
+            (Some(reason), None) => {
+                let since = *self.holding_since.get_or_insert(now);
+                if now.duration_since(since) < PAUSE_GRACE {
+                    return None;
+                }
+                self.holding_since = None;
+                self.paused = Some(reason);
+                Some(PauseChange::Paused(reason))
+            }
+        }
+    }
+
+    /// Sample `source` and update with the result
+    pub async fn poll(&mut self, source: &dyn SignalSource) -> Option<PauseChange> {
+        let signals = source.sample(&self.config).await;
+        self.update(&signals, Instant::now())
+    }
+}
+
+/// Whether the login session is locked, via `loginctl`
+async fn session_locked() -> Option<bool> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".to_string());
+    let output = Command::new("loginctl")
+        .args(["show-session", &session, "-p", "LockedHint", "--value"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether a process named like one of `names` runs, scanning `proc_dir`.
+/// Names match the kernel's (truncated) process name or the executable's
+/// file name, ignoring case.
+pub fn any_process_running(proc_dir: &Path, names: &[String]) -> Option<bool> {
+    let names: Vec<String> = names.iter().map(|n| n.to_lowercase()).collect();
+    let entries = std::fs::read_dir(proc_dir).ok()?;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let dir = entry.path();
+        let is_pid = dir
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().bytes().all(|b| b.is_ascii_digit()));
+        if !is_pid {
+            continue;
+        }
+
+        let comm = std::fs::read_to_string(dir.join("comm"))
+            .map(|c| c.trim().to_lowercase())
+            .unwrap_or_default();
+        // argv[0] may be a Windows path under Wine/Proton
+        let exe = std::fs::read(dir.join("cmdline"))
+            .ok()
+            .and_then(|c| {
+                let argv0 = c.split(|b| *b == 0).next()?.to_vec();
+                let argv0 = String::from_utf8_lossy(&argv0).to_lowercase();
+                argv0.rsplit(['/', '\\']).next().map(str::to_string)
+            })
+            .unwrap_or_default();
+
+        let matches = |name: &String| {
+            let truncated: String = name.chars().take(COMM_LEN).collect();
+            (!comm.is_empty() && comm == truncated) || (!exe.is_empty() && exe == *name)
+        };
+        if names.iter().any(matches) {
+            return Some(true);
+        }
+    }
+    Some(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn config() -> AutoPauseConfig {
+        AutoPauseConfig {
+            when_locked: true,
+            require_fullscreen: true,
+            games: vec!["cs2".to_string()],
+            idle_minutes: 5,
+        }
+    }
+
+    /// Signals for someone playing: nothing would pause the buffer
+    fn playing() -> DesktopSignals {
+        DesktopSignals {
+            locked: Some(false),
+            screen_blanked: Some(false),
+            fullscreen: Some(true),
+            game_running: Some(true),
+            idle: Some(Duration::from_secs(3)),
+        }
+    }
+
+    #[test]
+    fn each_condition_has_a_reason() {
+        let pause = AutoPause::new(config());
+        assert_eq!(pause.reason(&playing()), None);
+
+        let cases = [
+            (
+                DesktopSignals {
+                    locked: Some(true),
+                    ..playing()
+                },
+                PauseReason::Locked,
+            ),
+            (
+                DesktopSignals {
+                    screen_blanked: Some(true),
+                    ..playing()
+                },
+                PauseReason::ScreenBlanked,
+            ),
+            (
+                DesktopSignals {
+                    idle: Some(Duration::from_secs(300)),
+                    ..playing()
+                },
+                PauseReason::Idle,
+            ),
+            (
+                DesktopSignals {
+                    game_running: Some(false),
+                    ..playing()
+                },
+                PauseReason::NoGameRunning,
+            ),
+            (
+                DesktopSignals {
+                    fullscreen: Some(false),
+                    ..playing()
+                },
+                PauseReason::NotFullscreen,
+            ),
+        ];
+        for (signals, reason) in cases {
+            assert_eq!(pause.reason(&signals), Some(reason));
+        }
+    }
+
+    #[test]
+    fn unknown_or_unconfigured_signals_never_pause() {
+        let pause = AutoPause::new(config());
+        assert_eq!(pause.reason(&DesktopSignals::default()), None);
+
+        let off = AutoPause::new(AutoPauseConfig::default());
+        let away = DesktopSignals {
+            locked: Some(true),
+            fullscreen: Some(false),
+            game_running: Some(false),
+            idle: Some(Duration::from_secs(3600)),
+            ..playing()
+        };
+        assert_eq!(off.reason(&away), None);
+    }
+
+    #[test]
+    fn pauses_after_grace_and_resumes_at_once() {
+        let mut pause = AutoPause::new(config());
+        let start = Instant::now();
+        let browsing = DesktopSignals {
+            fullscreen: Some(false),
+            ..playing()
+        };
+
+        assert_eq!(pause.update(&browsing, start), None);
+        assert_eq!(pause.update(&browsing, start + PAUSE_GRACE / 2), None);
+        assert_eq!(
+            pause.update(&browsing, start + PAUSE_GRACE),
+            Some(PauseChange::Paused(PauseReason::NotFullscreen))
+        );
+        assert_eq!(pause.paused(), Some(PauseReason::NotFullscreen));
+
+        // Locking while paused only changes the reason
+        let locked = DesktopSignals {
+            locked: Some(true),
+            ..browsing
+        };
+        assert_eq!(pause.update(&locked, start + PAUSE_GRACE * 2), None);
+        assert_eq!(pause.paused(), Some(PauseReason::Locked));
+
+        assert_eq!(
+            pause.update(&playing(), start + PAUSE_GRACE * 3),
+            Some(PauseChange::Resumed)
+        );
+        assert_eq!(pause.paused(), None);
+    }
+
+    #[test]
+    fn brief_conditions_restart_the_grace_period() {
+        let mut pause = AutoPause::new(config());
+        let start = Instant::now();
+        let tabbed_out = DesktopSignals {
+            fullscreen: Some(false),
+            ..playing()
+        };
+
+        pause.update(&tabbed_out, start);
+        pause.update(&playing(), start + PAUSE_GRACE / 2);
+        assert_eq!(pause.update(&tabbed_out, start + PAUSE_GRACE), None);
+        assert_eq!(pause.paused(), None);
+    }
+
+    /// Replays scripted samples, recording the config it was asked with
+    struct Scripted {
+        samples: Mutex<Vec<DesktopSignals>>,
+        asked: Mutex<Vec<AutoPauseConfig>>,
+    }
+
+    impl SignalSource for Scripted {
+        fn sample<'a>(&'a self, config: &'a AutoPauseConfig) -> BoxFuture<'a, DesktopSignals> {
+            self.asked.lock().unwrap().push(config.clone());
+            let next = self.samples.lock().unwrap().remove(0);
+            Box::pin(async move { next })
+        }
+    }
+
+    #[tokio::test]
+    async fn poll_reads_injected_signals() {
+        let source = Scripted {
+            samples: Mutex::new(vec![playing(), DesktopSignals::default()]),
+            asked: Mutex::new(Vec::new()),
+        };
+        let mut pause = AutoPause::new(config());
+
+        assert_eq!(pause.poll(&source).await, None);
+        assert_eq!(pause.poll(&source).await, None);
+        assert_eq!(source.asked.lock().unwrap()[0], config());
+    }
+
+    fn fake_process(proc_dir: &Path, pid: u32, comm: &str, argv0: &str) {
+        let dir = proc_dir.join(pid.to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("comm"), format!("{comm}\n")).unwrap();
+        std::fs::write(dir.join("cmdline"), format!("{argv0}\0--flag\0")).unwrap();
+    }
+
+    #[test]
+    fn games_match_process_or_executable_name() {
+        let proc_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(proc_dir.path().join("self")).unwrap();
+        fake_process(proc_dir.path(), 100, "firefox", "/usr/lib/firefox/firefox");
+        let names = |n: &[&str]| n.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            any_process_running(proc_dir.path(), &names(&["cs2"])),
+            Some(false)
+        );
+
+        // Long names are truncated in comm; Wine shows a Windows path
+        fake_process(
+            proc_dir.path(),
+            200,
+            "EldenRing.exe",
+            "Z:\\Games\\ELDEN RING\\Game\\eldenring.exe",
+        );
+        fake_process(
+            proc_dir.path(),
+            300,
+            "HorizonZeroDawn",
+            "/games/HorizonZeroDawnRemastered",
+        );
+        assert_eq!(
+            any_process_running(proc_dir.path(), &names(&["eldenring.exe"])),
+            Some(true)
+        );
+        assert_eq!(
+            any_process_running(proc_dir.path(), &names(&["HorizonZeroDawnRemastered"])),
+            Some(true)
+        );
+        assert_eq!(
+            any_process_running(&proc_dir.path().join("missing"), &names(&["cs2"])),
+            None
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Turning the replay buffer into a full recording.
+//!
+//! FFmpeg reuses ring slots once `-segment_wrap` comes around, so segments
+//! that belong to the recording are copied out of the ring as soon as they
+//! are finished. On stop the copies and the in-progress segment are joined
+//! into one file.
+
+use crate::error::{Error, Result};
+use crate::replay::ring::{copy_segment_blocking, ReplayRing, SegmentEntry};
+use crate::replay::save::{concat_segments, snapshot_in_progress};
+use crate::runner::FfmpegRunner;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How often finished segments should be collected. Well inside the time a
+/// slot survives in any ring, which is at least two segments.
+pub const COLLECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What one `collect` picked up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Collected {
+    /// Segments copied out of the ring
+    pub copied: usize,
+    /// Segments overwritten before they could be copied, leaving a gap in
+    /// the recording
+    pub missed: u64,
+}
+
+/// A recording that started with the footage already in the replay ring
+pub struct BufferRecording {
+    output: PathBuf,
+    /// Where segments are kept safe from wraparound until the final join
+    work_dir: PathBuf,
+    /// Copied segments, oldest first; filenames are relative to `work_dir`
+    segments: Vec<SegmentEntry>,
+    /// First ring sequence not yet collected
+    next_sequence: u64,
+    /// Segments overwritten before they could be copied
+    missed: u64,
+}
+
+impl BufferRecording {
+    /// Start a recording to `output` from everything the ring holds now
+    pub async fn start(ring: &ReplayRing, output: &Path) -> Result<Self> {
+        let work_dir = output.with_extension("segments");
+        std::fs::create_dir_all(&work_dir).map_err(Error::Io)?;
+
+        // Read before the live set so a segment closing in between is
+        // still picked up by the next collect
+        let listed = ring.parse_segments()?.len() as u64;
+        let mut recording = Self {
+            output: output.to_path_buf(),
+            work_dir,
+            segments: Vec::new(),
+            next_sequence: listed,
+            missed: 0,
+        };
+
+        // Oldest first: those slots are the next to be reused
+        for entry in ring.live_segments()? {
+            match recording.copy_out(ring, &entry).await? {
+                Some(copied) => recording.segments.push(copied),
+                None => {
+                    // Anything older would leave a hole before this segment
+                    recording.clear_copies();
+                }
+            }
+            recording.next_sequence = recording.next_sequence.max(entry.sequence + 1);
+        }
+
+        info!(
+            output = %recording.output.display(),
+            from_buffer = recording.duration(),
+            "recording from replay buffer"
+        );
+        Ok(recording)
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Seconds of footage collected so far
+    pub fn duration(&self) -> f64 {
+        match (self.segments.first(), self.segments.last()) {
+            (Some(first), Some(last)) => last.end_time - first.start_time,
+            _ => 0.0,
+        }
+    }
+
+    /// Segments lost to wraparound because collecting fell behind
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Copy out segments finished since the last call
+    pub async fn collect(&mut self, ring: &ReplayRing) -> Result<Collected> {
+        let mut collected = Collected::default();
+        for entry in ring.live_segments()? {
+            if entry.sequence < self.next_sequence {
+                continue;
+            }
+            collected.missed += entry.sequence - self.next_sequence;
+            self.next_sequence = entry.sequence + 1;
+            match self.copy_out(ring, &entry).await? {
+                Some(segment) => {
+                    self.segments.push(segment);
+                    collected.copied += 1;
+                }
+                None => collected.missed += 1,
+            }
+        }
+        if collected.missed > 0 {
+            warn!(
+                missed = collected.missed,
+                "replay segments overwritten before they were recorded"
+            );
+            self.missed += collected.missed;
+        }
+        Ok(collected)
+    }
+
+    /// Collect the remaining footage, including the segment being written,
+    /// and join it into the output file.
+    ///
+    /// The copied segments are kept if joining fails.
+    pub async fn finish(mut self, runner: &dyn FfmpegRunner, ring: &ReplayRing) -> Result<PathBuf> {
+        self.collect(ring).await?;
+        let tail = snapshot_in_progress(runner, ring).await.unwrap_or_else(|e| {
+            warn!(error = %e, "could not read in-progress segment, ending at the last finished one");
+            None
+        });
+        if let Some(tail) = tail {
+            let snapshot = ring.segment_dir.join(&tail.filename);
+            if tail.sequence == self.next_sequence {
+                if let Some(copied) = self.copy_out(ring, &tail).await? {
+                    self.segments.push(copied);
+                }
+            }
+            let _ = std::fs::remove_file(snapshot);
+        }
+
+        if self.segments.is_empty() {
+            self.discard();
+            return Err(Error::NoSegments);
+        }
+        let paths: Vec<PathBuf> = self
+            .segments
+            .iter()
+            .map(|s| self.work_dir.join(&s.filename))
+            .collect();
+        concat_segments(runner, &self.work_dir, &paths, &self.output).await?;
+        let _ = std::fs::remove_dir_all(&self.work_dir);
+
+        info!(
+            output = %self.output.display(),
+            segments = paths.len(),
+            duration = self.duration(),
+            missed = self.missed,
+            "recording from replay buffer saved"
+        );
+        Ok(self.output)
+    }
+
+    /// Abandon the recording and delete what was collected
+    pub fn discard(self) {
+        let _ = std::fs::remove_dir_all(&self.work_dir);
+    }
+
+    /// Copy a ring segment into the work dir; `None` if FFmpeg reused its
+    /// slot before or during the copy
+    async fn copy_out(
+        &self,
+        ring: &ReplayRing,
+        entry: &SegmentEntry,
+    ) -> Result<Option<SegmentEntry>> {
+        let src = ring.segment_path(entry);
+        let ext = src.extension().and_then(|e| e.to_str()).unwrap_or("mkv");
+        let filename = format!("seg_{:06}.{ext}", entry.sequence);
+        if !copy_segment_blocking(src, self.work_dir.join(&filename)).await? {
+            return Ok(None);
+        }
+
+        Ok(Some(SegmentEntry {
+            filename,
+            ..entry.clone()
+        }))
+    }
+
+    fn clear_copies(&mut self) {
+        for segment in self.segments.drain(..) {
+            let _ = std::fs::remove_file(self.work_dir.join(&segment.filename));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ring::tests::produce;
+    use crate::runner::fake::{FakeResponse, FakeRunner};
+    use crate::runner::Tool;
+
+    /// Concat joins the contents of the listed files
+    fn joining_runner() -> FakeRunner {
+        FakeRunner::new().on_with(Tool::Ffmpeg, "concat", |args| {
+            let list = args.iter().skip_while(|a| *a != "-i").nth(1).unwrap();
+            let joined: String = std::fs::read_to_string(list)
+                .unwrap()
+                .lines()
+                .map(|l| l.trim_start_matches("file '").trim_end_matches('\''))
+                .map(|f| std::fs::read_to_string(f).unwrap())
+                .collect();
+            FakeResponse::ok().writes(joined)
+        })
+    }
+
+    #[tokio::test]
+    async fn buffered_segments_survive_wraparound() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(ring_dir.path(), 3, 4);
+        produce(&ring, 0..3, "mkv");
+
+        let output = out_dir.path().join("recording.mkv");
+        let mut recording = BufferRecording::start(&ring, &output).await.unwrap();
+        assert!((recording.duration() - 9.0).abs() < 1e-6);
+
+        // The ring wraps over every slot the recording started from
+        for seq in 3..7 {
+            produce(&ring, seq..seq + 1, "mkv");
+            recording.collect(&ring).await.unwrap();
+        }
+        assert_eq!(recording.missed(), 0);
+
+        let runner = joining_runner();
+        let saved = recording.finish(&runner, &ring).await.unwrap();
+        assert_eq!(saved, output);
+        let joined = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(joined, "seq 0;seq 1;seq 2;seq 3;seq 4;seq 5;seq 6;");
+        assert!(!output.with_extension("segments").exists());
+    }
+
+    #[tokio::test]
+    async fn collecting_late_counts_overwritten_segments() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(ring_dir.path(), 3, 4);
+        produce(&ring, 0..2, "mkv");
+
+        let output = out_dir.path().join("recording.mkv");
+        let mut recording = BufferRecording::start(&ring, &output).await.unwrap();
+        // Seven more segments through four slots before the next collect
+        produce(&ring, 2..9, "mkv");
+        let collected = recording.collect(&ring).await.unwrap();
+        assert_eq!(
+            collected,
+            Collected {
+                copied: 4,
+                missed: 3
+            }
+        );
+        assert_eq!(recording.missed(), 3);
+
+        let runner = joining_runner();
+        recording.finish(&runner, &ring).await.unwrap();
+        let joined = std::fs::read_to_string(&output).unwrap();
+        assert!(joined.starts_with("seq 0;seq 1;seq 5;"), "got: {joined}");
+    }
+
+    #[tokio::test]
+    async fn failed_join_keeps_collected_segments() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(ring_dir.path(), 3, 4);
+        produce(&ring, 0..2, "mkv");
+
+        let output = out_dir.path().join("recording.mkv");
+        let recording = BufferRecording::start(&ring, &output).await.unwrap();
+        let runner = FakeRunner::new().on(
+            Tool::Ffmpeg,
+            "concat",
+            FakeResponse::failing(1, "disk full"),
+        );
+
+        assert!(recording.finish(&runner, &ring).await.is_err());
+        let kept = std::fs::read_dir(output.with_extension("segments"))
+            .unwrap()
+            .count();
+        assert_eq!(kept, 2);
+    }
+
+    #[tokio::test]
+    async fn empty_buffer_cannot_be_recorded() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let out_dir = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(ring_dir.path(), 3, 4);
+
+        let output = out_dir.path().join("recording.mkv");
+        let recording = BufferRecording::start(&ring, &output).await.unwrap();
+        let err = recording
+            .finish(&FakeRunner::new(), &ring)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NoSegments));
+        assert!(!output.with_extension("segments").exists());
+    }
+}
@@ -1,103 +1,599 @@
+use crate::config::{Config, RING_HEADROOM_SEGMENTS};
 use crate::error::{Error, Result};
+use crate::replay::tier::DiskTier;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::sync::OwnedRwLockReadGuard;
 use tracing::debug;
 
+/// Timestamps closer than this are treated as the same instant
+const BOUNDARY_EPSILON: f64 = 0.001;
+
+/// Largest hole between consecutive segments still treated as contiguous
+const MAX_SEGMENT_GAP: f64 = 0.5;
+
 /// Manages the circular segment buffer for instant replay
+#[derive(Clone)]
 pub struct ReplayRing {
     pub segment_dir: PathBuf,
     pub segment_list: PathBuf,
+    /// Segments of captures before the last resume; FFmpeg starts
+    /// `segment_list` afresh every time it starts
+    pub earlier_list: PathBuf,
     pub segment_time: u32,
     pub max_segments: u32,
+    /// Where footage older than the cache dir holds is kept, if tiered
+    pub disk: Option<DiskTier>,
+    /// Encoder args for clips that cannot be cut on a keyframe, matching the
+    /// capture (see `build_clip_encoding`); software x264 when unset
+    pub clip_encoding: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct SegmentEntry {
+    /// Position in the segment list; increases monotonically even when
+    /// `-segment_wrap` reuses filenames
+    pub sequence: u64,
+    /// Relative to the ring's segment dir; absolute for disk tier copies
     pub filename: String,
     pub start_time: f64,
     pub end_time: f64,
 }
 
+/// The segment FFmpeg is writing right now, not yet in the segment list
+#[derive(Debug, Clone, PartialEq)]
+pub struct InProgressSegment {
+    pub sequence: u64,
+    pub path: PathBuf,
+    pub start_time: f64,
+}
+
+/// Segments covering a requested span, and where in them the span starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplaySelection {
+    pub segments: Vec<PathBuf>,
+    /// Offset into the concatenated segments at which the clip begins
+    pub trim_start: f64,
+    /// Length of the clip in seconds (less than requested if the buffer is short)
+    pub duration: f64,
+    /// Footage after the clip's end in the last segment
+    pub trim_end: f64,
+}
+
+impl ReplaySelection {
+    /// Whether the clip is the whole concatenation and needs no trimming
+    pub fn is_whole(&self) -> bool {
+        self.trim_start < BOUNDARY_EPSILON && self.trim_end < BOUNDARY_EPSILON
+    }
+}
+
 impl ReplayRing {
     pub fn new(segment_dir: &Path, segment_time: u32, max_segments: u32) -> Self {
         Self {
             segment_dir: segment_dir.to_path_buf(),
             segment_list: segment_dir.join("segments.csv"),
+            earlier_list: segment_dir.join("segments.earlier.csv"),
             segment_time,
             max_segments,
+            disk: None,
+            clip_encoding: None,
         }
     }
 
-    /// Parse FFmpeg's segment list CSV to get current segments
-    pub fn parse_segments(&self) -> Result<Vec<SegmentEntry>> {
-        if !self.segment_list.exists() {
-            return Ok(Vec::new());
+    /// Ring laid out as configured, with a disk tier when `ram_secs` is set
+    pub fn for_config(config: &Config) -> Self {
+        let replay = &config.replay;
+        let ring = Self::new(
+            &config.paths.replay_cache_dir,
+            replay.segment_secs,
+            replay.segment_count(),
+        );
+        if !replay.is_tiered() {
+            return ring;
         }
+        let budget_mb = replay.disk_budget_mb;
+        ring.with_disk_tier(DiskTier::new(
+            &config.paths.replay_disk_dir,
+            replay.duration_secs,
+            (budget_mb > 0).then_some(budget_mb * 1024 * 1024),
+        ))
+    }
+
+    pub fn with_disk_tier(mut self, disk: DiskTier) -> Self {
+        self.disk = Some(disk);
+        self
+    }
 
-        let content = std::fs::read_to_string(&self.segment_list).map_err(Error::Io)?;
+    pub fn with_clip_encoding(mut self, args: Vec<String>) -> Self {
+        self.clip_encoding = Some(args);
+        self
+    }
+
+    /// Seconds of footage the ring is sized to keep: the disk tier's span
+    /// when tiered, the cache slots less headroom otherwise
+    pub fn capacity_secs(&self) -> u32 {
+        match &self.disk {
+            Some(disk) => disk.keep_secs,
+            None => self.max_segments.saturating_sub(RING_HEADROOM_SEGMENTS) * self.segment_time,
+        }
+    }
+
+    /// Location of a segment's file, in either tier
+    pub fn segment_path(&self, entry: &SegmentEntry) -> PathBuf {
+        self.segment_dir.join(&entry.filename)
+    }
+
+    /// Keep the disk tier's copies from expiring while a save reads them;
+    /// `None` without a disk tier
+    pub async fn pin_disk(&self) -> Option<OwnedRwLockReadGuard<()>> {
+        match self.disk {
+            Some(ref disk) => Some(disk.pin().await),
+            None => None,
+        }
+    }
+
+    /// Directory for temporary files as large as a save. The cache dir is
+    /// only sized for the RAM tier, so a tiered ring uses the disk tier.
+    pub fn scratch_dir(&self) -> &Path {
+        self.disk.as_ref().map_or(&self.segment_dir, |d| &d.dir)
+    }
+
+    /// Parse FFmpeg's segment list CSV to get current segments, after
+    /// those of earlier captures.
+    ///
+    /// A resumed capture's timestamps start over, so its segments are moved
+    /// to follow on from the earlier ones: the pause is cut out of the
+    /// ring's timeline.
+    pub fn parse_segments(&self) -> Result<Vec<SegmentEntry>> {
         let mut segments = Vec::new();
+        for (filename, start_time, end_time) in read_segment_list(&self.earlier_list)? {
+            segments.push(SegmentEntry {
+                sequence: segments.len() as u64,
+                filename,
+                start_time,
+                end_time,
+            });
+        }
 
-        for line in content.lines() {
-            let parts: Vec<&str> = line.split(',').collect();
-            if parts.len() >= 3 {
-                let filename = parts[0].trim().to_string();
-                let start_time: f64 = parts[1].trim().parse().unwrap_or(0.0);
-                let end_time: f64 = parts[2].trim().parse().unwrap_or(0.0);
-
-                segments.push(SegmentEntry {
-                    filename,
-                    start_time,
-                    end_time,
-                });
-            }
+        let current = read_segment_list(&self.segment_list)?;
+        let offset = match (segments.last(), current.first()) {
+            (Some(last), Some((_, first_start, _))) => last.end_time - first_start,
+            _ => 0.0,
+        };
+        for (filename, start_time, end_time) in current {
+            segments.push(SegmentEntry {
+                sequence: segments.len() as u64,
+                filename,
+                start_time: start_time + offset,
+                end_time: end_time + offset,
+            });
         }
 
         Ok(segments)
     }
 
-    /// Get segments covering the last N seconds, handling ring buffer wrap
-    pub fn get_last_n_seconds(&self, seconds: u32) -> Result<Vec<PathBuf>> {
+    /// Keep the ring's segments for a capture resuming after a pause.
+    /// Returns the number FFmpeg should give its first segment (see
+    /// `-segment_start_number`), so it carries on through the slots instead
+    /// of overwriting the newest ones.
+    pub fn resume(&self) -> Result<u64> {
         let segments = self.parse_segments()?;
-        if segments.is_empty() {
-            return Err(Error::NoSegments);
+        let content: String = segments
+            .iter()
+            .map(|s| format!("{},{:.6},{:.6}\n", s.filename, s.start_time, s.end_time))
+            .collect();
+        let tmp = self.earlier_list.with_extension("csv.tmp");
+        std::fs::write(&tmp, content).map_err(Error::Io)?;
+        std::fs::rename(&tmp, &self.earlier_list).map_err(Error::Io)?;
+        match std::fs::remove_file(&self.segment_list) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(Error::Io(e)),
+            _ => {}
         }
+        Ok(segments.len() as u64)
+    }
 
-        let needed_segments = (seconds / self.segment_time).max(1) as usize;
-        let total = segments.len();
+    /// Segments of the whole buffer, oldest first: the disk tier's copies
+    /// of footage no longer in the cache dir, followed by the cache dir's
+    /// own segments. Stops at the first hole in the timeline.
+    pub fn live_segments(&self) -> Result<Vec<SegmentEntry>> {
+        let mut live = self.ram_segments()?;
+        let (Some(disk), Some(first)) = (&self.disk, live.first()) else {
+            return Ok(live);
+        };
+
+        let mut older = Vec::new();
+        let mut next_start = first.start_time;
+        for entry in disk
+            .segments()?
+            .into_iter()
+            .rev()
+            .filter(|e| e.sequence < first.sequence)
+        {
+            let gap = next_start - entry.end_time;
+            if !(-BOUNDARY_EPSILON..=MAX_SEGMENT_GAP).contains(&gap) {
+                break;
+            }
+            next_start = entry.start_time;
+            older.push(entry);
+        }
+        older.reverse();
+        older.append(&mut live);
+        Ok(older)
+    }
 
-        // Take the last N segments (they're in chronological order in the CSV)
-        let start_idx = total.saturating_sub(needed_segments);
-        let selected: Vec<PathBuf> = segments[start_idx..]
+    /// Copy finished segments into the disk tier before FFmpeg reuses their
+    /// slots; returns how many were copied. Call at least every
+    /// `COLLECT_INTERVAL` while the buffer runs.
+    pub fn migrate(&self) -> Result<usize> {
+        match self.disk {
+            Some(ref disk) => disk.migrate(&self.segment_dir, &self.ram_segments()?),
+            None => Ok(0),
+        }
+    }
+
+    /// Segments in the cache dir whose files still hold the listed
+    /// footage, oldest first.
+    ///
+    /// With `-segment_wrap` the list keeps growing while filenames are
+    /// reused, so only the latest entry per file can be valid. A file
+    /// modified after a newer segment was closed has since been overwritten
+    /// (usually by the segment being recorded now). The result is the
+    /// newest contiguous run, so a save never spans a hole.
+    fn ram_segments(&self) -> Result<Vec<SegmentEntry>> {
+        let entries = self.parse_segments()?;
+
+        let mut latest: HashMap<&str, u64> = HashMap::new();
+        for entry in &entries {
+            latest.insert(&entry.filename, entry.sequence);
+        }
+        let current: Vec<&SegmentEntry> = entries
             .iter()
-            .map(|s| self.segment_dir.join(&s.filename))
-            .filter(|p| p.exists())
+            .filter(|e| latest.get(e.filename.as_str()) == Some(&e.sequence))
             .collect();
 
-        if selected.is_empty() {
+        let mut live: Vec<SegmentEntry> = Vec::new();
+        let mut newer: Option<(&SegmentEntry, Option<SystemTime>)> = None;
+        for entry in current.into_iter().rev() {
+            let path = self.segment_dir.join(&entry.filename);
+            let Ok(meta) = std::fs::metadata(&path) else {
+                break;
+            };
+            let modified = meta.modified().ok();
+
+            if let Some((next, next_modified)) = newer {
+                let overwritten = matches!(
+                    (modified, next_modified),
+                    (Some(m), Some(n)) if m > n
+                );
+                let gap = next.start_time - entry.end_time;
+                if overwritten || !(-BOUNDARY_EPSILON..=MAX_SEGMENT_GAP).contains(&gap) {
+                    debug!(
+                        file = %entry.filename,
+                        sequence = entry.sequence,
+                        overwritten,
+                        "replay ring stops at stale segment"
+                    );
+                    break;
+                }
+            }
+            live.push(entry.clone());
+            newer = Some((entry, modified));
+        }
+
+        live.reverse();
+        Ok(live)
+    }
+
+    /// Select segments covering the last `seconds`, by their timestamps.
+    ///
+    /// Segment lengths vary with keyframe placement, so the selection is
+    /// made from the recorded start/end times and paired with the offset
+    /// needed to trim it to exactly `seconds`.
+    pub fn select_last(&self, seconds: f64) -> Result<ReplaySelection> {
+        self.select_last_with(seconds, None)
+    }
+
+    /// Like `select_last`, with `tail` (a snapshot of the in-progress
+    /// segment) appended after the listed segments
+    pub fn select_last_with(
+        &self,
+        seconds: f64,
+        tail: Option<SegmentEntry>,
+    ) -> Result<ReplaySelection> {
+        let mut segments = self.live_segments()?;
+        segments.extend(tail);
+        let Some(end) = segments.last().map(|s| s.end_time) else {
+            return Err(Error::NoSegments);
+        };
+        self.select_span(&segments, end - seconds, end)
+    }
+
+    /// Select segments covering `start..end` on the ring's timeline, cut
+    /// short if the buffer does not reach `end` yet
+    pub fn select_window(
+        &self,
+        start: f64,
+        end: f64,
+        tail: Option<SegmentEntry>,
+    ) -> Result<ReplaySelection> {
+        let mut segments = self.live_segments()?;
+        segments.extend(tail);
+        let Some(last_end) = segments.last().map(|s| s.end_time) else {
+            return Err(Error::NoSegments);
+        };
+        self.select_span(&segments, start, end.min(last_end))
+    }
+
+    fn select_span(
+        &self,
+        segments: &[SegmentEntry],
+        start: f64,
+        end: f64,
+    ) -> Result<ReplaySelection> {
+        let last = segments
+            .iter()
+            .position(|s| s.end_time >= end - BOUNDARY_EPSILON)
+            .unwrap_or(segments.len() - 1);
+        let segments = &segments[..=last];
+        let first = segments
+            .iter()
+            .rposition(|s| s.start_time <= start + BOUNDARY_EPSILON)
+            .unwrap_or(0);
+        let selected = &segments[first..];
+        let clip_start = start.max(selected[0].start_time);
+        if end - clip_start < BOUNDARY_EPSILON {
             return Err(Error::NoSegments);
         }
 
         debug!(
             count = selected.len(),
-            seconds = seconds,
+            start = clip_start,
+            end = end,
             "selected segments for replay save"
         );
 
-        Ok(selected)
+        Ok(ReplaySelection {
+            segments: selected.iter().map(|s| self.segment_path(s)).collect(),
+            trim_start: clip_start - selected[0].start_time,
+            duration: end - clip_start,
+            trim_end: selected[selected.len() - 1].end_time - end,
+        })
+    }
+
+    /// End of the newest finished segment on the ring's timeline
+    pub fn finished_until(&self) -> Result<Option<f64>> {
+        Ok(self.live_segments()?.last().map(|s| s.end_time))
+    }
+
+    /// Position of "now" on the ring's timeline: the end of the newest
+    /// finished segment plus the time since it was closed. Zero until the
+    /// first segment finishes.
+    pub fn live_edge(&self) -> Result<f64> {
+        let Some(last) = self.live_segments()?.pop() else {
+            return Ok(0.0);
+        };
+        let since_closed = std::fs::metadata(self.segment_dir.join(&last.filename))
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|closed| SystemTime::now().duration_since(closed).ok())
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+        Ok(last.end_time + since_closed)
+    }
+
+    /// Seconds of footage held in finished segments
+    pub fn buffered_secs(&self) -> Result<f64> {
+        let live = self.live_segments()?;
+        Ok(match (live.first(), live.last()) {
+            (Some(first), Some(last)) => last.end_time - first.start_time,
+            _ => 0.0,
+        })
+    }
+
+    /// Find the segment being recorded: the slot after the newest listed
+    /// segment, modified since that segment was closed
+    pub fn in_progress_segment(&self) -> Result<Option<InProgressSegment>> {
+        let live = self.ram_segments()?;
+        let Some(last) = live.last() else {
+            // Nothing finished yet: FFmpeg is still on the first slot
+            let first = std::fs::read_dir(&self.segment_dir)
+                .map_err(Error::Io)?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .find(|p| {
+                    p.file_stem()
+                        .is_some_and(|stem| stem.to_string_lossy() == "seg_000")
+                });
+            return Ok(first.map(|path| InProgressSegment {
+                sequence: 0,
+                path,
+                start_time: 0.0,
+            }));
+        };
+
+        let Some(next) = self.next_slot(&last.filename) else {
+            return Ok(None);
+        };
+        let path = self.segment_dir.join(next);
+        let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+        match (
+            modified(&path),
+            modified(&self.segment_dir.join(&last.filename)),
+        ) {
+            (Some(current), Some(closed)) if current >= closed => Ok(Some(InProgressSegment {
+                sequence: last.sequence + 1,
+                path,
+                start_time: last.end_time,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Filename FFmpeg writes after `filename` (`seg_007.mkv` -> `seg_008.mkv`)
+    fn next_slot(&self, filename: &str) -> Option<String> {
+        let (stem, ext) = filename.rsplit_once('.')?;
+        let (prefix, digits) = stem.rsplit_once('_')?;
+        let index: u32 = digits.parse().ok()?;
+        let next = match self.max_segments {
+            0 => index + 1,
+            wrap => (index + 1) % wrap,
+        };
+        Some(format!(
+            "{prefix}_{next:0width$}.{ext}",
+            width = digits.len()
+        ))
     }
 
-    /// Clean up segment directory
+    /// Get segments covering the last N seconds, handling ring buffer wrap
+    pub fn get_last_n_seconds(&self, seconds: u32) -> Result<Vec<PathBuf>> {
+        Ok(self.select_last(seconds as f64)?.segments)
+    }
+
+    /// Clean up segment directory and the disk tier
     pub fn cleanup(&self) -> Result<()> {
         if self.segment_dir.exists() {
             std::fs::remove_dir_all(&self.segment_dir).map_err(Error::Io)?;
         }
         std::fs::create_dir_all(&self.segment_dir).map_err(Error::Io)?;
+        if let Some(ref disk) = self.disk {
+            disk.cleanup()?;
+        }
         Ok(())
     }
 }
 
+/// `filename,start,end` lines of a segment list; malformed ones are skipped
+fn read_segment_list(path: &Path) -> Result<Vec<(String, f64, f64)>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(path).map_err(Error::Io)?;
+    Ok(content
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split(',').collect();
+            (parts.len() >= 3).then(|| {
+                (
+                    parts[0].trim().to_string(),
+                    parts[1].trim().parse().unwrap_or(0.0),
+                    parts[2].trim().parse().unwrap_or(0.0),
+                )
+            })
+        })
+        .collect())
+}
+
+/// Copy a ring segment to `dest`; `false` if FFmpeg reused its slot before
+/// or during the copy
+pub(crate) fn copy_segment(src: &Path, dest: &Path) -> Result<bool> {
+    let modified =
+        |p: &Path| -> Option<SystemTime> { std::fs::metadata(p).and_then(|m| m.modified()).ok() };
+    let Some(before) = modified(src) else {
+        return Ok(false);
+    };
+    match std::fs::copy(src, dest) {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(Error::Io(e)),
+    }
+    if modified(src) != Some(before) {
+        let _ = std::fs::remove_file(dest);
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// `copy_segment` on the blocking pool, for async callers; segments run to
+/// tens of MiB
+pub(crate) async fn copy_segment_blocking(src: PathBuf, dest: PathBuf) -> Result<bool> {
+    tokio::task::spawn_blocking(move || copy_segment(&src, &dest))
+        .await
+        .map_err(|e| Error::Other(format!("segment copy failed: {e}")))?
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use std::ops::Range;
+    use std::time::Duration;
+
+    /// When test segment `seq` was closed: one second after the previous
+    pub(crate) fn closed_at(seq: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + seq)
+    }
+
+    /// Write segments `seqs` as FFmpeg would and append them to the ring's
+    /// list. Each lasts `segment_time`, goes to its slot
+    /// `seg_NNN.{ext}` (wrapping after `max_segments`, if set), holds
+    /// "seq N;" and was closed at `closed_at(seq)`.
+    pub(crate) fn produce(ring: &ReplayRing, seqs: Range<u64>, ext: &str) -> Vec<SegmentEntry> {
+        let length = ring.segment_time as u64;
+        let mut csv = std::fs::read_to_string(&ring.segment_list).unwrap_or_default();
+        let mut produced = Vec::new();
+        for seq in seqs {
+            let slot = match ring.max_segments {
+                0 => seq,
+                wrap => seq % wrap as u64,
+            };
+            let entry = SegmentEntry {
+                sequence: seq,
+                filename: format!("seg_{slot:03}.{ext}"),
+                start_time: (seq * length) as f64,
+                end_time: ((seq + 1) * length) as f64,
+            };
+            let path = ring.segment_dir.join(&entry.filename);
+            std::fs::write(&path, format!("seq {seq};")).unwrap();
+            set_mtime(&path, closed_at(seq));
+            csv.push_str(&format!(
+                "{},{:.6},{:.6}\n",
+                entry.filename, entry.start_time, entry.end_time
+            ));
+            produced.push(entry);
+        }
+        std::fs::write(&ring.segment_list, csv).unwrap();
+        produced
+    }
+
+    /// Re-time the listed segments so the newest was closed just now
+    pub(crate) fn close_now(ring: &ReplayRing) {
+        let listed = ring.parse_segments().unwrap();
+        let Some(newest) = listed.last().map(|s| s.sequence) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for entry in &listed {
+            let age = Duration::from_secs(newest - entry.sequence);
+            set_mtime(&ring.segment_dir.join(&entry.filename), now - age);
+        }
+    }
+
+    /// Ring of 3s slots holding segments of `lengths`, back to back from 0
+    pub(crate) fn uneven_ring(dir: &Path, lengths: &[f64]) -> ReplayRing {
+        let ring = ReplayRing::new(dir, 3, 40);
+        let mut csv = String::new();
+        let mut start = 0.0;
+        for (seq, len) in lengths.iter().enumerate() {
+            let name = format!("seg_{seq:03}.mkv");
+            let path = dir.join(&name);
+            std::fs::write(&path, format!("seq {seq};")).unwrap();
+            set_mtime(&path, closed_at(seq as u64));
+            csv.push_str(&format!("{name},{start:.6},{:.6}\n", start + len));
+            start += len;
+        }
+        std::fs::write(&ring.segment_list, csv).unwrap();
+        ring
+    }
+
+    fn set_mtime(path: &Path, time: SystemTime) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
 
     #[test]
     fn new_sets_fields_correctly() {
@@ -164,6 +660,78 @@ mod tests {
         assert_eq!(paths.len(), 3);
     }
 
+    #[test]
+    fn select_last_uses_timestamps_for_uneven_segments() {
+        let tmp = tempfile::tempdir().unwrap();
+        // Ends at 0, 2.5, 6.2, 7.4, 11.4, 14.7
+        let ring = uneven_ring(tmp.path(), &[2.5, 3.7, 1.2, 4.0, 3.3]);
+
+        // Last 8s starts at 6.7, inside seg_002 (6.2..7.4)
+        let selection = ring.select_last(8.0).unwrap();
+        let names: Vec<_> = selection
+            .segments
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["seg_002.mkv", "seg_003.mkv", "seg_004.mkv"]);
+        assert!((selection.trim_start - 0.5).abs() < 1e-6);
+        assert!((selection.duration - 8.0).abs() < 1e-6);
+        assert!(!selection.is_whole());
+    }
+
+    #[test]
+    fn select_last_on_segment_boundary_needs_no_trim() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ring = uneven_ring(tmp.path(), &[2.5, 3.7, 1.2, 4.0, 3.3]);
+
+        // 14.7 - 7.3 = 7.4, exactly where seg_003 starts
+        let selection = ring.select_last(7.3).unwrap();
+        assert_eq!(selection.segments.len(), 2);
+        assert!(selection.is_whole());
+    }
+
+    #[test]
+    fn select_window_trims_both_ends() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ring = uneven_ring(tmp.path(), &[2.5, 3.7, 1.2, 4.0, 3.3]);
+
+        // 3.0..9.0 spans seg_001 (2.5..6.2) to seg_003 (7.4..11.4)
+        let selection = ring.select_window(3.0, 9.0, None).unwrap();
+        assert_eq!(selection.segments.len(), 3);
+        assert!((selection.trim_start - 0.5).abs() < 1e-6);
+        assert!((selection.duration - 6.0).abs() < 1e-6);
+        assert!((selection.trim_end - 2.4).abs() < 1e-6);
+        assert!(!selection.is_whole());
+
+        // A window past the buffer is cut at its end
+        let selection = ring.select_window(10.0, 20.0, None).unwrap();
+        assert!((selection.duration - 4.7).abs() < 1e-6);
+        assert!(selection.trim_end < 1e-6);
+        assert!(ring.select_window(15.0, 20.0, None).is_err());
+    }
+
+    #[test]
+    fn live_edge_follows_newest_segment() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert_eq!(ReplayRing::new(tmp.path(), 3, 40).live_edge().unwrap(), 0.0);
+
+        let ring = write_wrapped(tmp.path(), 4, 6);
+        // The newest segment ends at 18s and was closed long ago
+        assert_eq!(ring.finished_until().unwrap(), Some(18.0));
+        assert!(ring.live_edge().unwrap() > 18.0 + 1e6);
+    }
+
+    #[test]
+    fn select_last_longer_than_buffer_returns_everything() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ring = uneven_ring(tmp.path(), &[2.5, 3.7, 1.2]);
+
+        let selection = ring.select_last(60.0).unwrap();
+        assert_eq!(selection.segments.len(), 3);
+        assert!(selection.is_whole());
+        assert!((selection.duration - 7.4).abs() < 1e-6);
+    }
+
     #[test]
     fn get_last_n_seconds_caps_at_available() {
         let tmp = tempfile::tempdir().unwrap();
@@ -194,6 +762,148 @@ mod tests {
         assert!(result.is_err());
     }
 
+    /// `count` 3s segments written through `slots` wrapped files
+    fn write_wrapped(dir: &Path, slots: u32, count: u64) -> ReplayRing {
+        let ring = ReplayRing::new(dir, 3, slots);
+        produce(&ring, 0..count, "mkv");
+        ring
+    }
+
+    fn sequences(ring: &ReplayRing) -> Vec<u64> {
+        ring.live_segments()
+            .unwrap()
+            .iter()
+            .map(|s| s.sequence)
+            .collect()
+    }
+
+    #[test]
+    fn live_segments_keep_latest_entry_per_slot_across_wraps() {
+        let tmp = tempfile::tempdir().unwrap();
+        // 11 segments through 4 slots: wrapped twice
+        let ring = write_wrapped(tmp.path(), 4, 11);
+
+        assert_eq!(ring.parse_segments().unwrap().len(), 11);
+        assert_eq!(sequences(&ring), vec![7, 8, 9, 10]);
+        assert!((ring.buffered_secs().unwrap() - 12.0).abs() < 1e-6);
+        // seg_003 still holds segment 7: nothing is being written yet
+        assert_eq!(ring.in_progress_segment().unwrap(), None);
+
+        let selection = ring.select_last(6.0).unwrap();
+        let names: Vec<_> = selection
+            .segments
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["seg_001.mkv", "seg_002.mkv"]);
+    }
+
+    #[test]
+    fn live_segments_drop_slot_being_overwritten() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ring = write_wrapped(tmp.path(), 4, 11);
+
+        // Segment 11 is being recorded into seg_003, which the list still
+        // attributes to segment 7
+        let slot = tmp.path().join("seg_003.mkv");
+        std::fs::write(&slot, "partial").unwrap();
+        set_mtime(&slot, closed_at(10) + Duration::from_millis(500));
+
+        assert_eq!(sequences(&ring), vec![8, 9, 10]);
+        let current = ring.in_progress_segment().unwrap().unwrap();
+        assert_eq!(current.path, slot);
+        assert_eq!(current.sequence, 11);
+        assert!((current.start_time - 33.0).abs() < 1e-6);
+        let selection = ring.select_last(60.0).unwrap();
+        assert_eq!(selection.segments.len(), 3);
+        assert!((selection.duration - 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tiered_ring_stitches_disk_and_cache_segments() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ram_dir = tmp.path().join("ram");
+        let disk_dir = tmp.path().join("disk");
+        let ring =
+            ReplayRing::new(&ram_dir, 3, 4).with_disk_tier(DiskTier::new(&disk_dir, 60, None));
+        ring.cleanup().unwrap();
+
+        // FFmpeg wraps through 4 slots; each finished segment is migrated
+        for seq in 0..12 {
+            produce(&ring, seq..seq + 1, "mkv");
+            ring.migrate().unwrap();
+        }
+
+        assert_eq!(sequences(&ring), (0..12).collect::<Vec<_>>());
+        assert!((ring.buffered_secs().unwrap() - 36.0).abs() < 1e-6);
+
+        // The last 30s start at segment 2, long gone from the cache dir
+        let selection = ring.select_last(30.0).unwrap();
+        assert_eq!(selection.segments.len(), 10);
+        assert_eq!(selection.segments[0], disk_dir.join("seg_000002.mkv"));
+        assert_eq!(
+            std::fs::read_to_string(&selection.segments[0]).unwrap(),
+            "seq 2;"
+        );
+        assert_eq!(selection.segments[9], ram_dir.join("seg_003.mkv"));
+        assert!(selection.is_whole());
+        assert_eq!(ring.scratch_dir(), disk_dir);
+    }
+
+    #[test]
+    fn live_segments_stop_at_gap_in_timestamps() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(tmp.path(), 3, 40);
+        let mut csv = String::new();
+        for (i, (start, end)) in [(0.0, 3.0), (3.0, 6.0), (9.0, 12.0), (12.0, 15.0)]
+            .iter()
+            .enumerate()
+        {
+            let name = format!("seg_{i:03}.mkv");
+            std::fs::write(tmp.path().join(&name), "data").unwrap();
+            csv.push_str(&format!("{name},{start},{end}\n"));
+        }
+        std::fs::write(&ring.segment_list, csv).unwrap();
+
+        assert_eq!(sequences(&ring), vec![2, 3]);
+    }
+
+    #[test]
+    fn resumed_capture_continues_the_ring() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ring = write_wrapped(tmp.path(), 4, 6);
+        assert_eq!(ring.resume().unwrap(), 6);
+        assert!(!ring.segment_list.exists());
+        assert_eq!(sequences(&ring), vec![2, 3, 4, 5]);
+
+        // FFmpeg starts a new list at slot 6 % 4, its timestamps from zero
+        std::fs::write(tmp.path().join("seg_002.mkv"), "seq 6;").unwrap();
+        set_mtime(&tmp.path().join("seg_002.mkv"), closed_at(100));
+        std::fs::write(&ring.segment_list, "seg_002.mkv,0.000000,3.000000\n").unwrap();
+
+        assert_eq!(sequences(&ring), vec![3, 4, 5, 6]);
+        let newest = ring.live_segments().unwrap().pop().unwrap();
+        assert!((newest.start_time - 18.0).abs() < 1e-6);
+        let selection = ring.select_last(6.0).unwrap();
+        assert_eq!(selection.segments[0], tmp.path().join("seg_001.mkv"));
+
+        // A second pause keeps both captures
+        assert_eq!(ring.resume().unwrap(), 7);
+        assert_eq!(sequences(&ring), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn first_segment_is_in_progress_before_any_are_listed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(tmp.path(), 3, 40);
+        assert_eq!(ring.in_progress_segment().unwrap(), None);
+
+        std::fs::write(tmp.path().join("seg_000.ts"), "growing").unwrap();
+        let current = ring.in_progress_segment().unwrap().unwrap();
+        assert_eq!(current.path, tmp.path().join("seg_000.ts"));
+        assert_eq!(current.start_time, 0.0);
+    }
+
     #[test]
     fn cleanup_removes_and_recreates_dir() {
         let tmp = tempfile::tempdir().unwrap();
@@ -1,15 +1,209 @@
 use crate::error::{Error, Result};
-use crate::process::run_ffmpeg;
-use crate::replay::ring::ReplayRing;
+use crate::process::{run_ffmpeg, run_ffprobe};
+use crate::replay::lookahead::{PendingSaves, POLL_INTERVAL};
+use crate::replay::partial;
+use crate::replay::ring::{ReplayRing, ReplaySelection, SegmentEntry};
+use crate::runner::FfmpegRunner;
 use std::path::{Path, PathBuf};
-use tracing::info;
+use tracing::{info, warn};
 
-/// Save the last N seconds from the replay ring buffer
-pub async fn save_replay(ring: &ReplayRing, seconds: u32, output: &Path) -> Result<PathBuf> {
-    let segments = ring.get_last_n_seconds(seconds)?;
+/// A keyframe this close before the cut point allows a stream-copy trim
+const KEYFRAME_TOLERANCE: f64 = 0.1;
 
-    // Create concat file
-    let concat_file = ring.segment_dir.join("concat.txt");
+/// Re-encoding for rings that don't know the capture's encoder
+const FALLBACK_CLIP_ENCODING: [&str; 10] = [
+    "-c:v", "libx264", "-preset", "veryfast", "-crf", "18", "-c:a", "aac", "-b:a", "192k",
+];
+
+/// Save the last N seconds from the replay ring buffer, up to the footage
+/// FFmpeg has written so far.
+///
+/// With `after_secs`, waits until the ring has recorded that much more and
+/// saves the window around the moment of the call instead.
+pub async fn save_replay(
+    runner: &dyn FfmpegRunner,
+    ring: &ReplayRing,
+    seconds: u32,
+    after_secs: u32,
+    output: &Path,
+) -> Result<PathBuf> {
+    if after_secs > 0 {
+        let mut pending = PendingSaves::default();
+        pending.request(ring, seconds, after_secs, output.to_path_buf())?;
+        loop {
+            if let Some(save) = pending.take_due(ring)?.pop() {
+                return save.save(runner, ring).await;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    let _pinned = ring.pin_disk().await;
+    let current = snapshot_in_progress(runner, ring)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(error = %e, "could not read in-progress segment, saving finished segments only");
+            None
+        });
+    let snapshot = current.as_ref().map(|s| ring.segment_dir.join(&s.filename));
+    let result = match ring.select_last_with(seconds as f64, current) {
+        Ok(selection) => save_selection(runner, ring, &selection, output).await,
+        Err(e) => Err(e),
+    };
+    if let Some(snapshot) = snapshot {
+        let _ = std::fs::remove_file(snapshot);
+    }
+    result
+}
+
+/// Save `start..end` on the ring's timeline, reading the in-progress
+/// segment only if the finished ones stop short of `end`
+pub(crate) async fn save_window(
+    runner: &dyn FfmpegRunner,
+    ring: &ReplayRing,
+    start: f64,
+    end: f64,
+    output: &Path,
+) -> Result<PathBuf> {
+    let _pinned = ring.pin_disk().await;
+    let current = if ring.finished_until()?.is_some_and(|f| f >= end) {
+        None
+    } else {
+        snapshot_in_progress(runner, ring)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "could not read in-progress segment, saving finished segments only");
+                None
+            })
+    };
+    let snapshot = current.as_ref().map(|s| ring.segment_dir.join(&s.filename));
+    let result = match ring.select_window(start, end, current) {
+        Ok(selection) => save_selection(runner, ring, &selection, output).await,
+        Err(e) => Err(e),
+    };
+    if let Some(snapshot) = snapshot {
+        let _ = std::fs::remove_file(snapshot);
+    }
+    result
+}
+
+async fn save_selection(
+    runner: &dyn FfmpegRunner,
+    ring: &ReplayRing,
+    selection: &ReplaySelection,
+    output: &Path,
+) -> Result<PathBuf> {
+    let output_path = output.to_path_buf();
+
+    // Saves may run side by side, so each works in a directory of its own,
+    // removed when it is dropped
+    let scratch = tempfile::Builder::new()
+        .prefix("replay_save_")
+        .tempdir_in(ring.scratch_dir())
+        .map_err(Error::Io)?;
+    if selection.is_whole() {
+        concat_segments(runner, scratch.path(), &selection.segments, &output_path).await?;
+    } else {
+        // Join first, then cut the joined file down to the exact length
+        let ext = output.extension().and_then(|e| e.to_str()).unwrap_or("mkv");
+        let joined = scratch.path().join(format!("replay_joined.{ext}"));
+        concat_segments(runner, scratch.path(), &selection.segments, &joined).await?;
+        trim_clip(
+            runner,
+            &joined,
+            selection.trim_start,
+            selection.duration,
+            ring.clip_encoding.as_deref(),
+            &output_path,
+        )
+        .await?;
+    }
+
+    info!(
+        output = %output_path.display(),
+        segments = selection.segments.len(),
+        duration = selection.duration,
+        "replay saved"
+    );
+
+    Ok(output_path)
+}
+
+/// Copy the complete part of the segment being recorded into the ring
+/// directory, timed to follow the newest listed segment
+pub(crate) async fn snapshot_in_progress(
+    runner: &dyn FfmpegRunner,
+    ring: &ReplayRing,
+) -> Result<Option<SegmentEntry>> {
+    let Some(current) = ring.in_progress_segment()? else {
+        return Ok(None);
+    };
+    let ext = current
+        .path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("mkv");
+    // Unique, as concurrent saves each take their own snapshot
+    let filename = format!("replay_partial_{}.{ext}", uuid::Uuid::new_v4().simple());
+    let dest = ring.segment_dir.join(&filename);
+    // Reads and writes up to a whole segment, so off the runtime
+    let (src, snapshot) = (current.path.clone(), dest.clone());
+    let taken = tokio::task::spawn_blocking(move || partial::snapshot(&src, &snapshot))
+        .await
+        .map_err(|e| Error::Other(format!("segment snapshot failed: {e}")))??;
+    if !taken {
+        return Ok(None);
+    }
+
+    let duration = run_ffprobe(
+        runner,
+        &[
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+            &dest.to_string_lossy(),
+        ],
+    )
+    .await
+    .and_then(|out| {
+        out.trim()
+            .parse::<f64>()
+            .map_err(|_| Error::FfmpegFailed(format!("unexpected duration: {out}")))
+    });
+    match duration {
+        Ok(duration) if duration > 0.0 => Ok(Some(SegmentEntry {
+            sequence: current.sequence,
+            filename,
+            start_time: current.start_time,
+            end_time: current.start_time + duration,
+        })),
+        other => {
+            let _ = std::fs::remove_file(&dest);
+            other.map(|_| None)
+        }
+    }
+}
+
+/// Join segments into `output` without re-encoding, using `work_dir` for
+/// intermediate files.
+///
+/// MPEG-TS segments share one timeline, so their bytes are appended and the
+/// result is remuxed. Other segments go through the concat demuxer.
+pub(crate) async fn concat_segments(
+    runner: &dyn FfmpegRunner,
+    work_dir: &Path,
+    segments: &[PathBuf],
+    output: &Path,
+) -> Result<()> {
+    let is_ts = |p: &PathBuf| p.extension().is_some_and(|e| e == "ts");
+    if !segments.is_empty() && segments.iter().all(is_ts) {
+        return join_transport_streams(runner, work_dir, segments, output).await;
+    }
+
+    let concat_file = work_dir.join("concat.txt");
     let concat_content: String = segments
         .iter()
         .map(|p| format!("file '{}'", p.to_string_lossy()))
@@ -18,30 +212,619 @@ pub async fn save_replay(ring: &ReplayRing, seconds: u32, output: &Path) -> Resu
 
     std::fs::write(&concat_file, &concat_content).map_err(Error::Io)?;
 
-    // Run ffmpeg concat (copy, no re-encode)
-    let output_path = output.to_path_buf();
-    run_ffmpeg(&[
-        "-f",
-        "concat",
-        "-safe",
-        "0",
-        "-i",
-        &concat_file.to_string_lossy(),
-        "-c",
-        "copy",
-        &output_path.to_string_lossy(),
-    ])
-    .await?;
+    let result = run_ffmpeg(
+        runner,
+        &[
+            "-y",
+            "-f",
+            "concat",
+            "-safe",
+            "0",
+            "-i",
+            &concat_file.to_string_lossy(),
+            "-c",
+            "copy",
+            &output.to_string_lossy(),
+        ],
+    )
+    .await;
 
-    // Cleanup concat file
     let _ = std::fs::remove_file(&concat_file);
+    result.map(|_| ())
+}
 
-    info!(
-        output = %output_path.display(),
-        segments = segments.len(),
-        seconds = seconds,
-        "replay saved"
-    );
+/// Append MPEG-TS segments into one stream and remux it into `output`
+async fn join_transport_streams(
+    runner: &dyn FfmpegRunner,
+    work_dir: &Path,
+    segments: &[PathBuf],
+    output: &Path,
+) -> Result<()> {
+    let joined = work_dir.join("concat.ts");
+    // As large as the save, so off the runtime
+    let (sources, dest) = (segments.to_vec(), joined.clone());
+    let appended = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+        let mut out = std::fs::File::create(&dest)?;
+        for segment in &sources {
+            std::io::copy(&mut std::fs::File::open(segment)?, &mut out)?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| Error::Other(format!("segment join failed: {e}")))?;
+    if let Err(e) = appended {
+        let _ = std::fs::remove_file(&joined);
+        return Err(Error::Io(e));
+    }
 
-    Ok(output_path)
+    let result = run_ffmpeg(
+        runner,
+        &[
+            "-y",
+            "-i",
+            &joined.to_string_lossy(),
+            "-map",
+            "0",
+            "-c",
+            "copy",
+            &output.to_string_lossy(),
+        ],
+    )
+    .await;
+
+    let _ = std::fs::remove_file(&joined);
+    result.map(|_| ())
+}
+
+/// Cut `duration` seconds starting at `start` out of `input`.
+///
+/// Stream-copies from the nearest keyframe when one sits at the cut point;
+/// otherwise re-encodes with `encoding` so the clip starts exactly at
+/// `start`.
+async fn trim_clip(
+    runner: &dyn FfmpegRunner,
+    input: &Path,
+    start: f64,
+    duration: f64,
+    encoding: Option<&[String]>,
+    output: &Path,
+) -> Result<()> {
+    let keyframe = keyframe_before(runner, input, start)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(error = %e, "could not probe keyframes, re-encoding replay");
+            None
+        });
+
+    let input = input.to_string_lossy();
+    let output = output.to_string_lossy();
+    match keyframe.filter(|kf| start - kf <= KEYFRAME_TOLERANCE) {
+        Some(kf) => {
+            let seek = format!("{kf:.6}");
+            let length = format!("{:.6}", duration + (start - kf));
+            run_ffmpeg(
+                runner,
+                &[
+                    "-y", "-ss", &seek, "-i", &input, "-t", &length, "-c", "copy", &output,
+                ],
+            )
+            .await?;
+        }
+        None => {
+            let seek = format!("{start:.6}");
+            let length = format!("{duration:.6}");
+            let mut args = vec!["-y", "-ss", &seek, "-i", &input, "-t", &length];
+            match encoding {
+                Some(encoding) => args.extend(encoding.iter().map(String::as_str)),
+                None => args.extend(FALLBACK_CLIP_ENCODING),
+            }
+            args.push(&output);
+            run_ffmpeg(runner, &args).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Latest video keyframe at or before `at` seconds into `input`.
+///
+/// `at` and `-ss` count from the start of the file, while packet times of
+/// MPEG-TS footage carry its initial offset, so they are shifted by the
+/// file's start time.
+async fn keyframe_before(runner: &dyn FfmpegRunner, input: &Path, at: f64) -> Result<Option<f64>> {
+    let output = run_ffprobe(
+        runner,
+        &[
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "packet=pts_time,flags:format=start_time",
+            "-of",
+            "csv=p=0",
+            &input.to_string_lossy(),
+        ],
+    )
+    .await?;
+
+    // Packets are `pts,flags`; the format's start time is a line of its own
+    let mut start_time = 0.0;
+    let mut keyframes = Vec::new();
+    for line in output.lines() {
+        match line.split_once(',') {
+            Some((pts, flags)) if flags.contains('K') => {
+                keyframes.extend(pts.trim().parse::<f64>().ok())
+            }
+            Some(_) => {}
+            None => start_time = line.trim().parse().unwrap_or(start_time),
+        }
+    }
+    Ok(keyframes
+        .into_iter()
+        .map(|pts| pts - start_time)
+        .filter(|pts| *pts <= at + 0.001)
+        .reduce(f64::max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, SegmentContainer};
+    use crate::encode::ffmpeg::FfmpegCommandBuilder;
+    use crate::replay::ring;
+    use crate::replay::ring::tests::produce;
+    use crate::replay::tier::DiskTier;
+    use crate::runner::fake::{FakeResponse, FakeRunner};
+    use crate::runner::{SystemRunner, Tool};
+
+    #[tokio::test]
+    async fn save_concats_latest_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(dir.path(), 3, 40);
+        produce(&ring, 0..4, "mkv");
+
+        let runner = FakeRunner::new().on_with(Tool::Ffmpeg, "concat", |args| {
+            FakeResponse::ok().writes(std::fs::read_to_string(input_of(args)).unwrap())
+        });
+
+        let output = dir.path().join("replay.mkv");
+        let saved = save_replay(&runner, &ring, 6, 0, &output).await.unwrap();
+
+        assert_eq!(saved, output);
+        let listed = std::fs::read_to_string(&output).unwrap();
+        assert!(!listed.contains("seg_001.mkv"));
+        assert!(listed.contains("seg_002.mkv"));
+        assert!(listed.contains("seg_003.mkv"));
+        assert_no_scratch_left(dir.path());
+    }
+
+    #[tokio::test]
+    async fn concurrent_saves_use_separate_scratch_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = uneven_ring(dir.path());
+        let runner = FakeRunner::new().on_with(Tool::Ffmpeg, "concat", |args| {
+            FakeResponse::ok().writes(std::fs::read_to_string(input_of(args)).unwrap())
+        });
+
+        let (first, second) = (dir.path().join("a.mkv"), dir.path().join("b.mkv"));
+        let (a, b) = tokio::join!(
+            save_replay(&runner, &ring, 60, 0, &first),
+            save_replay(&runner, &ring, 60, 0, &second),
+        );
+        a.unwrap();
+        b.unwrap();
+
+        let calls = runner.calls();
+        assert_ne!(input_of(&calls[0].1), input_of(&calls[1].1));
+        assert_no_scratch_left(dir.path());
+    }
+
+    #[tokio::test]
+    async fn save_spans_disk_and_cache_tiers() {
+        let ram_dir = tempfile::tempdir().unwrap();
+        let disk_dir = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(ram_dir.path(), 3, 40).with_disk_tier(DiskTier::new(
+            disk_dir.path(),
+            60,
+            None,
+        ));
+        produce(&ring, 0..6, "mkv");
+        ring.migrate().unwrap();
+        // The oldest three have since left the cache dir
+        for i in 0..3 {
+            std::fs::remove_file(ram_dir.path().join(format!("seg_{i:03}.mkv"))).unwrap();
+        }
+
+        // Joins happen on the disk tier, not in tmpfs
+        let disk = disk_dir.path().to_path_buf();
+        let runner = FakeRunner::new().on_with(Tool::Ffmpeg, "concat", move |args| {
+            assert!(Path::new(input_of(args)).starts_with(&disk));
+            FakeResponse::ok().writes(std::fs::read_to_string(input_of(args)).unwrap())
+        });
+        let output = ram_dir.path().join("replay.mkv");
+        save_replay(&runner, &ring, 15, 0, &output).await.unwrap();
+
+        let listed = std::fs::read_to_string(&output).unwrap();
+        let files: Vec<&str> = listed
+            .lines()
+            .map(|l| l.trim_start_matches("file '").trim_end_matches('\''))
+            .collect();
+        let disk = |name: &str| disk_dir.path().join(name).to_string_lossy().into_owned();
+        let ram = |name: &str| ram_dir.path().join(name).to_string_lossy().into_owned();
+        assert_eq!(
+            files,
+            vec![
+                disk("seg_000001.mkv"),
+                disk("seg_000002.mkv"),
+                ram("seg_003.mkv"),
+                ram("seg_004.mkv"),
+                ram("seg_005.mkv"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn mpegts_segments_are_appended_then_remuxed() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(dir.path(), 3, 40);
+        produce(&ring, 0..4, "ts");
+
+        let runner = FakeRunner::new().on_with(Tool::Ffmpeg, "concat.ts", |args| {
+            FakeResponse::ok().writes(std::fs::read(input_of(args)).unwrap())
+        });
+        let output = dir.path().join("replay.mkv");
+        save_replay(&runner, &ring, 6, 0, &output).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "seq 2;seq 3;");
+        let remux = &runner.calls()[0].1;
+        assert_eq!(arg_after(remux, "-c"), "copy");
+        assert!(!remux.iter().any(|a| a == "concat"));
+        assert_no_scratch_left(dir.path());
+    }
+
+    /// Real FFmpeg and ffprobe. Tests using them are ignored by default;
+    /// run them with `cargo test -p clipforge-core -- --ignored`.
+    fn system_runner() -> SystemRunner {
+        for tool in ["ffmpeg", "ffprobe"] {
+            let installed = std::process::Command::new(tool)
+                .arg("-version")
+                .output()
+                .is_ok_and(|o| o.status.success());
+            assert!(installed, "{tool} is not installed");
+        }
+        SystemRunner::from_config(&Config::default())
+    }
+
+    /// Record `secs` of lavfi test video and tone into 1s ring segments
+    async fn record_lavfi_ring(
+        runner: &SystemRunner,
+        dir: &Path,
+        secs: u32,
+        container: SegmentContainer,
+    ) -> ReplayRing {
+        let secs = secs.to_string();
+        let inputs = [
+            "-f",
+            "lavfi",
+            "-i",
+            "testsrc2=size=160x120:rate=25",
+            "-f",
+            "lavfi",
+            "-i",
+            "sine=frequency=440:sample_rate=48000",
+            "-t",
+            &secs,
+            "-map",
+            "0:v",
+            "-map",
+            "1:a",
+            "-c:v",
+            "mpeg2video",
+            "-g",
+            "25",
+            "-c:a",
+            "aac",
+        ];
+        let args = FfmpegCommandBuilder::new()
+            .with_extra_input_args(inputs.iter().map(|a| a.to_string()).collect())
+            .with_segment_output(dir, 1, 0, 0, container)
+            .build();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        run_ffmpeg(runner, &args).await.unwrap();
+        ReplayRing::new(dir, 1, 0)
+    }
+
+    /// Sorted packet timestamps of the video and audio streams
+    async fn packet_times(runner: &SystemRunner, file: &Path) -> (Vec<f64>, Vec<f64>) {
+        let out = run_ffprobe(
+            runner,
+            &[
+                "-v",
+                "error",
+                "-show_entries",
+                "packet=codec_type,pts_time",
+                "-of",
+                "csv=p=0",
+                &file.to_string_lossy(),
+            ],
+        )
+        .await
+        .unwrap();
+        let (mut video, mut audio) = (Vec::new(), Vec::new());
+        for (kind, pts) in out.lines().filter_map(|l| l.split_once(',')) {
+            let Ok(pts) = pts.trim().parse::<f64>() else {
+                continue;
+            };
+            match kind {
+                "video" => video.push(pts),
+                "audio" => audio.push(pts),
+                _ => {}
+            }
+        }
+        video.sort_by(f64::total_cmp);
+        audio.sort_by(f64::total_cmp);
+        (video, audio)
+    }
+
+    /// Every packet follows the previous one by about `step`
+    fn assert_steady(times: &[f64], step: f64, what: &str) {
+        for pair in times.windows(2) {
+            let gap = pair[1] - pair[0];
+            assert!(
+                gap > step * 0.5 && gap < step * 1.5,
+                "{what} jumps by {gap:.4}s at {:.3}s",
+                pair[0]
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs ffmpeg and ffprobe"]
+    async fn mpegts_ring_saves_continuous_audio_and_video() {
+        let runner = system_runner();
+        let dir = tempfile::tempdir().unwrap();
+        let ring = record_lavfi_ring(&runner, dir.path(), 4, SegmentContainer::Mpegts).await;
+        assert!(ring.live_segments().unwrap().len() >= 3);
+
+        let output = dir.path().join("replay.mkv");
+        save_replay(&runner, &ring, 60, 0, &output).await.unwrap();
+
+        let (video, audio) = packet_times(&runner, &output).await;
+        // 4s at 25 fps, with no frame lost or repeated at the joins
+        assert_eq!(video.len(), 100);
+        assert_steady(&video, 1.0 / 25.0, "video");
+        assert_steady(&audio, 1024.0 / 48000.0, "audio");
+        assert!(
+            (video[0] - audio[0]).abs() < 0.05,
+            "streams start apart: {} vs {}",
+            video[0],
+            audio[0]
+        );
+        let (video_end, audio_end) = (video[video.len() - 1], audio[audio.len() - 1]);
+        assert!(
+            (video_end - audio_end).abs() < 0.1,
+            "streams end apart: {video_end} vs {audio_end}"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs ffmpeg and ffprobe"]
+    async fn mpegts_ring_trims_from_the_keyframe_before_the_cut() {
+        let runner = system_runner();
+        let dir = tempfile::tempdir().unwrap();
+        let ring = record_lavfi_ring(&runner, dir.path(), 4, SegmentContainer::Mpegts).await;
+        let segments = ring.live_segments().unwrap();
+
+        // Just past the keyframe opening the second segment
+        let start = segments[1].start_time + 0.05;
+        let output = dir.path().join("replay.mkv");
+        save_window(&runner, &ring, start, start + 2.0, &output)
+            .await
+            .unwrap();
+
+        // Stream-copied from that keyframe rather than re-encoded
+        let codec = run_ffprobe(
+            &runner,
+            &[
+                "-v",
+                "error",
+                "-select_streams",
+                "v:0",
+                "-show_entries",
+                "stream=codec_name",
+                "-of",
+                "csv=p=0",
+                &output.to_string_lossy(),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(codec.trim(), "mpeg2video");
+        let (video, _) = packet_times(&runner, &output).await;
+        assert!(
+            (50..=53).contains(&video.len()),
+            "{} frames for 2.05s",
+            video.len()
+        );
+        assert_steady(&video, 1.0 / 25.0, "video");
+    }
+
+    /// Ring with uneven segments ending at 14.7s; the last 8s start 0.5s
+    /// into seg_002
+    fn uneven_ring(dir: &Path) -> ReplayRing {
+        ring::tests::uneven_ring(dir, &[2.5, 3.7, 1.2, 4.0, 3.3])
+    }
+
+    fn trim_runner(keyframes: &str) -> FakeRunner {
+        FakeRunner::new()
+            .on(Tool::Ffmpeg, "concat", FakeResponse::ok().writes("joined"))
+            .on(
+                Tool::Ffprobe,
+                "replay_joined.mkv",
+                FakeResponse::ok().stdout(keyframes),
+            )
+            .on(Tool::Ffmpeg, "-ss", FakeResponse::ok().writes("clip"))
+    }
+
+    /// The file FFmpeg reads
+    fn input_of(args: &[String]) -> &str {
+        args.iter().skip_while(|a| *a != "-i").nth(1).unwrap()
+    }
+
+    /// No snapshot, list or joined file outlives a save
+    fn assert_no_scratch_left(dir: &Path) {
+        let left: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("replay_") || name.starts_with("concat"))
+            .collect();
+        assert!(left.is_empty(), "left behind: {left:?}");
+    }
+
+    fn arg_after<'a>(args: &'a [String], flag: &str) -> &'a str {
+        let idx = args.iter().position(|a| a == flag).unwrap();
+        &args[idx + 1]
+    }
+
+    #[tokio::test]
+    async fn trims_with_stream_copy_from_keyframe_at_cut() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = uneven_ring(dir.path());
+        let runner = trim_runner("0.000000,K__\n0.016667,___\n0.450000,K__\n0.466667,___\n");
+
+        let output = dir.path().join("replay.mkv");
+        save_replay(&runner, &ring, 8, 0, &output).await.unwrap();
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 3);
+        let trim = &calls[2].1;
+        assert_eq!(arg_after(trim, "-ss"), "0.450000");
+        assert_eq!(arg_after(trim, "-t"), "8.050000");
+        assert_eq!(arg_after(trim, "-c"), "copy");
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "clip");
+        assert_no_scratch_left(dir.path());
+    }
+
+    #[tokio::test]
+    async fn mpegts_keyframes_are_timed_from_the_start_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = ReplayRing::new(dir.path(), 3, 40);
+        produce(&ring, 0..4, "ts");
+        // The joined stream keeps the 1.4s offset MPEG-TS starts at
+        let runner = FakeRunner::new()
+            .on(
+                Tool::Ffmpeg,
+                "concat.ts",
+                FakeResponse::ok().writes("joined"),
+            )
+            .on(
+                Tool::Ffprobe,
+                "replay_joined.mkv",
+                FakeResponse::ok().stdout("1.400000,K__\n2.400000,K__\n2.440000,___\n1.400000\n"),
+            )
+            .on(Tool::Ffmpeg, "-ss", FakeResponse::ok().writes("clip"));
+
+        // 1.05s into seg_002, 0.05s after its second keyframe
+        let output = dir.path().join("replay.mkv");
+        save_window(&runner, &ring, 7.05, 12.0, &output)
+            .await
+            .unwrap();
+
+        let trim = &runner.calls()[2].1;
+        assert_eq!(arg_after(trim, "-ss"), "1.000000");
+        assert_eq!(arg_after(trim, "-t"), "5.000000");
+        assert_eq!(arg_after(trim, "-c"), "copy");
+        assert_no_scratch_left(dir.path());
+    }
+
+    #[tokio::test]
+    async fn window_is_cut_at_both_ends() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = uneven_ring(dir.path());
+        let runner = trim_runner("0.000000,K__\n0.500000,K__\n");
+
+        // 3.0..9.0 starts 0.5s into seg_001 and ends inside seg_003
+        let output = dir.path().join("replay.mkv");
+        save_window(&runner, &ring, 3.0, 9.0, &output)
+            .await
+            .unwrap();
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 3);
+        let trim = &calls[2].1;
+        assert_eq!(arg_after(trim, "-ss"), "0.500000");
+        assert_eq!(arg_after(trim, "-t"), "6.000000");
+        assert_eq!(arg_after(trim, "-c"), "copy");
+    }
+
+    #[tokio::test]
+    async fn includes_footage_from_segment_being_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = uneven_ring(dir.path());
+        let (growing, _) = crate::replay::partial::tests::growing_mkv(2);
+        std::fs::write(dir.path().join("seg_005.mkv"), growing).unwrap();
+
+        let runner = FakeRunner::new()
+            .on(
+                Tool::Ffprobe,
+                "format=duration",
+                FakeResponse::ok().stdout("1.700000\n"),
+            )
+            .on_with(Tool::Ffmpeg, "concat", |args| {
+                FakeResponse::ok().writes(std::fs::read_to_string(input_of(args)).unwrap())
+            });
+
+        // 14.7s listed + 1.7s in progress: the last 5s start at seg_004
+        let output = dir.path().join("replay.mkv");
+        save_replay(&runner, &ring, 5, 0, &output).await.unwrap();
+
+        let listed = std::fs::read_to_string(&output).unwrap();
+        let files: Vec<&str> = listed
+            .lines()
+            .map(|l| l.rsplit('/').next().unwrap().trim_end_matches('\''))
+            .collect();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0], "seg_004.mkv");
+        assert!(files[1].starts_with("replay_partial_"));
+        assert_no_scratch_left(dir.path());
+    }
+
+    #[tokio::test]
+    async fn re_encodes_when_no_keyframe_near_cut() {
+        let dir = tempfile::tempdir().unwrap();
+        let ring = uneven_ring(dir.path());
+        let runner = trim_runner("0.000000,K__\n0.016667,___\n");
+
+        let output = dir.path().join("replay.mkv");
+        save_replay(&runner, &ring, 8, 0, &output).await.unwrap();
+
+        let trim = &runner.calls()[2].1;
+        assert_eq!(arg_after(trim, "-ss"), "0.500000");
+        assert_eq!(arg_after(trim, "-t"), "8.000000");
+        assert_eq!(arg_after(trim, "-c:v"), "libx264");
+    }
+
+    #[tokio::test]
+    async fn re_encodes_with_the_capture_encoder() {
+        let dir = tempfile::tempdir().unwrap();
+        let encoder = crate::encode::hw_probe::EncoderInfo {
+            name: "h264_nvenc".to_string(),
+            hw_accel: crate::encode::hw_probe::HwAccelType::Nvenc,
+            available: true,
+            device: None,
+            ten_bit_codecs: Vec::new(),
+            hdr_conversion: false,
+        };
+        let ring = uneven_ring(dir.path()).with_clip_encoding(
+            crate::encode::ffmpeg::build_clip_encoding(&Config::default(), &encoder),
+        );
+        let runner = trim_runner("0.000000,K__\n");
+
+        let output = dir.path().join("replay.mkv");
+        save_replay(&runner, &ring, 8, 0, &output).await.unwrap();
+
+        let trim = &runner.calls()[2].1;
+        assert_eq!(arg_after(trim, "-c:v"), "h264_nvenc");
+        assert_eq!(trim.last().unwrap(), &output.to_string_lossy());
+    }
 }
@@ -0,0 +1,337 @@
+//! Disk tier of a long replay buffer.
+//!
+//! tmpfs cannot hold half an hour of high-bitrate footage, so a tiered ring
+//! keeps only the newest `ram_secs` in the cache dir and copies every
+//! finished segment into a disk directory with its own index. Copies older
+//! than the buffer's length, or over the tier's size cap, are deleted. The
+//! ring stitches both tiers into one timeline.
+//!
+//! Migration runs on a task of its own (`Migration`) so a long save never
+//! holds it up while the cache dir wraps around. Saves pin the tier instead,
+//! which only defers deleting expired copies.
+
+use crate::error::{Error, Result};
+use crate::replay::record::COLLECT_INTERVAL;
+use crate::replay::ring::{copy_segment, ReplayRing, SegmentEntry};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedRwLockReadGuard, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Segments migrated out of the cache dir, indexed by ring sequence
+#[derive(Debug, Clone)]
+pub struct DiskTier {
+    pub dir: PathBuf,
+    pub index: PathBuf,
+    /// Footage to keep, in seconds
+    pub keep_secs: u32,
+    /// Cap on the tier's size in bytes
+    pub budget_bytes: Option<u64>,
+    /// Held while the tier's files and index change, so migrations never
+    /// interleave
+    migrating: Arc<Mutex<()>>,
+    /// Read by saves using the tier's files; expired copies are only
+    /// deleted while nobody holds it
+    pins: Arc<RwLock<()>>,
+}
+
+impl DiskTier {
+    pub fn new(dir: &Path, keep_secs: u32, budget_bytes: Option<u64>) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            index: dir.join("segments.csv"),
+            keep_secs,
+            budget_bytes,
+            migrating: Arc::default(),
+            pins: Arc::default(),
+        }
+    }
+
+    /// Keep every copy on disk until the guard is dropped
+    pub async fn pin(&self) -> OwnedRwLockReadGuard<()> {
+        self.pins.clone().read_owned().await
+    }
+
+    /// Migrated segments, oldest first. Filenames are absolute so they
+    /// resolve from the ring's own directory too.
+    pub fn segments(&self) -> Result<Vec<SegmentEntry>> {
+        Ok(self
+            .read_index()?
+            .into_iter()
+            .map(|s| SegmentEntry {
+                filename: self.dir.join(&s.filename).to_string_lossy().into_owned(),
+                ..s
+            })
+            .collect())
+    }
+
+    /// Copy `finished` segments from `ring_dir` that are not on disk yet,
+    /// then delete copies that fell out of the buffer unless a save has the
+    /// tier pinned. Returns how many segments were copied.
+    ///
+    /// A segment overwritten before it was copied leaves a gap in the index;
+    /// the copies before it are kept, and the ring's timeline stops at the
+    /// gap until they expire.
+    pub fn migrate(&self, ring_dir: &Path, finished: &[SegmentEntry]) -> Result<usize> {
+        let _migrating = self.migrating.lock().unwrap_or_else(|e| e.into_inner());
+        let mut segments = self.read_index()?;
+        let mut migrated_until = segments.last().map(|s| s.sequence);
+        let mut copied = 0;
+
+        for entry in finished {
+            if migrated_until.is_some_and(|last| entry.sequence <= last) {
+                continue;
+            }
+            let src = ring_dir.join(&entry.filename);
+            let ext = src.extension().and_then(|e| e.to_str()).unwrap_or("mkv");
+            let filename = format!("seg_{:06}.{ext}", entry.sequence);
+            if !copy_segment(&src, &self.dir.join(&filename))? {
+                continue;
+            }
+
+            if let Some(last) = migrated_until.filter(|&last| last + 1 != entry.sequence) {
+                warn!(
+                    from = last + 1,
+                    to = entry.sequence - 1,
+                    "replay segments overwritten before they reached disk"
+                );
+            }
+            segments.push(SegmentEntry {
+                filename,
+                ..entry.clone()
+            });
+            migrated_until = Some(entry.sequence);
+            copied += 1;
+        }
+
+        let expired = match self.pins.try_write() {
+            Ok(_unpinned) => {
+                let expired = self.expired(&segments);
+                self.remove(&segments[..expired]);
+                segments.drain(..expired);
+                expired
+            }
+            // A save is reading the copies; expire them next time
+            Err(_) => 0,
+        };
+        if copied > 0 || expired > 0 {
+            debug!(copied, expired, "replay disk tier updated");
+            self.write_index(&segments)?;
+        }
+        Ok(copied)
+    }
+
+    /// Delete every migrated segment
+    pub fn cleanup(&self) -> Result<()> {
+        let _migrating = self.migrating.lock().unwrap_or_else(|e| e.into_inner());
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir).map_err(Error::Io)?;
+        }
+        std::fs::create_dir_all(&self.dir).map_err(Error::Io)?;
+        Ok(())
+    }
+
+    /// Number of oldest segments outside `keep_secs` or over the budget
+    fn expired(&self, segments: &[SegmentEntry]) -> usize {
+        let Some(newest) = segments.last().map(|s| s.end_time) else {
+            return 0;
+        };
+        let mut expired = segments
+            .iter()
+            .take_while(|s| s.end_time <= newest - self.keep_secs as f64)
+            .count();
+
+        if let Some(budget) = self.budget_bytes {
+            let sizes: Vec<u64> = segments
+                .iter()
+                .map(|s| std::fs::metadata(self.dir.join(&s.filename)).map_or(0, |m| m.len()))
+                .collect();
+            let mut total: u64 = sizes[expired..].iter().sum();
+            while total > budget && expired < segments.len() {
+                total -= sizes[expired];
+                expired += 1;
+            }
+        }
+        expired
+    }
+
+    fn remove(&self, segments: &[SegmentEntry]) {
+        for segment in segments {
+            let _ = std::fs::remove_file(self.dir.join(&segment.filename));
+        }
+    }
+
+    /// Index lines are `sequence,filename,start,end`
+    fn read_index(&self) -> Result<Vec<SegmentEntry>> {
+        if !self.index.exists() {
+            return Ok(Vec::new());
+        }
+        let content = std::fs::read_to_string(&self.index).map_err(Error::Io)?;
+        Ok(content
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split(',').map(str::trim);
+                Some(SegmentEntry {
+                    sequence: parts.next()?.parse().ok()?,
+                    filename: parts.next()?.to_string(),
+                    start_time: parts.next()?.parse().ok()?,
+                    end_time: parts.next()?.parse().ok()?,
+                })
+            })
+            .collect())
+    }
+
+    /// Replace the index in one step so readers never see half of it
+    fn write_index(&self, segments: &[SegmentEntry]) -> Result<()> {
+        let content: String = segments
+            .iter()
+            .map(|s| {
+                format!(
+                    "{},{},{:.6},{:.6}\n",
+                    s.sequence, s.filename, s.start_time, s.end_time
+                )
+            })
+            .collect();
+        let tmp = self.index.with_extension("csv.tmp");
+        std::fs::write(&tmp, content).map_err(Error::Io)?;
+        std::fs::rename(&tmp, &self.index).map_err(Error::Io)
+    }
+}
+
+/// Task moving a ring's finished segments to its disk tier every
+/// `COLLECT_INTERVAL`; stopped when dropped
+pub struct Migration {
+    task: JoinHandle<()>,
+}
+
+impl Migration {
+    /// Start migrating `ring`'s segments; `None` if the ring has no disk tier
+    pub fn spawn(ring: &ReplayRing) -> Option<Self> {
+        ring.disk.as_ref()?;
+        let ring = ring.clone();
+        let task = tokio::spawn(async move {
+            let ring = Arc::new(ring);
+            let mut interval = tokio::time::interval(COLLECT_INTERVAL);
+            loop {
+                interval.tick().await;
+                let ring = ring.clone();
+                match tokio::task::spawn_blocking(move || ring.migrate()).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => warn!(error = %e, "could not move replay segments to disk"),
+                    Err(e) => warn!(error = %e, "replay disk migration panicked"),
+                }
+            }
+        });
+        Some(Self { task })
+    }
+}
+
+impl Drop for Migration {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ring::tests::produce;
+
+    /// 3s segments `from..to` finished in the ring dir
+    fn finished(ring_dir: &Path, from: u64, to: u64) -> Vec<SegmentEntry> {
+        produce(&ReplayRing::new(ring_dir, 3, 0), from..to, "mkv")
+    }
+
+    fn sequences(tier: &DiskTier) -> Vec<u64> {
+        tier.segments()
+            .unwrap()
+            .iter()
+            .map(|s| s.sequence)
+            .collect()
+    }
+
+    #[test]
+    fn migrates_each_segment_once() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let disk_dir = tempfile::tempdir().unwrap();
+        let tier = DiskTier::new(disk_dir.path(), 60, None);
+
+        assert_eq!(
+            tier.migrate(ring_dir.path(), &finished(ring_dir.path(), 0, 3))
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            tier.migrate(ring_dir.path(), &finished(ring_dir.path(), 1, 4))
+                .unwrap(),
+            1
+        );
+        assert_eq!(sequences(&tier), vec![0, 1, 2, 3]);
+
+        let last = tier.segments().unwrap().pop().unwrap();
+        assert_eq!(std::fs::read_to_string(&last.filename).unwrap(), "seq 3;");
+        assert!(Path::new(&last.filename).starts_with(disk_dir.path()));
+        assert!((last.start_time - 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn drops_footage_older_than_the_buffer() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let disk_dir = tempfile::tempdir().unwrap();
+        let tier = DiskTier::new(disk_dir.path(), 9, None);
+
+        tier.migrate(ring_dir.path(), &finished(ring_dir.path(), 0, 6))
+            .unwrap();
+        // Ends at 18s: keep the segments ending after 9s
+        assert_eq!(sequences(&tier), vec![3, 4, 5]);
+        assert!(!disk_dir.path().join("seg_000002.mkv").exists());
+    }
+
+    #[test]
+    fn budget_drops_oldest_segments() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let disk_dir = tempfile::tempdir().unwrap();
+        // Each "seq N;" segment is 6 bytes
+        let tier = DiskTier::new(disk_dir.path(), 60, Some(12));
+
+        tier.migrate(ring_dir.path(), &finished(ring_dir.path(), 0, 5))
+            .unwrap();
+        assert_eq!(sequences(&tier), vec![3, 4]);
+    }
+
+    #[test]
+    fn hole_in_sequence_keeps_older_copies() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let disk_dir = tempfile::tempdir().unwrap();
+        let tier = DiskTier::new(disk_dir.path(), 60, None);
+
+        tier.migrate(ring_dir.path(), &finished(ring_dir.path(), 0, 2))
+            .unwrap();
+        let mut later = finished(ring_dir.path(), 2, 5);
+        // Overwritten before it could be copied
+        std::fs::remove_file(ring_dir.path().join(&later[0].filename)).unwrap();
+        later.remove(0);
+        tier.migrate(ring_dir.path(), &later).unwrap();
+        assert_eq!(sequences(&tier), vec![0, 1, 3, 4]);
+        assert!(disk_dir.path().join("seg_000000.mkv").exists());
+    }
+
+    #[tokio::test]
+    async fn pinned_tier_keeps_expired_copies_until_released() {
+        let ring_dir = tempfile::tempdir().unwrap();
+        let disk_dir = tempfile::tempdir().unwrap();
+        let tier = DiskTier::new(disk_dir.path(), 9, None);
+
+        let pin = tier.pin().await;
+        tier.migrate(ring_dir.path(), &finished(ring_dir.path(), 0, 6))
+            .unwrap();
+        assert_eq!(sequences(&tier), (0..6).collect::<Vec<_>>());
+
+        drop(pin);
+        tier.migrate(ring_dir.path(), &finished(ring_dir.path(), 0, 6))
+            .unwrap();
+        assert_eq!(sequences(&tier), vec![3, 4, 5]);
+        assert!(!disk_dir.path().join("seg_000000.mkv").exists());
+    }
+}
@@ -0,0 +1,178 @@
+//! Scripted stand-in for FFmpeg used by tests.
+//!
+//! Rules match on the tool and a substring of the joined args; the first
+//! matching rule decides what the fake prints, which file it writes and how
+//! it exits. Unmatched calls fail, so tests only pass for calls they expect.
+
+use super::{BoxFuture, FfmpegRunner, Tool, ToolOutput, ToolProcess};
+use crate::error::Result;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::oneshot;
+
+type Responder = Arc<dyn Fn(&[String]) -> FakeResponse + Send + Sync>;
+
+/// What a fake invocation does
+#[derive(Debug, Clone, Default)]
+pub struct FakeResponse {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: i32,
+    /// Contents written to the output file (the last arg)
+    pub output: Option<Vec<u8>>,
+    /// Stay alive until 'q' arrives on stdin or the process is killed
+    pub until_quit: bool,
+}
+
+impl FakeResponse {
+    pub fn ok() -> Self {
+        Self::default()
+    }
+
+    pub fn failing(code: i32, stderr: &str) -> Self {
+        Self {
+            code,
+            stderr: stderr.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn stdout(mut self, stdout: &str) -> Self {
+        self.stdout = stdout.to_string();
+        self
+    }
+
+    pub fn writes(mut self, contents: impl Into<Vec<u8>>) -> Self {
+        self.output = Some(contents.into());
+        self
+    }
+
+    /// Behave like a capture: report `frames` of progress, then wait for 'q'
+    pub fn capture(frames: u64) -> Self {
+        let stdout = (1..=frames)
+            .map(|f| format!("frame={f}\nout_time_us={}\nprogress=continue\n", f * 1000))
+            .collect();
+        Self {
+            stdout,
+            code: 255,
+            until_quit: true,
+            ..Self::default()
+        }
+    }
+}
+
+struct Rule {
+    tool: Tool,
+    pattern: String,
+    respond: Responder,
+}
+
+#[derive(Default)]
+pub struct FakeRunner {
+    rules: Vec<Rule>,
+    calls: Mutex<Vec<(Tool, Vec<String>)>>,
+}
+
+impl FakeRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Respond to calls whose joined args contain `pattern`
+    pub fn on(self, tool: Tool, pattern: &str, response: FakeResponse) -> Self {
+        self.on_with(tool, pattern, move |_| response.clone())
+    }
+
+    /// Like `on`, but computes the response from the args
+    pub fn on_with(
+        mut self,
+        tool: Tool,
+        pattern: &str,
+        respond: impl Fn(&[String]) -> FakeResponse + Send + Sync + 'static,
+    ) -> Self {
+        self.rules.push(Rule {
+            tool,
+            pattern: pattern.to_string(),
+            respond: Arc::new(respond),
+        });
+        self
+    }
+
+    /// Every invocation so far, in order
+    pub fn calls(&self) -> Vec<(Tool, Vec<String>)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn respond(&self, tool: Tool, args: &[String]) -> FakeResponse {
+        self.calls.lock().unwrap().push((tool, args.to_vec()));
+
+        let joined = args.join(" ");
+        let response = self
+            .rules
+            .iter()
+            .find(|r| r.tool == tool && joined.contains(&r.pattern))
+            .map(|r| (r.respond)(args))
+            .unwrap_or_else(|| FakeResponse::failing(1, &format!("unexpected call: {joined}")));
+
+        if let (Some(contents), Some(path)) = (&response.output, args.last()) {
+            std::fs::write(PathBuf::from(path), contents).expect("fake output writable");
+        }
+        response
+    }
+}
+
+impl FfmpegRunner for FakeRunner {
+    fn run(&self, tool: Tool, args: Vec<String>) -> BoxFuture<'_, Result<ToolOutput>> {
+        let response = self.respond(tool, &args);
+        Box::pin(async move {
+            Ok(ToolOutput {
+                code: Some(response.code),
+                stdout: response.stdout.into_bytes(),
+                stderr: response.stderr.into_bytes(),
+            })
+        })
+    }
+
+    fn spawn(
+        &self,
+        tool: Tool,
+        args: Vec<String>,
+        kill: oneshot::Receiver<()>,
+    ) -> Result<ToolProcess> {
+        let response = self.respond(tool, &args);
+
+        let (stdin, mut fake_stdin) = tokio::io::duplex(1024);
+        let (mut fake_stdout, stdout) = tokio::io::duplex(64 * 1024);
+        let (mut fake_stderr, stderr) = tokio::io::duplex(64 * 1024);
+
+        let exit = Box::pin(async move {
+            let _ = fake_stdout.write_all(response.stdout.as_bytes()).await;
+            let _ = fake_stderr.write_all(response.stderr.as_bytes()).await;
+
+            if response.until_quit {
+                let quit = async {
+                    let mut byte = [0u8; 1];
+                    while fake_stdin.read(&mut byte).await.unwrap_or(0) == 1 {
+                        if byte[0] == b'q' {
+                            break;
+                        }
+                    }
+                };
+                tokio::select! {
+                    _ = quit => {}
+                    _ = kill => return Ok(None),
+                }
+            }
+            Ok(Some(response.code))
+        });
+
+        Ok(ToolProcess {
+            pid: None,
+            stdin: Box::new(stdin),
+            stdout: Box::new(stdout),
+            stderr: Box::new(stderr),
+            exit,
+        })
+    }
+}
@@ -0,0 +1,246 @@
+use crate::config::{EncoderProcessConfig, IoClass};
+use std::path::{Path, PathBuf};
+
+/// A way of restricting the encoder, each backed by an external wrapper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mechanism {
+    Nice,
+    Ionice,
+    Affinity,
+    CpuQuota,
+}
+
+impl Mechanism {
+    fn program(self) -> &'static str {
+        match self {
+            Mechanism::Nice => "nice",
+            Mechanism::Ionice => "ionice",
+            Mechanism::Affinity => "taskset",
+            Mechanism::CpuQuota => "systemd-run",
+        }
+    }
+}
+
+/// Wrapper command line that applies `EncoderProcessConfig` to a spawn.
+///
+/// Every wrapper execs the next, so the spawned PID is FFmpeg itself and
+/// stdin/kill handling is unchanged. Mechanisms the host lacks are left
+/// out rather than failing the spawn.
+#[derive(Debug, Clone, Default)]
+pub struct Isolation {
+    prefix: Vec<String>,
+    /// Human-readable settings that will take effect
+    pub applied: Vec<String>,
+    /// Requested settings skipped because their mechanism is missing
+    pub unavailable: Vec<String>,
+}
+
+impl Isolation {
+    /// Resolve against the tools available on this host
+    pub fn resolve(config: &EncoderProcessConfig) -> Self {
+        let online = std::fs::read_to_string("/sys/devices/system/cpu/online")
+            .ok()
+            .and_then(|list| parse_cpu_list(list.trim()).ok());
+        Self::resolve_with(config, online.as_deref(), |m| match m {
+            Mechanism::CpuQuota => find_in_path(m.program()).is_some() && has_user_systemd(),
+            _ => find_in_path(m.program()).is_some(),
+        })
+    }
+
+    /// Resolve with an explicit availability check and set of online CPUs
+    pub fn resolve_with(
+        config: &EncoderProcessConfig,
+        online_cpus: Option<&[usize]>,
+        available: impl Fn(Mechanism) -> bool,
+    ) -> Self {
+        let mut isolation = Self::default();
+        let add = |isolation: &mut Self, mechanism: Mechanism, label: String, args: Vec<String>| {
+            if available(mechanism) {
+                isolation.prefix.push(mechanism.program().to_string());
+                isolation.prefix.extend(args);
+                isolation.applied.push(label);
+            } else {
+                isolation
+                    .unavailable
+                    .push(format!("{label} ({} not found)", mechanism.program()));
+            }
+        };
+
+        // Outermost first: the scope must contain the whole exec chain
+        if let Some(quota) = config.cpu_quota_percent {
+            add(
+                &mut isolation,
+                Mechanism::CpuQuota,
+                format!("CPU quota {quota}%"),
+                vec![
+                    "--user".into(),
+                    "--scope".into(),
+                    "--quiet".into(),
+                    "--collect".into(),
+                    "-p".into(),
+                    format!("CPUQuota={quota}%"),
+                    "--".into(),
+                ],
+            );
+        }
+        if let Some(ref cpus) = config.cpu_affinity {
+            match parse_cpu_list(cpus) {
+                Ok(list) if online_cpus.is_none_or(|o| list.iter().all(|c| o.contains(c))) => {
+                    add(
+                        &mut isolation,
+                        Mechanism::Affinity,
+                        format!("CPUs {cpus}"),
+                        vec!["-c".into(), cpus.clone()],
+                    );
+                }
+                Ok(_) => isolation
+                    .unavailable
+                    .push(format!("CPUs {cpus} (not all online)")),
+                Err(e) => isolation.unavailable.push(format!("CPUs {cpus} ({e})")),
+            }
+        }
+        let io_args = match config.io_class {
+            IoClass::Unchanged => None,
+            IoClass::BestEffort => Some(("best-effort", vec!["-c", "2", "-n", "7"])),
+            IoClass::Idle => Some(("idle", vec!["-c", "3"])),
+        };
+        if let Some((name, args)) = io_args {
+            // -t: run anyway if the class cannot be set
+            let mut args: Vec<String> = args.into_iter().map(String::from).collect();
+            args.push("-t".into());
+            add(
+                &mut isolation,
+                Mechanism::Ionice,
+                format!("I/O class {name}"),
+                args,
+            );
+        }
+        if config.nice != 0 {
+            add(
+                &mut isolation,
+                Mechanism::Nice,
+                format!("nice {}", config.nice),
+                vec!["-n".into(), config.nice.to_string()],
+            );
+        }
+        isolation
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefix.is_empty()
+    }
+
+    /// Program and args to execute in place of `program args`
+    pub fn wrap(&self, program: &Path, args: Vec<String>) -> (PathBuf, Vec<String>) {
+        match self.prefix.split_first() {
+            None => (program.to_path_buf(), args),
+            Some((wrapper, rest)) => {
+                let mut wrapped = rest.to_vec();
+                wrapped.push(program.to_string_lossy().into_owned());
+                wrapped.extend(args);
+                (PathBuf::from(wrapper), wrapped)
+            }
+        }
+    }
+}
+
+/// Parse a CPU list like "0-3,6" into sorted CPU indices
+pub fn parse_cpu_list(list: &str) -> std::result::Result<Vec<usize>, String> {
+    let mut cpus = Vec::new();
+    for part in list.split(',') {
+        let part = part.trim();
+        let parse = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid CPU '{s}' in '{list}'"))
+        };
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    return Err(format!("invalid CPU range '{part}'"));
+                }
+                cpus.extend(start..=end);
+            }
+            None => cpus.push(parse(part)?),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(program))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// `systemd-run --user` needs a running user manager
+fn has_user_systemd() -> bool {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| Path::new(&dir).join("systemd/private").exists())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_config() -> EncoderProcessConfig {
+        EncoderProcessConfig {
+            nice: 10,
+            io_class: IoClass::Idle,
+            cpu_affinity: Some("2-3".to_string()),
+            cpu_quota_percent: Some(150),
+        }
+    }
+
+    #[test]
+    fn default_config_leaves_command_untouched() {
+        let isolation = Isolation::resolve_with(&EncoderProcessConfig::default(), None, |_| true);
+        assert!(isolation.is_empty());
+        let (program, args) = isolation.wrap(Path::new("ffmpeg"), vec!["-i".into()]);
+        assert_eq!(program, PathBuf::from("ffmpeg"));
+        assert_eq!(args, vec!["-i"]);
+    }
+
+    #[test]
+    fn wrappers_chain_outermost_scope_first() {
+        let isolation = Isolation::resolve_with(&full_config(), Some(&[0, 1, 2, 3]), |_| true);
+        let (program, args) = isolation.wrap(Path::new("/usr/bin/ffmpeg"), vec!["-y".into()]);
+
+        assert_eq!(program, PathBuf::from("systemd-run"));
+        let line = args.join(" ");
+        assert_eq!(
+            line,
+            "--user --scope --quiet --collect -p CPUQuota=150% -- \
+             taskset -c 2-3 ionice -c 3 -t nice -n 10 /usr/bin/ffmpeg -y"
+        );
+        assert_eq!(isolation.applied.len(), 4);
+        assert!(isolation.unavailable.is_empty());
+    }
+
+    #[test]
+    fn missing_mechanisms_are_skipped_and_reported() {
+        let isolation =
+            Isolation::resolve_with(&full_config(), Some(&[0, 1]), |m| m == Mechanism::Nice);
+        let (program, args) = isolation.wrap(Path::new("ffmpeg"), Vec::new());
+
+        assert_eq!(program, PathBuf::from("nice"));
+        assert_eq!(args, vec!["-n", "10", "ffmpeg"]);
+        assert_eq!(isolation.applied, vec!["nice 10"]);
+        assert_eq!(isolation.unavailable.len(), 3);
+        assert!(isolation.unavailable[1].contains("not all online"));
+    }
+
+    #[test]
+    fn cpu_lists_parse_ranges_and_singles() {
+        assert_eq!(parse_cpu_list("0-2,5,1").unwrap(), vec![0, 1, 2, 5]);
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a").is_err());
+        assert!(parse_cpu_list("").is_err());
+    }
+}
@@ -0,0 +1,241 @@
+#[cfg(test)]
+pub mod fake;
+pub mod isolation;
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use isolation::Isolation;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Stdio;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::process::Command;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The FFmpeg binaries ClipForge invokes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Ffmpeg,
+    Ffprobe,
+}
+
+/// Captured result of a tool run to completion
+#[derive(Debug, Clone, Default)]
+pub struct ToolOutput {
+    /// Exit code, `None` if terminated by a signal
+    pub code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl ToolOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// A running tool with piped stdio
+pub struct ToolProcess {
+    pub pid: Option<u32>,
+    pub stdin: Box<dyn AsyncWrite + Send + Sync + Unpin>,
+    pub stdout: Box<dyn AsyncRead + Send + Sync + Unpin>,
+    pub stderr: Box<dyn AsyncRead + Send + Sync + Unpin>,
+    /// Resolves with the exit code once the process ends
+    pub exit: BoxFuture<'static, Result<Option<i32>>>,
+}
+
+/// Executes FFmpeg and ffprobe.
+///
+/// Everything that shells out to FFmpeg goes through a runner so the binary
+/// location is configurable and tests can substitute a scripted fake.
+pub trait FfmpegRunner: Send + Sync {
+    /// Run a tool to completion, capturing its output
+    fn run(&self, tool: Tool, args: Vec<String>) -> BoxFuture<'_, Result<ToolOutput>>;
+
+    /// Start a long-running tool. Firing `kill` must terminate it.
+    fn spawn(
+        &self,
+        tool: Tool,
+        args: Vec<String>,
+        kill: oneshot::Receiver<()>,
+    ) -> Result<ToolProcess>;
+}
+
+/// Runs the real binaries, `ffmpeg`/`ffprobe` from PATH unless configured
+#[derive(Debug, Clone)]
+pub struct SystemRunner {
+    pub ffmpeg_path: PathBuf,
+    pub ffprobe_path: PathBuf,
+    /// Applied to spawned (long-running) FFmpeg processes only
+    pub isolation: Isolation,
+}
+
+impl Default for SystemRunner {
+    fn default() -> Self {
+        Self {
+            ffmpeg_path: PathBuf::from("ffmpeg"),
+            ffprobe_path: PathBuf::from("ffprobe"),
+            isolation: Isolation::default(),
+        }
+    }
+}
+
+impl SystemRunner {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            ffmpeg_path: config.paths.ffmpeg_path.clone(),
+            ffprobe_path: config.paths.ffprobe_path.clone(),
+            isolation: Isolation::resolve(&config.encoder_process),
+        }
+    }
+
+    fn command(&self, tool: Tool) -> Command {
+        match tool {
+            Tool::Ffmpeg => Command::new(&self.ffmpeg_path),
+            Tool::Ffprobe => Command::new(&self.ffprobe_path),
+        }
+    }
+}
+
+impl FfmpegRunner for SystemRunner {
+    fn run(&self, tool: Tool, args: Vec<String>) -> BoxFuture<'_, Result<ToolOutput>> {
+        Box::pin(async move {
+            let output = self
+                .command(tool)
+                .args(&args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output()
+                .await
+                .map_err(|e| spawn_error(tool, e))?;
+
+            Ok(ToolOutput {
+                code: output.status.code(),
+                stdout: output.stdout,
+                stderr: output.stderr,
+            })
+        })
+    }
+
+    fn spawn(
+        &self,
+        tool: Tool,
+        args: Vec<String>,
+        kill: oneshot::Receiver<()>,
+    ) -> Result<ToolProcess> {
+        if tool == Tool::Ffmpeg && !self.isolation.unavailable.is_empty() {
+            warn!(skipped = ?self.isolation.unavailable, "some encoder limits are unavailable");
+        }
+        let mut command = match tool {
+            Tool::Ffmpeg if !self.isolation.is_empty() => {
+                let (program, args) = self.isolation.wrap(&self.ffmpeg_path, args);
+                let mut command = Command::new(program);
+                command.args(args);
+                command
+            }
+            _ => {
+                let mut command = self.command(tool);
+                command.args(&args);
+                command
+            }
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(tool, e))?;
+
+        let stdin = child.stdin.take().expect("stdin was piped");
+        let stdout = child.stdout.take().expect("stdout was piped");
+        let stderr = child.stderr.take().expect("stderr was piped");
+
+        Ok(ToolProcess {
+            pid: child.id(),
+            stdin: Box::new(stdin),
+            stdout: Box::new(stdout),
+            stderr: Box::new(stderr),
+            exit: Box::pin(async move {
+                let status = tokio::select! {
+                    status = child.wait() => status,
+                    _ = kill => {
+                        let _ = child.start_kill();
+                        child.wait().await
+                    }
+                };
+                Ok(status?.code())
+            }),
+        })
+    }
+}
+
+fn spawn_error(tool: Tool, e: std::io::Error) -> Error {
+    if tool == Tool::Ffmpeg && e.kind() == std::io::ErrorKind::NotFound {
+        Error::FfmpegNotFound
+    } else {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn missing_ffmpeg_binary_is_reported() {
+        let runner = SystemRunner {
+            ffmpeg_path: PathBuf::from("/nonexistent/ffmpeg"),
+            ..SystemRunner::default()
+        };
+        let err = runner.run(Tool::Ffmpeg, Vec::new()).await.unwrap_err();
+        assert!(matches!(err, Error::FfmpegNotFound), "got: {err}");
+    }
+
+    #[tokio::test]
+    async fn configured_path_is_used() {
+        let runner = SystemRunner {
+            ffprobe_path: PathBuf::from("echo"),
+            ..SystemRunner::default()
+        };
+        let output = runner
+            .run(Tool::Ffprobe, vec!["probed".into()])
+            .await
+            .unwrap();
+        assert!(output.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "probed\n");
+    }
+
+    #[tokio::test]
+    async fn spawned_ffmpeg_runs_under_configured_niceness() {
+        let config = crate::config::EncoderProcessConfig {
+            nice: 7,
+            ..Default::default()
+        };
+        let runner = SystemRunner {
+            ffmpeg_path: PathBuf::from("sh"),
+            isolation: Isolation::resolve(&config),
+            ..SystemRunner::default()
+        };
+        if runner.isolation.is_empty() {
+            return; // no `nice` on this host
+        }
+
+        let (_kill_tx, kill_rx) = oneshot::channel();
+        let mut process = runner
+            .spawn(Tool::Ffmpeg, vec!["-c".into(), "nice".into()], kill_rx)
+            .unwrap();
+        let mut stdout = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut process.stdout, &mut stdout)
+            .await
+            .unwrap();
+        assert_eq!(process.exit.await.unwrap(), Some(0));
+        let base = std::process::Command::new("nice").output().unwrap().stdout;
+        let base: i32 = String::from_utf8_lossy(&base).trim().parse().unwrap();
+        assert_eq!(stdout.trim(), (base + 7).min(19).to_string());
+    }
+}
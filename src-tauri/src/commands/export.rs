@@ -1,6 +1,7 @@
 use crate::state::AppState;
 use clipforge_core::export::pipeline::{ExportJob, ExportPipeline};
 use clipforge_core::export::presets::ExportPreset;
+use clipforge_core::runner::SystemRunner;
 use std::path::PathBuf;
 use tauri::{AppHandle, Emitter, State};
 use tracing::info;
@@ -11,6 +12,7 @@ pub async fn get_export_presets() -> Result<Vec<ExportPreset>, String> {
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn start_export(
     app: AppHandle,
     state: State<'_, AppState>,
@@ -19,6 +21,7 @@ pub async fn start_export(
     trim_start: Option<f64>,
     trim_end: Option<f64>,
     output: Option<String>,
+    tonemap_sdr: Option<bool>,
 ) -> Result<String, String> {
     let preset = ExportPreset::all()
         .into_iter()
@@ -47,16 +50,18 @@ pub async fn start_export(
         preset,
         trim_start,
         trim_end,
+        tonemap_sdr: tonemap_sdr.unwrap_or(false),
     };
 
     let _ = app.emit("export-started", &output_path.to_string_lossy().to_string());
 
     // Run export in background
+    let runner = SystemRunner::from_config(&config);
     let app_handle = app.clone();
     let output_str = output_path.to_string_lossy().to_string();
 
     tokio::spawn(async move {
-        match ExportPipeline::run(&job).await {
+        match ExportPipeline::run(&runner, &job).await {
             Ok(()) => {
                 info!(output = %output_str, "export completed");
                 let _ = app_handle.emit("export-completed", &output_str);
@@ -1,5 +1,6 @@
 use crate::state::AppState;
 use clipforge_core::library::db::Recording;
+use clipforge_core::recording::PerformanceSummary;
 use tauri::State;
 
 #[tauri::command]
@@ -49,3 +50,15 @@ pub async fn get_recording(
         None => Ok(None),
     }
 }
+
+#[tauri::command]
+pub async fn get_recording_stats(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Option<PerformanceSummary>, String> {
+    let lib = state.library.lock().await;
+    match lib.as_ref() {
+        Some(lib) => lib.get_stats(&id).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
@@ -1,11 +1,19 @@
-use crate::state::{AppState, RecordingStatus};
+use crate::state::{AppState, RecorderState, RecordingStatus, SessionSlot};
 use clipforge_core::capture::x11::create_capture_source;
 use clipforge_core::encode::ffmpeg::build_recording_command;
-use clipforge_core::encode::hw_probe::select_best_encoder;
-use clipforge_core::process::FfmpegProcess;
+use clipforge_core::encode::hw_probe::select_encoder;
+use clipforge_core::library::{index, Library, SourceType};
+use clipforge_core::process::{FfmpegProcess, ProcessState};
+use clipforge_core::recording::{
+    recover_orphans, DropWarning, PerformanceSummary, RecordingSession, SessionOptions,
+};
+use clipforge_core::runner::{FfmpegRunner, SystemRunner};
 use serde::Serialize;
-use tauri::{AppHandle, Emitter, State};
-use tracing::{error, info};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{broadcast, Mutex};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Clone, Serialize)]
 pub struct RecordingState {
@@ -40,7 +48,7 @@ pub async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Resu
         return Err("No encoders available. Run encoder probe first.".to_string());
     }
 
-    let encoder = select_best_encoder(&encoders);
+    let encoder = select_encoder(&encoders, &config.recording.encoder);
     let source = create_capture_source(&config)
         .await
         .map_err(|e| e.to_string())?;
@@ -53,16 +61,38 @@ pub async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Resu
     // Ensure recording directory exists
     std::fs::create_dir_all(&config.paths.recordings_dir).map_err(|e| e.to_string())?;
 
-    let args = build_recording_command(&config, encoder, &source, &output_path).await;
+    let args = match build_recording_command(&config, encoder, &source, &output_path).await {
+        Ok(args) => args,
+        Err(e) => {
+            recorder.status = RecordingStatus::Idle;
+            return Err(e.to_string());
+        }
+    };
 
     info!(output = %output_path.display(), "starting recording");
 
-    match FfmpegProcess::spawn(args).await {
-        Ok(process) => {
-            recorder.process = Some(process);
+    match RecordingSession::start(
+        Arc::new(SystemRunner::from_config(&config)),
+        args,
+        SessionOptions::from_config(&config.recording)
+            .with_journal(&config.paths.recording_journal_dir()),
+    )
+    .await
+    {
+        Ok(session) => {
+            forward_drop_warnings(app.clone(), session.subscribe_warnings());
+            let slot = recorder.session.clone();
+            watch_part_end(
+                app.clone(),
+                state.recorder.clone(),
+                slot.clone(),
+                session.process(),
+            );
+            let output_path = session.current_output().to_path_buf();
+            *slot.lock().await = Some(session);
             recorder.status = RecordingStatus::Recording;
             recorder.output_path = Some(output_path.clone());
-            recorder.start_time = Some(std::time::Instant::now());
+            recorder.recorded_secs = 0;
 
             let _ = app.emit(
                 "recording-state-changed",
@@ -80,12 +110,57 @@ pub async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Resu
                 let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
                 loop {
                     interval.tick().await;
-                    let rec = recorder_state.lock().await;
-                    if rec.status != RecordingStatus::Recording {
+                    match recorder_state.lock().await.status {
+                        RecordingStatus::Recording => {}
+                        RecordingStatus::Paused => continue,
+                        _ => break,
+                    }
+
+                    // Never take the recorder lock while holding the session
+                    let mut slot_guard = slot.lock().await;
+                    let Some(session) = slot_guard.as_mut() else {
                         break;
+                    };
+
+                    // Capture froze: finalize this part and keep going in a new one
+                    if session.is_stalled() {
+                        match session.restart().await {
+                            Ok(part) => {
+                                let _ = app_handle
+                                    .emit("recording-stalled", part.to_string_lossy().to_string());
+                                watch_part_end(
+                                    app_handle.clone(),
+                                    recorder_state.clone(),
+                                    slot.clone(),
+                                    session.process(),
+                                );
+                            }
+                            Err(e) => {
+                                // Keep what was recorded rather than leave parts unjoined
+                                error!(error = %e, "failed to restart stalled recording");
+                                drop(slot_guard);
+                                let app_state = app_handle.state::<AppState>();
+                                let _ = finalize_recording(&app_handle, &app_state).await;
+                                break;
+                            }
+                        }
                     }
-                    let elapsed = rec.start_time.map(|t| t.elapsed().as_secs()).unwrap_or(0);
-                    let _ = app_handle.emit("recording-timer", elapsed);
+
+                    // FFmpeg died on its own: keep what was recorded and report why
+                    let exit = session.process().exit();
+                    if let Some(exit) = exit.filter(|e| e.failure.is_some()) {
+                        error!(reason = ?exit.failure, "recording process failed");
+                        drop(slot_guard);
+                        let _ = app_handle.emit("recording-failed", &exit);
+                        let app_state = app_handle.state::<AppState>();
+                        let _ = finalize_recording(&app_handle, &app_state).await;
+                        break;
+                    }
+
+                    let secs = session.recorded_time().as_secs();
+                    drop(slot_guard);
+                    recorder_state.lock().await.recorded_secs = secs;
+                    let _ = app_handle.emit("recording-timer", secs);
                 }
             });
 
@@ -99,40 +174,177 @@ pub async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Resu
     }
 }
 
+/// Continue a split recording in its next part as soon as FFmpeg ends the
+/// current one at its limit. Every new part needs its own watch.
+fn watch_part_end(
+    app: AppHandle,
+    recorder_state: Arc<Mutex<RecorderState>>,
+    slot: SessionSlot,
+    process: &FfmpegProcess,
+) {
+    let mut state_rx = process.subscribe_state();
+    tokio::spawn(async move {
+        if state_rx
+            .wait_for(|s| *s == ProcessState::Stopped)
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let part = {
+            let mut slot_guard = slot.lock().await;
+            // Paused, stopped or restarted by someone else
+            let Some(session) = slot_guard.as_mut().filter(|s| s.part_complete()) else {
+                return;
+            };
+            match session.next_part().await {
+                Ok(part) => {
+                    let part = part.to_path_buf();
+                    watch_part_end(
+                        app.clone(),
+                        recorder_state.clone(),
+                        slot.clone(),
+                        session.process(),
+                    );
+                    part
+                }
+                Err(e) => {
+                    error!(error = %e, "failed to continue recording in a new part");
+                    return;
+                }
+            }
+        };
+        let _ = app.emit("recording-part-started", part.to_string_lossy());
+        recorder_state.lock().await.output_path = Some(part);
+    });
+}
+
+/// Emit live drop warnings until the session ends
+fn forward_drop_warnings(app: AppHandle, mut warnings: broadcast::Receiver<DropWarning>) {
+    tokio::spawn(async move {
+        loop {
+            match warnings.recv().await {
+                Ok(warning) => {
+                    let _ = app.emit("recording-drop-warning", &warning);
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
 #[tauri::command]
-pub async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
-    let mut recorder = state.recorder.lock().await;
+pub async fn toggle_recording_pause(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<RecordingStatus, String> {
+    toggle_pause(&app, &state).await
+}
 
-    if recorder.status != RecordingStatus::Recording {
-        return Err("Not recording".to_string());
-    }
+/// Pause a running recording or resume a paused one. Used by the command
+/// and the hotkey.
+pub async fn toggle_pause(app: &AppHandle, state: &AppState) -> Result<RecordingStatus, String> {
+    let slot = {
+        let recorder = state.recorder.lock().await;
+        match recorder.status {
+            RecordingStatus::Recording | RecordingStatus::Paused => recorder.session.clone(),
+            _ => return Err("Not recording".to_string()),
+        }
+    };
+
+    // The session knows best whether it is paused; a stall restart may
+    // still be running, so wait for it without holding the recorder
+    let (next, current, secs) = {
+        let mut slot_guard = slot.lock().await;
+        let session = slot_guard.as_mut().ok_or("No recording session")?;
+        let next = if session.is_paused() {
+            session.resume().await.map_err(|e| e.to_string())?;
+            watch_part_end(
+                app.clone(),
+                state.recorder.clone(),
+                slot.clone(),
+                session.process(),
+            );
+            info!("recording resumed");
+            RecordingStatus::Recording
+        } else {
+            session.pause().await.map_err(|e| e.to_string())?;
+            info!("recording paused");
+            RecordingStatus::Paused
+        };
+        (
+            next,
+            session.current_output().to_path_buf(),
+            session.recorded_time().as_secs(),
+        )
+    };
+
+    let mut recorder = state.recorder.lock().await;
+    recorder.status = next;
+    recorder.output_path = Some(current);
+    recorder.recorded_secs = secs;
 
-    recorder.status = RecordingStatus::Stopping;
     let _ = app.emit(
         "recording-state-changed",
         RecordingState {
-            status: RecordingStatus::Stopping,
-            elapsed_secs: 0,
+            status: next,
+            elapsed_secs: secs,
             file_path: recorder
                 .output_path
                 .as_ref()
                 .map(|p| p.to_string_lossy().to_string()),
         },
     );
+    Ok(next)
+}
 
-    if let Some(ref mut process) = recorder.process {
-        process.stop_graceful().await.map_err(|e| e.to_string())?;
-    }
+#[tauri::command]
+pub async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    finalize_recording(&app, &state).await
+}
 
-    let output_path = recorder.output_path.take();
-    recorder.process = None;
-    recorder.status = RecordingStatus::Idle;
-    recorder.start_time = None;
+/// Stop capture, join the parts and index the result, then go idle. Used
+/// by stop and when capture cannot go on. Returns the recording's path.
+async fn finalize_recording(app: &AppHandle, state: &AppState) -> Result<String, String> {
+    let slot = {
+        let mut recorder = state.recorder.lock().await;
+        if !matches!(
+            recorder.status,
+            RecordingStatus::Recording | RecordingStatus::Paused
+        ) {
+            return Err("Not recording".to_string());
+        }
 
-    let path_str = output_path
-        .as_ref()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_default();
+        recorder.status = RecordingStatus::Stopping;
+        let _ = app.emit(
+            "recording-state-changed",
+            RecordingState {
+                status: RecordingStatus::Stopping,
+                elapsed_secs: 0,
+                file_path: recorder
+                    .output_path
+                    .as_ref()
+                    .map(|p| p.to_string_lossy().to_string()),
+            },
+        );
+        recorder.session.clone()
+    };
+
+    // Waits for a part change in progress to settle
+    let session = slot.lock().await.take();
+    let finished = match session {
+        Some(session) => session.finish().await.map(Some),
+        None => Ok(None),
+    };
+
+    {
+        let mut recorder = state.recorder.lock().await;
+        recorder.output_path = None;
+        recorder.recorded_secs = 0;
+        recorder.status = RecordingStatus::Idle;
+    }
 
     let _ = app.emit(
         "recording-state-changed",
@@ -143,15 +355,37 @@ pub async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Resul
         },
     );
 
-    // Index the recording in the library
-    if let Some(ref path) = output_path {
-        let state_clone = state.inner().library.clone();
+    // Parts are left on disk if joining failed
+    let finished = match finished {
+        Ok(finished) => finished,
+        Err(e) => {
+            error!(error = %e, "failed to finalize recording");
+            return Err(e.to_string());
+        }
+    };
+
+    // Index the recording (every part of a split one) in the library
+    let path_str = finished
+        .as_ref()
+        .map(|f| f.path.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if let Some(finished) = finished {
+        let state_clone = state.library.clone();
         let config = state.config.read().await;
         let thumb_dir = config.paths.thumbnails_dir.clone();
-        let path = path.clone();
+        let runner = SystemRunner::from_config(&config);
 
         tokio::spawn(async move {
-            if let Err(e) = index_recording(&state_clone, &path, &thumb_dir).await {
+            if let Err(e) = index_saved_parts(
+                &runner,
+                &state_clone,
+                &finished.parts,
+                &thumb_dir,
+                SourceType::Recording,
+                Some(finished.performance),
+            )
+            .await
+            {
                 error!(error = %e, "failed to index recording");
             }
         });
@@ -161,68 +395,111 @@ pub async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Resul
     Ok(path_str)
 }
 
-async fn index_recording(
-    library: &std::sync::Arc<tokio::sync::Mutex<Option<clipforge_core::library::Library>>>,
-    path: &std::path::Path,
-    thumb_dir: &std::path::Path,
+/// Add the parts of a finished recording to the library as one session
+async fn index_saved_parts(
+    runner: &dyn FfmpegRunner,
+    library: &Arc<Mutex<Option<Library>>>,
+    parts: &[PathBuf],
+    thumb_dir: &Path,
+    source: SourceType,
+    performance: Option<PerformanceSummary>,
 ) -> Result<(), String> {
-    use clipforge_core::library::db::{generate_thumbnail, probe_media, Recording};
-
-    let info = probe_media(path).await.map_err(|e| e.to_string())?;
-
-    let id = uuid::Uuid::new_v4().to_string();
-    let title = path
-        .file_stem()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "Untitled".to_string());
-
-    // Generate thumbnail
-    let _ = std::fs::create_dir_all(thumb_dir);
-    let thumb_path = thumb_dir.join(format!("{id}.jpg"));
-    let _ = generate_thumbnail(path, &thumb_path).await;
-
-    let recording = Recording {
-        id,
-        title,
-        file_path: path.to_string_lossy().to_string(),
-        file_size: info.file_size,
-        duration: info.duration,
-        resolution: format!("{}x{}", info.width, info.height),
-        fps: info.fps,
-        codec: info.codec,
-        container: path
-            .extension()
-            .map(|e| e.to_string_lossy().to_string())
-            .unwrap_or_default(),
-        source_type: "recording".to_string(),
-        game_name: None,
-        created_at: chrono::Local::now().to_rfc3339(),
-        thumbnail_path: if thumb_path.exists() {
-            Some(thumb_path.to_string_lossy().to_string())
-        } else {
-            None
-        },
-    };
+    let recordings = index::describe_parts(runner, parts, thumb_dir, source)
+        .await
+        .map_err(|e| e.to_string())?;
 
     let lib = library.lock().await;
     if let Some(ref lib) = *lib {
-        lib.insert(&recording).map_err(|e| e.to_string())?;
+        index::store_parts(lib, &recordings, performance.as_ref()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Repair recordings left unfinished by a crash, add them to the library
+/// flagged as recovered and tell the UI which files came back
+pub(crate) async fn recover_unfinished(app: &AppHandle, state: &AppState) {
+    let runner = state.runner().await;
+    let (journal_dir, thumb_dir) = {
+        let config = state.config.read().await;
+        (
+            config.paths.recording_journal_dir(),
+            config.paths.thumbnails_dir.clone(),
+        )
+    };
+
+    let recovered = match recover_orphans(runner.as_ref(), &journal_dir).await {
+        Ok(recovered) => recovered,
+        Err(e) => {
+            warn!(error = %e, "failed to check for unfinished recordings");
+            return;
+        }
+    };
+    if recovered.is_empty() {
+        return;
+    }
+
+    let mut paths = Vec::new();
+    for recording in &recovered {
+        let described = index::describe_parts(
+            runner.as_ref(),
+            &recording.parts,
+            &thumb_dir,
+            SourceType::Recording,
+        )
+        .await;
+        match described {
+            Ok(mut recordings) => {
+                for r in &mut recordings {
+                    r.recovered = true;
+                }
+                let lib = state.library.lock().await;
+                if let Some(ref lib) = *lib {
+                    if let Err(e) = index::store_parts(lib, &recordings, None) {
+                        error!(error = %e, "failed to index recovered recording");
+                    }
+                }
+            }
+            Err(e) => error!(error = %e, "failed to index recovered recording"),
+        }
+        paths.extend(
+            recording
+                .parts
+                .iter()
+                .map(|p| p.to_string_lossy().to_string()),
+        );
     }
 
+    info!(count = paths.len(), "recovered unfinished recordings");
+    let _ = app.emit("recordings-recovered", paths);
+}
+
+/// Add a finished file to the library, locking it only for the insert
+pub(crate) async fn index_saved_file(
+    runner: &dyn FfmpegRunner,
+    library: &Arc<Mutex<Option<Library>>>,
+    path: &Path,
+    thumb_dir: &Path,
+    source: SourceType,
+    performance: Option<PerformanceSummary>,
+) -> Result<(), String> {
+    let recording = index::describe(runner, path, thumb_dir, source)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let lib = library.lock().await;
+    if let Some(ref lib) = *lib {
+        index::store(lib, &recording, performance.as_ref()).map_err(|e| e.to_string())?;
+    }
     Ok(())
 }
 
 #[tauri::command]
 pub async fn get_recording_status(state: State<'_, AppState>) -> Result<RecordingState, String> {
     let recorder = state.recorder.lock().await;
-    let elapsed = recorder
-        .start_time
-        .map(|t| t.elapsed().as_secs())
-        .unwrap_or(0);
 
     Ok(RecordingState {
         status: recorder.status,
-        elapsed_secs: elapsed,
+        elapsed_secs: recorder.recorded_secs,
         file_path: recorder
             .output_path
             .as_ref()
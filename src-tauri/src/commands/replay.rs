@@ -1,12 +1,42 @@
-use crate::state::AppState;
+use crate::commands::recording::index_saved_file;
+use crate::state::{AppState, ReplayState};
 use clipforge_core::capture::x11::create_capture_source;
-use clipforge_core::encode::ffmpeg::build_replay_command;
-use clipforge_core::encode::hw_probe::select_best_encoder;
-use clipforge_core::process::FfmpegProcess;
+use clipforge_core::config::Config;
+use clipforge_core::encode::ffmpeg::{build_clip_encoding, build_replay_command};
+use clipforge_core::encode::hw_probe::{select_encoder, EncoderInfo};
+use clipforge_core::library::SourceType;
+use clipforge_core::process::{FfmpegProcess, ProcessState};
+use clipforge_core::replay::daemon::{DaemonPaths, ReplayLock};
+use clipforge_core::replay::lookahead::{PendingSaveStatus, PendingSaves, POLL_INTERVAL};
+use clipforge_core::replay::pause::{
+    PauseReason, SignalSource, SystemSignals, PAUSE_POLL_INTERVAL,
+};
+use clipforge_core::replay::record::COLLECT_INTERVAL;
 use clipforge_core::replay::ring::ReplayRing;
 use clipforge_core::replay::save::save_replay;
-use tauri::{AppHandle, Emitter, State};
-use tracing::{error, info};
+use clipforge_core::replay::{
+    AutoPause, BufferRecording, Migration, PauseChange, RingFit, RingPlan,
+};
+use clipforge_core::runner::SystemRunner;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayStatus {
+    pub active: bool,
+    /// Look-ahead saves still waiting for footage
+    pub pending_saves: Vec<PendingSaveStatus>,
+    /// Output of a recording started from the buffer
+    pub recording: Option<String>,
+    /// Why the capture is paused, while an auto-pause condition holds
+    pub paused: Option<PauseReason>,
+}
 
 #[tauri::command]
 pub async fn toggle_replay_buffer(
@@ -16,16 +46,32 @@ pub async fn toggle_replay_buffer(
     let mut replay = state.replay.lock().await;
 
     if replay.active {
+        // Save a recording from the buffer and waiting look-ahead saves
+        // before their segments go away
+        if let (Some(recording), Some(ring)) = (replay.take_recording(), replay.ring.as_ref()) {
+            let _ = finish_buffer_recording(&app, recording, ring).await;
+        }
+        let ReplayState { ring, pending, .. } = &mut *replay;
+        if let Some(ring) = ring {
+            flush_pending_saves(&app, pending, ring).await;
+        }
+
         // Stop replay buffer
         if let Some(ref mut process) = replay.process {
             let _ = process.stop_graceful().await;
         }
+        if let Some(watcher) = replay.pause_watcher.take() {
+            watcher.abort();
+        }
+        replay.migration = None;
         if let Some(ref ring) = replay.ring {
             let _ = ring.cleanup();
         }
         replay.process = None;
         replay.ring = None;
+        replay.lock = None;
         replay.active = false;
+        replay.auto_pause = AutoPause::default();
 
         let _ = app.emit("replay-state-changed", false);
         info!("replay buffer stopped");
@@ -39,28 +85,45 @@ pub async fn toggle_replay_buffer(
             return Err("No encoders available".to_string());
         }
 
-        let encoder = select_best_encoder(&encoders);
-        let source = create_capture_source(&config)
-            .await
-            .map_err(|e| e.to_string())?;
-
         // Ensure cache directory
         std::fs::create_dir_all(&config.paths.replay_cache_dir).map_err(|e| e.to_string())?;
 
-        let ring = ReplayRing::new(
-            &config.paths.replay_cache_dir,
-            config.replay.segment_secs,
-            config.replay.max_segments,
-        );
+        // Another buffer (e.g. `clipforge replay`) may own the ring
+        let lock =
+            ReplayLock::acquire(&DaemonPaths::for_cache_dir(&config.paths.replay_cache_dir).lock)
+                .map_err(|e| e.to_string())?;
+
+        // Clips cut off a keyframe are re-encoded like the capture
+        let encoder = select_encoder(&encoders, &config.recording.encoder);
+        let ring = ReplayRing::for_config(&config)
+            .with_clip_encoding(build_clip_encoding(&config, encoder));
         ring.cleanup().map_err(|e| e.to_string())?;
 
-        let args = build_replay_command(&config, encoder, &source).await;
+        // Refuse a ring that cannot fit in the cache dir or memory budget
+        match RingPlan::for_config(&config).check() {
+            Ok(RingFit::Fits) => {}
+            Ok(RingFit::Tight(message)) => {
+                warn!(%message, "replay buffer close to its limit");
+                let _ = app.emit("replay-buffer-warning", &message);
+            }
+            Err(e) => {
+                error!(error = %e, "replay buffer does not fit");
+                return Err(e.to_string());
+            }
+        }
 
-        match FfmpegProcess::spawn(args).await {
+        match spawn_replay_capture(&app, &state.replay, &config, &encoders, 0).await {
             Ok(process) => {
+                replay.migration = Migration::spawn(&ring);
+                let watcher = watch_auto_pause(app.clone(), state.replay.clone());
+                if let Some(previous) = replay.pause_watcher.replace(watcher) {
+                    previous.abort();
+                }
                 replay.process = Some(process);
                 replay.ring = Some(ring);
+                replay.lock = Some(lock);
                 replay.active = true;
+                replay.auto_pause = AutoPause::new(config.replay.auto_pause.clone());
 
                 let _ = app.emit("replay-state-changed", true);
                 info!("replay buffer started");
@@ -68,47 +131,463 @@ pub async fn toggle_replay_buffer(
             }
             Err(e) => {
                 error!(error = %e, "failed to start replay buffer");
-                Err(e.to_string())
+                Err(e)
             }
         }
     }
 }
 
+/// Start capturing into the ring from segment `first_segment`, watching
+/// the process for failures
+async fn spawn_replay_capture(
+    app: &AppHandle,
+    replay_state: &Arc<Mutex<ReplayState>>,
+    config: &Config,
+    encoders: &[EncoderInfo],
+    first_segment: u64,
+) -> Result<FfmpegProcess, String> {
+    let encoder = select_encoder(encoders, &config.recording.encoder);
+    let source = create_capture_source(config)
+        .await
+        .map_err(|e| e.to_string())?;
+    let args = build_replay_command(config, encoder, &source, first_segment)
+        .await
+        .map_err(|e| e.to_string())?;
+    let process = FfmpegProcess::spawn(&SystemRunner::from_config(config), args)
+        .await
+        .map_err(|e| e.to_string())?;
+    watch_replay_process(app.clone(), replay_state.clone(), &process);
+    Ok(process)
+}
+
+/// Pause the capture while an auto-pause condition holds and resume it
+/// once none does, until the buffer stops
+fn watch_auto_pause(app: AppHandle, replay_state: Arc<Mutex<ReplayState>>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PAUSE_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = app.state::<AppState>();
+            // Sample without holding the buffer; probes run external tools
+            let pause_config = state.config.read().await.replay.auto_pause.clone();
+            let signals = SystemSignals.sample(&pause_config).await;
+
+            let mut replay = replay_state.lock().await;
+            if !replay.active {
+                break;
+            }
+            // Pausing would cut a recording from the buffer short
+            if replay.recording.is_some() {
+                continue;
+            }
+            replay.auto_pause.set_config(pause_config);
+            match replay.auto_pause.update(&signals, Instant::now()) {
+                Some(PauseChange::Paused(reason)) => {
+                    let ReplayState { ring, pending, .. } = &mut *replay;
+                    if let Some(ring) = ring {
+                        flush_pending_saves(&app, pending, ring).await;
+                    }
+                    if let Some(mut process) = replay.process.take() {
+                        let _ = process.stop_graceful().await;
+                    }
+                    let _ = app.emit("replay-paused", reason);
+                    info!(reason = reason.label(), "replay buffer paused");
+                }
+                Some(PauseChange::Resumed) => {
+                    // Footage from before the pause stays saveable
+                    let resumed = match replay.ring {
+                        Some(ref ring) => ring.resume().map_err(|e| e.to_string()),
+                        None => Err("No replay ring".to_string()),
+                    };
+                    let config = state.config.read().await;
+                    let encoders = state.encoders.read().await;
+                    let spawned = match resumed {
+                        Ok(first_segment) => {
+                            spawn_replay_capture(
+                                &app,
+                                &replay_state,
+                                &config,
+                                &encoders,
+                                first_segment,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    match spawned {
+                        Ok(process) => {
+                            // The settings may have changed while paused
+                            if let Some(ring) = replay.ring.as_mut() {
+                                let encoder = select_encoder(&encoders, &config.recording.encoder);
+                                ring.clip_encoding = Some(build_clip_encoding(&config, encoder));
+                            }
+                            replay.process = Some(process);
+                            let _ = app.emit("replay-resumed", ());
+                            info!("replay buffer resumed");
+                        }
+                        Err(e) => {
+                            error!(error = %e, "failed to resume replay buffer");
+                            replay.migration = None;
+                            replay.ring = None;
+                            replay.lock = None;
+                            replay.active = false;
+                            replay.auto_pause = AutoPause::default();
+                            let _ = app.emit("replay-state-changed", false);
+                            break;
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+    })
+}
+
+/// Deactivate the buffer and report why if FFmpeg exits on its own
+fn watch_replay_process(
+    app: AppHandle,
+    replay_state: Arc<Mutex<ReplayState>>,
+    process: &FfmpegProcess,
+) {
+    let mut state_rx = process.subscribe_state();
+    tokio::spawn(async move {
+        if state_rx
+            .wait_for(|s| *s == ProcessState::Failed)
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut replay = replay_state.lock().await;
+        let Some(exit) = replay.process.as_ref().and_then(|p| p.exit()) else {
+            return;
+        };
+        error!(reason = ?exit.failure, "replay buffer process failed");
+        if let (Some(recording), Some(ring)) = (replay.take_recording(), replay.ring.as_ref()) {
+            let _ = finish_buffer_recording(&app, recording, ring).await;
+        }
+        let ReplayState { ring, pending, .. } = &mut *replay;
+        if let Some(ring) = ring {
+            flush_pending_saves(&app, pending, ring).await;
+        }
+        replay.process = None;
+        replay.migration = None;
+        replay.ring = None;
+        replay.lock = None;
+        replay.active = false;
+
+        let _ = app.emit("replay-failed", &exit);
+        let _ = app.emit("replay-state-changed", false);
+    });
+}
+
 #[tauri::command]
 pub async fn save_replay_clip(
     app: AppHandle,
     state: State<'_, AppState>,
     seconds: Option<u32>,
+    after_secs: Option<u32>,
 ) -> Result<String, String> {
-    let replay = state.replay.lock().await;
+    save_replay_now(&app, &state, seconds, after_secs.unwrap_or(0)).await
+}
+
+/// Save `seconds` (default `replay.save_secs`) of replay, waiting for
+/// `after_secs` more first. Used by the command, tray menu and hotkeys.
+pub async fn save_replay_now(
+    app: &AppHandle,
+    state: &AppState,
+    seconds: Option<u32>,
+    after: u32,
+) -> Result<String, String> {
+    let mut replay = state.replay.lock().await;
 
     if !replay.active {
         return Err("Replay buffer is not active".to_string());
     }
+    // A paused buffer still saves what it recorded, but gets no more
+    if let (Some(reason), true) = (replay.auto_pause.paused(), after > 0) {
+        return Err(format!("Replay buffer is paused ({})", reason.label()));
+    }
 
-    let ring = replay.ring.as_ref().ok_or("No replay ring")?;
+    let ReplayState { ring, pending, .. } = &mut *replay;
+    let ring = ring.as_ref().ok_or("No replay ring")?;
     let config = state.config.read().await;
 
-    let duration = seconds.unwrap_or(30);
+    let duration = seconds.unwrap_or(config.replay.save_secs);
     let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
     let filename = format!("replay_{timestamp}.mkv");
     let output_path = config.paths.replays_dir.join(&filename);
 
     std::fs::create_dir_all(&config.paths.replays_dir).map_err(|e| e.to_string())?;
 
-    let result = save_replay(ring, duration, &output_path)
+    // Look-ahead saves are queued (or merged into one already waiting) and
+    // written once the ring has the footage
+    if after > 0 {
+        let was_idle = pending.is_empty();
+        let (_, path) = pending
+            .request(ring, duration, after, output_path)
+            .map_err(|e| e.to_string())?;
+        let _ = app.emit("replay-save-pending", pending.status(ring));
+        if was_idle {
+            run_pending_saves(app.clone(), state.replay.clone());
+        }
+        info!(path = %path.display(), seconds = duration, after_secs = after, "replay save pending");
+        return Ok(path.to_string_lossy().to_string());
+    }
+
+    let runner = SystemRunner::from_config(&config);
+    let result = save_replay(&runner, ring, duration, 0, &output_path)
         .await
         .map_err(|e| e.to_string())?;
 
     let path_str = result.to_string_lossy().to_string();
-    let _ = app.emit("replay-saved", &path_str);
+    announce_replay(app, runner, result, config.paths.thumbnails_dir.clone());
 
     info!(path = %path_str, seconds = duration, "replay saved");
     Ok(path_str)
 }
 
+/// Tell the UI about a saved replay and index it in the background
+fn announce_replay(app: &AppHandle, runner: SystemRunner, path: PathBuf, thumb_dir: PathBuf) {
+    let _ = app.emit("replay-saved", path.to_string_lossy());
+    let library = app.state::<AppState>().library.clone();
+    tokio::spawn(async move {
+        if let Err(e) = index_saved_file(
+            &runner,
+            &library,
+            &path,
+            &thumb_dir,
+            SourceType::Replay,
+            None,
+        )
+        .await
+        {
+            error!(error = %e, "failed to index replay");
+        }
+    });
+}
+
+/// Write look-ahead saves as their windows are recorded, until none wait
+fn run_pending_saves(app: AppHandle, replay_state: Arc<Mutex<ReplayState>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut replay = replay_state.lock().await;
+            let ReplayState { ring, pending, .. } = &mut *replay;
+            // A stopped buffer flushes its saves itself
+            let Some(ring) = ring.as_ref() else {
+                break;
+            };
+            let due = match pending.take_due(ring) {
+                Ok(due) => due,
+                Err(e) => {
+                    warn!(error = %e, "could not check pending replay saves");
+                    continue;
+                }
+            };
+            if due.is_empty() {
+                continue;
+            }
+
+            let state = app.state::<AppState>();
+            let config = state.config.read().await;
+            let runner = SystemRunner::from_config(&config);
+            for save in due {
+                match save.save(&runner, ring).await {
+                    Ok(path) => {
+                        info!(path = %path.display(), "pending replay saved");
+                        announce_replay(
+                            &app,
+                            runner.clone(),
+                            path,
+                            config.paths.thumbnails_dir.clone(),
+                        );
+                    }
+                    Err(e) => {
+                        error!(error = %e, "failed to save pending replay");
+                        let _ = app.emit("replay-save-failed", e.to_string());
+                    }
+                }
+            }
+            let _ = app.emit("replay-save-pending", pending.status(ring));
+            if pending.is_empty() {
+                break;
+            }
+        }
+    });
+}
+
+/// Save every waiting look-ahead save with the footage recorded so far
+async fn flush_pending_saves(app: &AppHandle, pending: &mut PendingSaves, ring: &ReplayRing) {
+    let saves = pending.take_all();
+    if saves.is_empty() {
+        return;
+    }
+    let state = app.state::<AppState>();
+    let config = state.config.read().await;
+    let runner = SystemRunner::from_config(&config);
+    for save in saves {
+        match save.save(&runner, ring).await {
+            Ok(path) => announce_replay(
+                app,
+                runner.clone(),
+                path,
+                config.paths.thumbnails_dir.clone(),
+            ),
+            Err(e) => error!(error = %e, "failed to save pending replay"),
+        }
+    }
+    let _ = app.emit("replay-save-pending", Vec::<PendingSaveStatus>::new());
+}
+
+#[tauri::command]
+pub async fn start_buffer_recording(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let mut replay = state.replay.lock().await;
+
+    if !replay.active {
+        return Err("Replay buffer is not active".to_string());
+    }
+    if replay.recording.is_some() {
+        return Err("Already recording from the buffer".to_string());
+    }
+    if let Some(reason) = replay.auto_pause.paused() {
+        return Err(format!("Replay buffer is paused ({})", reason.label()));
+    }
+
+    let ring = replay.ring.as_ref().ok_or("No replay ring")?;
+    let config = state.config.read().await;
+    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
+    let output_path = config
+        .paths
+        .recordings_dir
+        .join(format!("recording_{timestamp}.mkv"));
+    std::fs::create_dir_all(&config.paths.recordings_dir).map_err(|e| e.to_string())?;
+
+    let recording = BufferRecording::start(ring, &output_path)
+        .await
+        .map_err(|e| e.to_string())?;
+    let buffered = recording.duration();
+    replay.recording = Some(recording);
+    replay.collector = Some(collect_buffer_segments(app.clone(), state.replay.clone()));
+
+    let path_str = output_path.to_string_lossy().to_string();
+    let _ = app.emit("buffer-recording-changed", Some(&path_str));
+    info!(path = %path_str, buffered_secs = buffered, "recording from replay buffer");
+    Ok(path_str)
+}
+
+#[tauri::command]
+pub async fn stop_buffer_recording(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let mut replay = state.replay.lock().await;
+    let recording = replay
+        .take_recording()
+        .ok_or("Not recording from the buffer")?;
+    let ring = replay.ring.as_ref().ok_or("No replay ring")?;
+    finish_buffer_recording(&app, recording, ring).await
+}
+
+/// Copy finished segments out of the ring until the recording ends, and
+/// warn the UI when some were overwritten first
+fn collect_buffer_segments(
+    app: AppHandle,
+    replay_state: Arc<Mutex<ReplayState>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(COLLECT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut replay = replay_state.lock().await;
+            let ReplayState {
+                ring: Some(ref ring),
+                recording: Some(ref mut recording),
+                ..
+            } = *replay
+            else {
+                break;
+            };
+            match recording.collect(ring).await {
+                Ok(collected) if collected.missed > 0 => {
+                    let message = format!(
+                        "{} segments were overwritten before they could be recorded",
+                        collected.missed
+                    );
+                    let _ = app.emit("replay-buffer-warning", &message);
+                }
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, "could not collect replay segments"),
+            }
+        }
+    })
+}
+
+/// Join a recording from the buffer, index it and tell the UI it ended
+async fn finish_buffer_recording(
+    app: &AppHandle,
+    recording: BufferRecording,
+    ring: &ReplayRing,
+) -> Result<String, String> {
+    let state = app.state::<AppState>();
+    let config = state.config.read().await;
+    let runner = SystemRunner::from_config(&config);
+    if recording.missed() > 0 {
+        warn!(
+            missed = recording.missed(),
+            "segments were overwritten before they could be recorded"
+        );
+    }
+
+    let result = recording.finish(&runner, ring).await;
+    let _ = app.emit("buffer-recording-changed", None::<String>);
+    let path = result.map_err(|e| {
+        error!(error = %e, "failed to save recording from replay buffer");
+        e.to_string()
+    })?;
+
+    let library = state.library.clone();
+    let thumb_dir = config.paths.thumbnails_dir.clone();
+    let indexed = path.clone();
+    tokio::spawn(async move {
+        if let Err(e) = index_saved_file(
+            &runner,
+            &library,
+            &indexed,
+            &thumb_dir,
+            SourceType::Recording,
+            None,
+        )
+        .await
+        {
+            error!(error = %e, "failed to index recording");
+        }
+    });
+
+    let path_str = path.to_string_lossy().to_string();
+    info!(path = %path_str, "recording from replay buffer saved");
+    Ok(path_str)
+}
+
 #[tauri::command]
-pub async fn get_replay_status(state: State<'_, AppState>) -> Result<bool, String> {
+pub async fn get_replay_status(state: State<'_, AppState>) -> Result<ReplayStatus, String> {
     let replay = state.replay.lock().await;
-    Ok(replay.active)
+    Ok(ReplayStatus {
+        active: replay.active,
+        pending_saves: replay
+            .ring
+            .as_ref()
+            .map(|ring| replay.pending.status(ring))
+            .unwrap_or_default(),
+        recording: replay
+            .recording
+            .as_ref()
+            .map(|r| r.output().to_string_lossy().to_string()),
+        paused: replay.auto_pause.paused(),
+    })
 }
@@ -3,7 +3,9 @@ use clipforge_core::audio::{list_audio_sources, AudioSource};
 use clipforge_core::config::Config;
 use clipforge_core::doctor::{run_diagnostics, DiagnosticReport};
 use clipforge_core::encode::hw_probe::EncoderInfo;
-use tauri::State;
+use clipforge_core::runner::SystemRunner;
+use tauri::{AppHandle, Emitter, State};
+use tracing::info;
 
 #[tauri::command]
 pub async fn get_encoders(state: State<'_, AppState>) -> Result<Vec<EncoderInfo>, String> {
@@ -23,13 +25,54 @@ pub async fn get_config(state: State<'_, AppState>) -> Result<Config, String> {
 }
 
 #[tauri::command]
-pub async fn update_config(state: State<'_, AppState>, config: Config) -> Result<(), String> {
+pub async fn update_config(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    config: Config,
+) -> Result<(), String> {
+    config.validate().map_err(|e| e.to_string())?;
     config.save().map_err(|e| e.to_string())?;
+    crate::hotkeys::register_hotkeys(&app, &config.hotkeys);
+    crate::tray::refresh_tray(&app, &config);
     *state.config.write().await = config;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn run_doctor() -> Result<DiagnosticReport, String> {
-    Ok(run_diagnostics().await)
+pub async fn set_active_profile(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<String, String> {
+    activate_profile(&app, &state, Some(&name)).await
+}
+
+/// Apply a named profile (or the next one when `name` is `None`), persist it
+/// and refresh the tray. Used by the command, tray menu and hotkey.
+pub async fn activate_profile(
+    app: &AppHandle,
+    state: &AppState,
+    name: Option<&str>,
+) -> Result<String, String> {
+    let mut config = state.config.write().await;
+    let name = match name {
+        Some(name) => {
+            config.apply_profile(name).map_err(|e| e.to_string())?;
+            name.to_string()
+        }
+        None => config.cycle_profile().map_err(|e| e.to_string())?,
+    };
+    config.save().map_err(|e| e.to_string())?;
+    crate::tray::refresh_tray(app, &config);
+
+    let _ = app.emit("profile-changed", &name);
+    info!(profile = %name, "recording profile activated");
+    Ok(name)
+}
+
+#[tauri::command]
+pub async fn run_doctor(state: State<'_, AppState>) -> Result<DiagnosticReport, String> {
+    let config = state.config.read().await.clone();
+    let runner = SystemRunner::from_config(&config);
+    Ok(run_diagnostics(&config, &runner).await)
 }
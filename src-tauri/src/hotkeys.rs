@@ -0,0 +1,101 @@
+use crate::commands::recording::toggle_pause;
+use crate::commands::replay::save_replay_now;
+use crate::commands::system::activate_profile;
+use crate::state::AppState;
+use clipforge_core::config::{HotkeyConfig, ReplaySaveBinding};
+use clipforge_core::hotkeys::HotkeyAction;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use tracing::{info, warn};
+
+/// (Re)register global shortcuts for every bound hotkey action
+pub fn register_hotkeys(app: &AppHandle, hotkeys: &HotkeyConfig) {
+    let shortcuts = app.global_shortcut();
+    if let Err(e) = shortcuts.unregister_all() {
+        warn!(error = %e, "failed to clear global shortcuts");
+    }
+
+    for &action in HotkeyAction::all() {
+        let binding = hotkeys.binding(action);
+        if binding.is_empty() {
+            continue;
+        }
+
+        let result = shortcuts.on_shortcut(binding, move |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                dispatch(app, action);
+            }
+        });
+        match result {
+            Ok(()) => info!(binding = %binding, action = action.label(), "hotkey registered"),
+            Err(e) => {
+                warn!(error = %e, binding = %binding, action = action.label(), "failed to register hotkey")
+            }
+        }
+    }
+
+    for save in &hotkeys.replay_saves {
+        let ReplaySaveBinding {
+            seconds,
+            after_secs,
+            ..
+        } = *save;
+        let result = shortcuts.on_shortcut(save.binding.as_str(), move |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                spawn_replay_save(app, Some(seconds), after_secs);
+            }
+        });
+        match result {
+            Ok(()) => {
+                info!(binding = %save.binding, save = %save.label(), "replay save hotkey registered")
+            }
+            Err(e) => {
+                warn!(error = %e, binding = %save.binding, "failed to register replay save hotkey")
+            }
+        }
+    }
+}
+
+/// Save a replay in the background, logging failures
+pub fn spawn_replay_save(app: &AppHandle, seconds: Option<u32>, after_secs: u32) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        if let Err(e) = save_replay_now(&app, &state, seconds, after_secs).await {
+            warn!(error = %e, "failed to save replay");
+        }
+    });
+}
+
+fn dispatch(app: &AppHandle, action: HotkeyAction) {
+    match action {
+        HotkeyAction::ToggleRecording => {
+            let _ = app.emit("tray-toggle-recording", ());
+        }
+        HotkeyAction::PauseRecording => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let state = app.state::<AppState>();
+                if let Err(e) = toggle_pause(&app, &state).await {
+                    warn!(error = %e, "failed to pause or resume recording");
+                }
+            });
+        }
+        HotkeyAction::SaveReplay => spawn_replay_save(app, None, 0),
+        HotkeyAction::ToggleReplayBuffer => {
+            let _ = app.emit("tray-toggle-replay", ());
+        }
+        HotkeyAction::MarkHighlight => {
+            let _ = app.emit("hotkey-mark-highlight", ());
+        }
+        HotkeyAction::CycleProfile => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let state = app.state::<AppState>();
+                if let Err(e) = activate_profile(&app, &state, None).await {
+                    warn!(error = %e, "failed to cycle recording profile");
+                }
+            });
+        }
+    }
+}
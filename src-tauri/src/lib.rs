@@ -1,4 +1,5 @@
 mod commands;
+mod hotkeys;
 mod state;
 mod tray;
 
@@ -27,6 +28,7 @@ pub fn run() {
         tracing::warn!(error = %e, "failed to create directories");
     }
 
+    let startup_config = config.clone();
     let app_state = AppState::new(config);
 
     tauri::Builder::default()
@@ -39,33 +41,41 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             commands::recording::start_recording,
             commands::recording::stop_recording,
+            commands::recording::toggle_recording_pause,
             commands::recording::get_recording_status,
             commands::replay::toggle_replay_buffer,
             commands::replay::save_replay_clip,
             commands::replay::get_replay_status,
+            commands::replay::start_buffer_recording,
+            commands::replay::stop_buffer_recording,
             commands::export::get_export_presets,
             commands::export::start_export,
             commands::library::get_recordings,
             commands::library::search_recordings,
             commands::library::delete_recording,
             commands::library::get_recording,
+            commands::library::get_recording_stats,
             commands::system::get_encoders,
             commands::system::get_audio_sources,
             commands::system::get_config,
             commands::system::update_config,
+            commands::system::set_active_profile,
             commands::system::run_doctor,
         ])
-        .setup(|app| {
+        .setup(move |app| {
             // Set window icon (taskbar / Alt+Tab)
             if let Some(window) = app.get_webview_window("main") {
                 let _ = window.set_icon(tauri::include_image!("icons/128x128.png"));
             }
 
             // Setup tray
-            if let Err(e) = tray::setup_tray(app.handle()) {
+            if let Err(e) = tray::setup_tray(app.handle(), &startup_config) {
                 tracing::warn!(error = %e, "failed to setup tray");
             }
 
+            // Register global hotkeys
+            hotkeys::register_hotkeys(app.handle(), &startup_config.hotkeys);
+
             // Probe encoders and init library in background
             let handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
@@ -73,18 +83,12 @@ pub fn run() {
 
                 // Probe hardware encoders
                 info!("probing hardware encoders...");
-                let encoders = probe_encoders().await;
+                let encoders = probe_encoders(state.runner().await.as_ref()).await;
                 info!(count = encoders.len(), "encoder probe complete");
                 *state.encoders.write().await = encoders;
 
                 // Initialize library database
-                let config = state.config.read().await;
-                let db_path = config
-                    .paths
-                    .recordings_dir
-                    .parent()
-                    .unwrap_or(&config.paths.recordings_dir)
-                    .join("library.db");
+                let db_path = state.config.read().await.paths.library_db();
 
                 match Library::open(&db_path) {
                     Ok(lib) => {
@@ -95,6 +99,9 @@ pub fn run() {
                         tracing::error!(error = %e, "failed to open library database");
                     }
                 }
+
+                // Repair recordings a crash left unfinished
+                commands::recording::recover_unfinished(&handle, &state).await;
             });
 
             Ok(())
@@ -2,11 +2,15 @@ use clipforge_core::config::Config;
 use clipforge_core::encode::hw_probe::EncoderInfo;
 use clipforge_core::library::Library;
 use clipforge_core::process::FfmpegProcess;
-use clipforge_core::replay::ReplayRing;
+use clipforge_core::recording::RecordingSession;
+use clipforge_core::replay::daemon::ReplayLock;
+use clipforge_core::replay::{AutoPause, BufferRecording, Migration, PendingSaves, ReplayRing};
+use clipforge_core::runner::{FfmpegRunner, SystemRunner};
 use serde::Serialize;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
 
 pub struct AppState {
     pub config: Arc<RwLock<Config>>,
@@ -16,11 +20,16 @@ pub struct AppState {
     pub library: Arc<Mutex<Option<Library>>>,
 }
 
+/// The running session. Locked on its own so part changes, which wait on
+/// FFmpeg, never hold up status reads, pause or stop.
+pub type SessionSlot = Arc<Mutex<Option<RecordingSession>>>;
+
 pub struct RecorderState {
-    pub process: Option<FfmpegProcess>,
+    pub session: SessionSlot,
     pub status: RecordingStatus,
     pub output_path: Option<PathBuf>,
-    pub start_time: Option<std::time::Instant>,
+    /// Seconds recorded so far, refreshed by the timer
+    pub recorded_secs: u64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
@@ -28,6 +37,7 @@ pub enum RecordingStatus {
     Idle,
     Starting,
     Recording,
+    Paused,
     Stopping,
 }
 
@@ -35,21 +45,51 @@ pub enum RecordingStatus {
 pub struct ReplayState {
     pub process: Option<FfmpegProcess>,
     pub ring: Option<ReplayRing>,
+    /// Moves the ring's segments to its disk tier, if it has one
+    pub migration: Option<Migration>,
+    /// Held while the buffer runs so a CLI daemon can't share the ring
+    pub lock: Option<ReplayLock>,
     pub active: bool,
+    /// Full recording started from the buffered footage
+    pub recording: Option<BufferRecording>,
+    /// Task copying segments into `recording`
+    pub collector: Option<JoinHandle<()>>,
+    /// Look-ahead saves waiting for the ring to record their end
+    pub pending: PendingSaves,
+    /// Pauses the capture while nobody plays; `process` is `None` then
+    pub auto_pause: AutoPause,
+    /// Task applying `auto_pause` while the buffer runs
+    pub pause_watcher: Option<JoinHandle<()>>,
+}
+
+impl ReplayState {
+    /// End the recording from the buffer and stop its collector, so a new
+    /// recording never has two copying into it
+    pub fn take_recording(&mut self) -> Option<BufferRecording> {
+        if let Some(collector) = self.collector.take() {
+            collector.abort();
+        }
+        self.recording.take()
+    }
 }
 
 impl Default for RecorderState {
     fn default() -> Self {
         Self {
-            process: None,
+            session: Arc::new(Mutex::new(None)),
             status: RecordingStatus::Idle,
             output_path: None,
-            start_time: None,
+            recorded_secs: 0,
         }
     }
 }
 
 impl AppState {
+    /// FFmpeg runner honoring the configured binary paths
+    pub async fn runner(&self) -> Arc<dyn FfmpegRunner> {
+        Arc::new(SystemRunner::from_config(&*self.config.read().await))
+    }
+
     pub fn new(config: Config) -> Self {
         Self {
             config: Arc::new(RwLock::new(config)),
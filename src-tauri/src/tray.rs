@@ -1,25 +1,21 @@
+use crate::commands::system::activate_profile;
+use crate::hotkeys::spawn_replay_save;
+use crate::state::AppState;
+use clipforge_core::config::Config;
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem, Submenu},
     tray::TrayIconBuilder,
-    AppHandle, Emitter, Manager,
+    AppHandle, Emitter, Manager, Wry,
 };
 
-pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-    let show = MenuItem::with_id(app, "show", "Show ClipForge", true, None::<&str>)?;
-    let record = MenuItem::with_id(app, "record", "Start Recording", true, None::<&str>)?;
-    let replay_toggle = MenuItem::with_id(
-        app,
-        "replay_toggle",
-        "Enable Replay Buffer",
-        true,
-        None::<&str>,
-    )?;
-    let replay_save = MenuItem::with_id(app, "replay_save", "Save Replay", true, None::<&str>)?;
-    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+const TRAY_ID: &str = "main";
+const PROFILE_ITEM_PREFIX: &str = "profile:";
+const REPLAY_SAVE_ITEM_PREFIX: &str = "replay_save:";
 
-    let menu = Menu::with_items(app, &[&show, &record, &replay_toggle, &replay_save, &quit])?;
+pub fn setup_tray(app: &AppHandle, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let menu = build_menu(app, config)?;
 
-    let _tray = TrayIconBuilder::new()
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
         .icon(tauri::include_image!("icons/32x32.png"))
         .icon_as_template(false)
         .menu(&menu)
@@ -45,19 +41,126 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
                         let _ = app.emit("tray-toggle-replay", ());
                     });
                 }
-                "replay_save" => {
-                    let app = app.clone();
-                    tokio::spawn(async move {
-                        let _ = app.emit("tray-save-replay", ());
-                    });
-                }
+                "replay_save" => spawn_replay_save(app, None, 0),
                 "quit" => {
                     app.exit(0);
                 }
-                _ => {}
+                id => {
+                    if let Some(index) = id.strip_prefix(REPLAY_SAVE_ITEM_PREFIX) {
+                        let app = app.clone();
+                        let index: usize = index.parse().unwrap_or(usize::MAX);
+                        tauri::async_runtime::spawn(async move {
+                            let state = app.state::<AppState>();
+                            let save = state.config.read().await.hotkeys.replay_saves.get(index).cloned();
+                            if let Some(save) = save {
+                                spawn_replay_save(&app, Some(save.seconds), save.after_secs);
+                            }
+                        });
+                    } else if let Some(name) = id.strip_prefix(PROFILE_ITEM_PREFIX) {
+                        let app = app.clone();
+                        let name = name.to_string();
+                        tauri::async_runtime::spawn(async move {
+                            let state = app.state::<AppState>();
+                            if let Err(e) = activate_profile(&app, &state, Some(&name)).await {
+                                tracing::warn!(error = %e, profile = %name, "failed to switch profile");
+                            }
+                        });
+                    }
+                }
             }
         })
         .build(app)?;
 
     Ok(())
 }
+
+/// Rebuild the tray menu so it reflects the current config (e.g. active profile)
+pub fn refresh_tray(app: &AppHandle, config: &Config) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    match build_menu(app, config) {
+        Ok(menu) => {
+            if let Err(e) = tray.set_menu(Some(menu)) {
+                tracing::warn!(error = %e, "failed to update tray menu");
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "failed to rebuild tray menu"),
+    }
+}
+
+fn build_menu(app: &AppHandle, config: &Config) -> tauri::Result<Menu<Wry>> {
+    let show = MenuItem::with_id(app, "show", "Show ClipForge", true, None::<&str>)?;
+    let record = MenuItem::with_id(app, "record", "Start Recording", true, None::<&str>)?;
+    let replay_toggle = MenuItem::with_id(
+        app,
+        "replay_toggle",
+        "Enable Replay Buffer",
+        true,
+        None::<&str>,
+    )?;
+    // One entry per save length, showing the hotkey bound to it
+    let replay_save = Submenu::with_id(app, "replay_saves", "Save Replay", true)?;
+    let default_save = MenuItem::with_id(
+        app,
+        "replay_save",
+        with_binding(
+            format!("Last {}s", config.replay.save_secs),
+            &config.hotkeys.save_replay,
+        ),
+        true,
+        None::<&str>,
+    )?;
+    replay_save.append(&default_save)?;
+    for (i, save) in config.hotkeys.replay_saves.iter().enumerate() {
+        let item = MenuItem::with_id(
+            app,
+            format!("{REPLAY_SAVE_ITEM_PREFIX}{i}"),
+            with_binding(save.label(), &save.binding),
+            true,
+            None::<&str>,
+        )?;
+        replay_save.append(&item)?;
+    }
+
+    let profiles = Submenu::with_id(
+        app,
+        "profiles",
+        "Recording Profile",
+        !config.profiles.is_empty(),
+    )?;
+    for profile in &config.profiles {
+        let active = config.active_profile.as_deref() == Some(profile.name.as_str());
+        let item = CheckMenuItem::with_id(
+            app,
+            format!("{PROFILE_ITEM_PREFIX}{}", profile.name),
+            &profile.name,
+            true,
+            active,
+            None::<&str>,
+        )?;
+        profiles.append(&item)?;
+    }
+
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+
+    Menu::with_items(
+        app,
+        &[
+            &show,
+            &record,
+            &replay_toggle,
+            &replay_save,
+            &profiles,
+            &quit,
+        ],
+    )
+}
+
+fn with_binding(label: String, binding: &str) -> String {
+    if binding.is_empty() {
+        label
+    } else {
+        format!("{label} ({binding})")
+    }
+}
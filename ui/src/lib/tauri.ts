@@ -3,7 +3,7 @@ import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 
 // Recording
 export interface RecordingState {
-  status: "Idle" | "Starting" | "Recording" | "Stopping";
+  status: "Idle" | "Starting" | "Recording" | "Paused" | "Stopping";
   elapsed_secs: number;
   file_path: string | null;
 }
@@ -16,6 +16,13 @@ export async function stopRecording(): Promise<string> {
   return invoke("stop_recording");
 }
 
+// Resolves to the new status, "Paused" or "Recording"
+export async function toggleRecordingPause(): Promise<
+  RecordingState["status"]
+> {
+  return invoke("toggle_recording_pause");
+}
+
 export async function getRecordingStatus(): Promise<RecordingState> {
   return invoke("get_recording_status");
 }
@@ -36,19 +43,120 @@ export function onRecordingTimer(
   );
 }
 
+export function onRecordingStalled(
+  callback: (partPath: string) => void
+): Promise<UnlistenFn> {
+  return listen<string>("recording-stalled", (event) =>
+    callback(event.payload)
+  );
+}
+
+export interface DropWarning {
+  dropped: number;
+  frames: number;
+  percent: number;
+}
+
+export function onRecordingPartStarted(
+  callback: (partPath: string) => void
+): Promise<UnlistenFn> {
+  return listen<string>("recording-part-started", (event) =>
+    callback(event.payload)
+  );
+}
+
+export function onRecordingsRecovered(
+  callback: (paths: string[]) => void
+): Promise<UnlistenFn> {
+  return listen<string[]>("recordings-recovered", (event) =>
+    callback(event.payload)
+  );
+}
+
+export function onRecordingDropWarning(
+  callback: (warning: DropWarning) => void
+): Promise<UnlistenFn> {
+  return listen<DropWarning>("recording-drop-warning", (event) =>
+    callback(event.payload)
+  );
+}
+
+export type FailureReason =
+  | "DeviceBusy"
+  | "EncoderInit"
+  | "DiskFull"
+  | "MissingInput"
+  | "Unknown";
+
+export interface ProcessExit {
+  code: number | null;
+  failure: FailureReason | null;
+  stderr_tail: string[];
+}
+
+export function onRecordingFailed(
+  callback: (exit: ProcessExit) => void
+): Promise<UnlistenFn> {
+  return listen<ProcessExit>("recording-failed", (event) =>
+    callback(event.payload)
+  );
+}
+
 // Replay
+export interface PendingSaveStatus {
+  id: number;
+  output: string;
+  window_secs: number;
+  remaining_secs: number;
+}
+
+export type PauseReason =
+  | "locked"
+  | "screen_blanked"
+  | "idle"
+  | "no_game_running"
+  | "not_fullscreen";
+
+export const pauseReasonLabels: Record<PauseReason, string> = {
+  locked: "screen locked",
+  screen_blanked: "screen blanked",
+  idle: "no input",
+  no_game_running: "no game running",
+  not_fullscreen: "no fullscreen window",
+};
+
+export interface ReplayStatus {
+  active: boolean;
+  pending_saves: PendingSaveStatus[];
+  recording: string | null;
+  paused: PauseReason | null;
+}
+
 export async function toggleReplayBuffer(): Promise<boolean> {
   return invoke("toggle_replay_buffer");
 }
 
-export async function saveReplayClip(seconds?: number): Promise<string> {
-  return invoke("save_replay_clip", { seconds });
+// With afterSecs, resolves once the clip is queued; it is written after
+// that much more footage is recorded
+export async function saveReplayClip(
+  seconds?: number,
+  afterSecs?: number
+): Promise<string> {
+  return invoke("save_replay_clip", { seconds, afterSecs });
 }
 
-export async function getReplayStatus(): Promise<boolean> {
+export async function getReplayStatus(): Promise<ReplayStatus> {
   return invoke("get_replay_status");
 }
 
+export async function startBufferRecording(): Promise<string> {
+  return invoke("start_buffer_recording");
+}
+
+export async function stopBufferRecording(): Promise<string> {
+  return invoke("stop_buffer_recording");
+}
+
 export function onReplayStateChanged(
   callback: (active: boolean) => void
 ): Promise<UnlistenFn> {
@@ -57,6 +165,50 @@ export function onReplayStateChanged(
   );
 }
 
+export function onReplayPaused(
+  callback: (reason: PauseReason) => void
+): Promise<UnlistenFn> {
+  return listen<PauseReason>("replay-paused", (event) =>
+    callback(event.payload)
+  );
+}
+
+export function onReplayResumed(callback: () => void): Promise<UnlistenFn> {
+  return listen("replay-resumed", () => callback());
+}
+
+export function onReplayFailed(
+  callback: (exit: ProcessExit) => void
+): Promise<UnlistenFn> {
+  return listen<ProcessExit>("replay-failed", (event) =>
+    callback(event.payload)
+  );
+}
+
+export function onReplayBufferWarning(
+  callback: (message: string) => void
+): Promise<UnlistenFn> {
+  return listen<string>("replay-buffer-warning", (event) =>
+    callback(event.payload)
+  );
+}
+
+export function onBufferRecordingChanged(
+  callback: (path: string | null) => void
+): Promise<UnlistenFn> {
+  return listen<string | null>("buffer-recording-changed", (event) =>
+    callback(event.payload)
+  );
+}
+
+export function onReplaySavePending(
+  callback: (pending: PendingSaveStatus[]) => void
+): Promise<UnlistenFn> {
+  return listen<PendingSaveStatus[]>("replay-save-pending", (event) =>
+    callback(event.payload)
+  );
+}
+
 export function onReplaySaved(
   callback: (path: string) => void
 ): Promise<UnlistenFn> {
@@ -89,6 +241,7 @@ export async function startExport(params: {
   trim_start?: number;
   trim_end?: number;
   output?: string;
+  tonemap_sdr?: boolean;
 }): Promise<string> {
   return invoke("start_export", params);
 }
@@ -116,6 +269,13 @@ export interface Recording {
   game_name: string | null;
   created_at: string;
   thumbnail_path: string | null;
+  // Shared by the parts of a split recording, numbered from 1
+  session_id: string | null;
+  part: number | null;
+  // Salvaged after a crash cut the recording short
+  recovered: boolean;
+  // Number of parts in the whole session, counted by the library
+  part_count: number | null;
 }
 
 export async function getRecordings(
@@ -133,12 +293,30 @@ export async function deleteRecording(id: string): Promise<void> {
   return invoke("delete_recording", { id });
 }
 
+export interface PerformanceSummary {
+  avg_fps: number;
+  low_1pct_fps: number;
+  total_drops: number;
+  total_dups: number;
+  avg_speed: number;
+  samples: number;
+}
+
+export async function getRecordingStats(
+  id: string
+): Promise<PerformanceSummary | null> {
+  return invoke("get_recording_stats", { id });
+}
+
 // System
 export interface EncoderInfo {
   name: string;
   hw_accel: string;
   available: boolean;
   device: string | null;
+  ten_bit_codecs: ("Hevc" | "Av1")[];
+  // FFmpeg can convert the capture to BT.2020/PQ for HDR output
+  hdr_conversion: boolean;
 }
 
 export interface AudioSource {
@@ -174,6 +352,18 @@ export async function updateConfig(config: any): Promise<void> {
   return invoke("update_config", { config });
 }
 
+export async function setActiveProfile(name: string): Promise<string> {
+  return invoke("set_active_profile", { name });
+}
+
+export function onProfileChanged(
+  callback: (name: string) => void
+): Promise<UnlistenFn> {
+  return listen<string>("profile-changed", (event) =>
+    callback(event.payload)
+  );
+}
+
 export async function runDoctor(): Promise<DiagnosticReport> {
   return invoke("run_doctor");
 }
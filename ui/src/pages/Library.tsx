@@ -1,9 +1,10 @@
-import { createSignal, onMount, For, Show } from "solid-js";
+import { createSignal, onCleanup, onMount, For, Show } from "solid-js";
 import { useNavigate } from "@solidjs/router";
 import {
   getRecordings,
   searchRecordings,
   deleteRecording,
+  onRecordingsRecovered,
   type Recording,
 } from "../lib/tauri";
 import { convertFileSrc } from "@tauri-apps/api/core";
@@ -21,6 +22,15 @@ function formatSize(bytes: number): string {
   return `${(bytes / (1024 * 1024 * 1024)).toFixed(2)} GB`;
 }
 
+// Parts of a split recording are listed together, so label each with its
+// position in the session
+function partLabel(rec: Recording): string | null {
+  if (!rec.session_id || rec.part === null || rec.part_count === null) {
+    return null;
+  }
+  return `Part ${rec.part} of ${rec.part_count}`;
+}
+
 export default function LibraryPage() {
   const navigate = useNavigate();
   const [recordings, setRecordings] = createSignal<Recording[]>([]);
@@ -29,6 +39,12 @@ export default function LibraryPage() {
   onMount(async () => {
     const recs = await getRecordings();
     setRecordings(recs);
+
+    // Recordings repaired at startup may land after the first load
+    const unlisten = await onRecordingsRecovered(async () => {
+      if (!query().trim()) setRecordings(await getRecordings());
+    });
+    onCleanup(unlisten);
   });
 
   async function handleSearch() {
@@ -95,6 +111,17 @@ export default function LibraryPage() {
                     <span>{formatDuration(rec.duration)}</span>
                     <span>{rec.resolution}</span>
                     <span>{formatSize(rec.file_size)}</span>
+                    <Show when={partLabel(rec)}>
+                      {(label) => <span>{label()}</span>}
+                    </Show>
+                    <Show when={rec.recovered}>
+                      <span
+                        class="recovered-badge"
+                        title="Repaired after the recording was cut short"
+                      >
+                        Recovered
+                      </span>
+                    </Show>
                   </div>
                   <div class="card-actions">
                     <button
@@ -1,5 +1,6 @@
 import { Show } from "solid-js";
 import { useRecording } from "../stores/recording";
+import { pauseReasonLabels } from "../lib/tauri";
 
 function formatTime(seconds: number): string {
   const h = Math.floor(seconds / 3600);
@@ -10,23 +11,39 @@ function formatTime(seconds: number): string {
 }
 
 export default function Recorder() {
-  const { state, timer, replayActive, toggleRecord, toggleReplay, saveReplay } =
-    useRecording();
+  const {
+    state,
+    timer,
+    replayActive,
+    replayPaused,
+    toggleRecord,
+    togglePause,
+    toggleReplay,
+    saveReplay,
+  } = useRecording();
 
   const isRecording = () => state().status === "Recording";
+  const isPaused = () => state().status === "Paused";
   const isIdle = () => state().status === "Idle";
   const isBusy = () =>
     state().status === "Starting" || state().status === "Stopping";
+  const replayLabel = () => {
+    const paused = replayPaused();
+    if (paused) return `(Paused: ${pauseReasonLabels[paused]})`;
+    return replayActive() ? "(Active)" : "(Off)";
+  };
 
   return (
     <div style="display: flex; flex-direction: column; height: 100%">
       <div class="record-container" style="flex: 1">
-        <Show when={isRecording()}>
+        <Show when={isRecording() || isPaused()}>
           <div class="record-timer">{formatTime(timer())}</div>
         </Show>
 
         <button
-          class={`record-btn ${isRecording() ? "recording" : ""}`}
+          class={`record-btn ${isRecording() ? "recording" : ""} ${
+            isPaused() ? "paused" : ""
+          }`}
           onClick={toggleRecord}
           disabled={isBusy()}
         >
@@ -37,9 +54,16 @@ export default function Recorder() {
           <Show when={isIdle()}>Click to start recording</Show>
           <Show when={state().status === "Starting"}>Starting...</Show>
           <Show when={isRecording()}>Recording</Show>
+          <Show when={isPaused()}>Paused</Show>
           <Show when={state().status === "Stopping"}>Stopping...</Show>
         </div>
 
+        <Show when={isRecording() || isPaused()}>
+          <button class="record-pause-btn" onClick={togglePause}>
+            {isPaused() ? "Resume" : "Pause"}
+          </button>
+        </Show>
+
         <Show when={state().file_path}>
           <div class="record-file">{state().file_path}</div>
         </Show>
@@ -51,7 +75,7 @@ export default function Recorder() {
           onClick={toggleReplay}
         />
         <span class="replay-label">
-          Replay Buffer {replayActive() ? "(Active)" : "(Off)"}
+          Replay Buffer {replayLabel()}
         </span>
         <button
           class="replay-save-btn"
@@ -86,6 +86,23 @@ export default function SettingsPage() {
               </For>
             </select>
           </div>
+          <div class="setting-row">
+            <div>
+              <div class="setting-label">Crash-Safe Files</div>
+              <div class="setting-desc">
+                Keep recordings playable if the app or system crashes
+              </div>
+            </div>
+            <input
+              type="checkbox"
+              checked={config()?.recording?.crash_safe ?? true}
+              onChange={(e) => {
+                const c = { ...config() };
+                c.recording = { ...c.recording, crash_safe: e.currentTarget.checked };
+                setConfig(c);
+              }}
+            />
+          </div>
         </div>
 
         <div class="settings-section">
@@ -125,6 +142,18 @@ export default function SettingsPage() {
               }}
             />
           </div>
+          <div class="setting-row">
+            <div class="setting-label">Pause/Resume Recording</div>
+            <input
+              type="text"
+              value={config()?.hotkeys?.pause_recording ?? ""}
+              onInput={(e) => {
+                const c = { ...config() };
+                c.hotkeys = { ...c.hotkeys, pause_recording: e.currentTarget.value };
+                setConfig(c);
+              }}
+            />
+          </div>
           <div class="setting-row">
             <div class="setting-label">Save Replay</div>
             <input
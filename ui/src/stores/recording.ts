@@ -2,13 +2,20 @@ import { createSignal, onCleanup, onMount } from "solid-js";
 import {
   startRecording,
   stopRecording,
+  toggleRecordingPause,
   getRecordingStatus,
   onRecordingStateChanged,
   onRecordingTimer,
+  onRecordingPartStarted,
   toggleReplayBuffer,
   saveReplayClip,
   getReplayStatus,
   onReplayStateChanged,
+  onReplaySavePending,
+  onReplayPaused,
+  onReplayResumed,
+  type PauseReason,
+  type PendingSaveStatus,
   type RecordingState,
 } from "../lib/tauri";
 
@@ -20,49 +27,78 @@ export function useRecording() {
   });
   const [timer, setTimer] = createSignal(0);
   const [replayActive, setReplayActive] = createSignal(false);
+  const [replayPaused, setReplayPaused] = createSignal<PauseReason | null>(
+    null
+  );
+  const [pendingSaves, setPendingSaves] = createSignal<PendingSaveStatus[]>(
+    []
+  );
 
   onMount(async () => {
     const status = await getRecordingStatus();
     setState(status);
 
     const replay = await getReplayStatus();
-    setReplayActive(replay);
+    setReplayActive(replay.active);
+    setPendingSaves(replay.pending_saves);
+    setReplayPaused(replay.paused);
 
     const unlisten1 = await onRecordingStateChanged((s) => setState(s));
     const unlisten2 = await onRecordingTimer((secs) => setTimer(secs));
-    const unlisten3 = await onReplayStateChanged((active) =>
-      setReplayActive(active)
+    const unlisten3 = await onReplayStateChanged((active) => {
+      setReplayActive(active);
+      setReplayPaused(null);
+    });
+    const unlisten4 = await onReplaySavePending((pending) =>
+      setPendingSaves(pending)
+    );
+    const unlisten5 = await onReplayPaused((reason) => setReplayPaused(reason));
+    const unlisten6 = await onReplayResumed(() => setReplayPaused(null));
+    const unlisten7 = await onRecordingPartStarted((path) =>
+      setState((s) => ({ ...s, file_path: path }))
     );
 
     onCleanup(() => {
       unlisten1();
       unlisten2();
       unlisten3();
+      unlisten4();
+      unlisten5();
+      unlisten6();
+      unlisten7();
     });
   });
 
   async function toggleRecord() {
-    if (state().status === "Recording") {
+    const status = state().status;
+    if (status === "Recording" || status === "Paused") {
       await stopRecording();
     } else if (state().status === "Idle") {
       await startRecording();
     }
   }
 
+  async function togglePause() {
+    await toggleRecordingPause();
+  }
+
   async function toggleReplay() {
     const active = await toggleReplayBuffer();
     setReplayActive(active);
   }
 
-  async function saveReplay(seconds?: number) {
-    return saveReplayClip(seconds);
+  async function saveReplay(seconds?: number, afterSecs?: number) {
+    return saveReplayClip(seconds, afterSecs);
   }
 
   return {
     state,
     timer,
     replayActive,
+    replayPaused,
+    pendingSaves,
     toggleRecord,
+    togglePause,
     toggleReplay,
     saveReplay,
   };